@@ -0,0 +1,346 @@
+//! ncdu style disk usage analysis: a directory tree scanned in the background, whose
+//! directories can be browsed biggest first.
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::fmt;
+
+/// progress is reported at most this often
+const REPORT_EVERY: Duration = Duration::from_millis(100);
+/// characters of a percentage bar
+const BAR: usize = 10;
+
+/// index into a [`Scan`]'s nodes
+pub type NodeId = usize;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NodeState {
+    /// a directory not read yet, the scan was cancelled before
+    Pending,
+    Done,
+    /// a directory that couldn't be read
+    Unreadable,
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: OsString,
+    pub parent: Option<NodeId>,
+    pub is_dir: bool,
+    /// of the node and everything in it found so far, hard links are counted once
+    pub bytes: u64,
+    pub state: NodeState,
+    pub children: Vec<NodeId>,
+    /// (device, inode) of a file with several links, if it's the link its bytes count for
+    pub linked: Option<(u64, u64)>,
+}
+
+/// A directory tree and the sizes found in it, possibly partial. A cancelled scan resumes
+/// where it stopped.
+#[derive(Clone, Debug)]
+pub struct Scan {
+    pub root: PathBuf,
+    nodes: Vec<Node>,
+    /// directories left to read, the next one last
+    pending: Vec<NodeId>,
+    /// (device, inode) of the files with several links already counted
+    linked: HashSet<(u64, u64)>,
+    /// skips the directories on other filesystems than the root's, `Some` of its device
+    device: Option<u64>,
+    /// entries found so far
+    pub items: u64,
+}
+
+impl Scan {
+    pub const ROOT: NodeId = 0;
+
+    /// a scan of `root` that hasn't started, `one_filesystem` like `du -x`
+    pub fn new(root: PathBuf, one_filesystem: bool) -> io::Result<Self> {
+        let meta = fs::symlink_metadata(&root)?;
+        if !meta.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"));
+        }
+        let node = Node {
+            name: root.as_os_str().to_owned(),
+            parent: None,
+            is_dir: true,
+            bytes: 0,
+            state: NodeState::Pending,
+            children: vec![],
+            linked: None,
+        };
+        let mut scan = Self {
+            root,
+            nodes: vec![node],
+            pending: vec![0],
+            linked: HashSet::new(),
+            device: if one_filesystem { Some(meta.dev()) } else { None },
+            items: 1,
+        };
+        scan.add_bytes(0, meta.len());
+        Ok(scan)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// whether it skips the directories on other filesystems than the root's
+    pub fn one_filesystem(&self) -> bool {
+        self.device.is_some()
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    /// where the node is on disk
+    pub fn path(&self, id: NodeId) -> PathBuf {
+        let mut names = vec![];
+        let mut node = id;
+        while let Some(parent) = self.nodes[node].parent {
+            names.push(&self.nodes[node].name);
+            node = parent;
+        }
+        let mut path = self.root.clone();
+        path.extend(names.into_iter().rev());
+        path
+    }
+
+    /// the node at `path`, if it's in the tree
+    pub fn find(&self, path: &Path) -> Option<NodeId> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let mut node = Self::ROOT;
+        for name in relative {
+            let children = &self.nodes[node].children;
+            node = *children.iter().find(|&&c| self.nodes[c].name == name)?;
+        }
+        Some(node)
+    }
+
+    /// adds `bytes` to the node and its ancestors
+    fn add_bytes(&mut self, id: NodeId, bytes: u64) {
+        let mut node = Some(id);
+        while let Some(id) = node {
+            self.nodes[id].bytes += bytes;
+            node = self.nodes[id].parent;
+        }
+    }
+
+    /// Reads the pending directories until there are none left or `cancel` is set.
+    /// `progress` is called now and then.
+    pub fn run(&mut self, cancel: &AtomicBool, mut progress: impl FnMut(&Self)) {
+        let mut reported = Instant::now();
+        while let Some(dir) = self.pending.pop() {
+            if cancel.load(Ordering::Relaxed) {
+                self.pending.push(dir);
+                return;
+            }
+            self.read(dir);
+            if reported.elapsed() >= REPORT_EVERY {
+                progress(self);
+                reported = Instant::now();
+            }
+        }
+    }
+
+    /// adds the entries of the directory `dir`, the directories among them are pending
+    fn read(&mut self, dir: NodeId) {
+        let entries = match fs::read_dir(self.path(dir)) {
+            Ok(entries) => entries,
+            Err(_) => {
+                self.nodes[dir].state = NodeState::Unreadable;
+                return;
+            }
+        };
+        self.nodes[dir].state = NodeState::Done;
+        for entry in entries.flatten() {
+            let meta = match entry.metadata() {
+                Ok(meta) => meta,
+                Err(_) => continue,
+            };
+            if meta.is_dir() && matches!(self.device, Some(device) if device != meta.dev()) {
+                continue;
+            }
+            let inode = (meta.dev(), meta.ino());
+            let linked = !meta.is_dir() && meta.nlink() > 1;
+            let counted = !linked || self.linked.insert(inode);
+            let id = self.nodes.len();
+            self.nodes.push(Node {
+                name: entry.file_name(),
+                parent: Some(dir),
+                is_dir: meta.is_dir(),
+                bytes: 0,
+                state: if meta.is_dir() { NodeState::Pending } else { NodeState::Done },
+                children: vec![],
+                linked: if linked && counted { Some(inode) } else { None },
+            });
+            self.nodes[dir].children.push(id);
+            self.items += 1;
+            if counted {
+                self.add_bytes(id, meta.len());
+            }
+            if meta.is_dir() {
+                self.pending.push(id);
+            }
+        }
+    }
+
+    /// the children of `dir`, biggest first
+    pub fn children(&self, dir: NodeId) -> Vec<NodeId> {
+        let mut children = self.nodes[dir].children.clone();
+        let nodes = &self.nodes;
+        let order = |&a: &NodeId, &b: &NodeId| {
+            nodes[b].bytes.cmp(&nodes[a].bytes).then_with(|| nodes[a].name.cmp(&nodes[b].name))
+        };
+        children.sort_by(order);
+        children
+    }
+
+    /// Takes the node out of the tree, once deleted from disk. Its nodes stay allocated,
+    /// only unreachable.
+    pub fn remove(&mut self, id: NodeId) {
+        let parent = match self.nodes[id].parent {
+            Some(parent) => parent,
+            None => return,
+        };
+        self.nodes[parent].children.retain(|&c| c != id);
+        let bytes = self.nodes[id].bytes;
+        let mut node = Some(parent);
+        while let Some(id) = node {
+            self.nodes[id].bytes -= bytes;
+            node = self.nodes[id].parent;
+        }
+        let mut removed = vec![id];
+        while let Some(id) = removed.pop() {
+            removed.extend(self.nodes[id].children.iter().copied());
+            // another link of it counts once read again
+            if let Some(inode) = self.nodes[id].linked {
+                self.linked.remove(&inode);
+            }
+            self.pending.retain(|&p| p != id);
+            self.items -= 1;
+        }
+    }
+
+    /// Takes what's in the node out of the tree and makes it pending again, so resuming the
+    /// scan reads it anew, or takes the node out if it's gone from the disk. For when
+    /// deleting it failed halfway.
+    pub fn forget(&mut self, id: NodeId) {
+        if fs::symlink_metadata(self.path(id)).is_err() {
+            return self.remove(id);
+        }
+        if !self.nodes[id].is_dir {
+            return;
+        }
+        for child in self.nodes[id].children.clone() {
+            self.remove(child);
+        }
+        self.nodes[id].state = NodeState::Pending;
+        if !self.pending.contains(&id) {
+            self.pending.push(id);
+        }
+    }
+
+    /// `  1.2G  42.0% [####      ] name/` for the node, its share of its parent's size
+    pub fn line(&self, id: NodeId) -> String {
+        let node = &self.nodes[id];
+        let total = node.parent.map_or(node.bytes, |p| self.nodes[p].bytes);
+        let share = if total == 0 { 0. } else { node.bytes as f64 / total as f64 };
+        let filled = (share * BAR as f64).round() as usize;
+        let bar = format!("{}{}", "#".repeat(filled), " ".repeat(BAR - filled));
+        let suffix = match (node.is_dir, node.state) {
+            (false, _) => "",
+            (true, NodeState::Done) => "/",
+            (true, NodeState::Pending) => "/ (not scanned)",
+            (true, NodeState::Unreadable) => "/ (unreadable)",
+        };
+        let (size, name) = (fmt::human_size(node.bytes), node.name.to_string_lossy());
+        format!("{:>6} {:>5.1}% [{}] {}{}", size, share * 100., bar, name, suffix)
+    }
+
+    /// `/path: 12G, 4567 items` and whether it's complete
+    pub fn summary(&self) -> String {
+        let size = fmt::human_size(self.nodes[Self::ROOT].bytes);
+        let mut summary = format!("{}: {}, {} items", self.root.display(), size, self.items);
+        if !self.is_complete() {
+            summary.push_str(", incomplete");
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod test_analyzer {
+    use super::*;
+    use crate::TestDir;
+
+    #[test]
+    fn test_scan() {
+        let dir = TestDir::new("scan");
+        fs::create_dir_all(dir.join("big/deep")).unwrap();
+        fs::write(dir.join("big/deep/a"), vec![0; 3000]).unwrap();
+        fs::hard_link(dir.join("big/deep/a"), dir.join("big/a")).unwrap();
+        fs::write(dir.join("small"), vec![0; 100]).unwrap();
+        let mut scan = Scan::new(dir.to_path_buf(), true).unwrap();
+        scan.run(&AtomicBool::new(true), |_| ());
+        assert!(!scan.is_complete());
+        scan.run(&AtomicBool::new(false), |_| ());
+        assert!(scan.is_complete());
+        assert_eq!(scan.items, 6);
+        let dir_len = |path: &str| fs::metadata(dir.join(path)).unwrap().len();
+        let big = scan.find(&dir.join("big")).unwrap();
+        assert_eq!(scan.node(big).bytes, 3000 + dir_len("big") + dir_len("big/deep"));
+        assert_eq!(scan.children(Scan::ROOT)[0], big);
+        assert_eq!(scan.path(big), dir.join("big"));
+        let small = scan.find(&dir.join("small")).unwrap();
+        assert!(scan.line(small).ends_with("] small"));
+        let total = scan.node(Scan::ROOT).bytes;
+        scan.remove(big);
+        assert_eq!(scan.node(Scan::ROOT).bytes, total - scan.node(big).bytes);
+        assert_eq!(scan.children(Scan::ROOT), vec![small]);
+        assert_eq!(scan.items, 2);
+
+        let mut scan = Scan::new(dir.to_path_buf(), false).unwrap();
+        scan.run(&AtomicBool::new(false), |_| ());
+        let big = scan.find(&dir.join("big")).unwrap();
+        let big_bytes = scan.node(big).bytes;
+        scan.forget(big);
+        assert_eq!((scan.node(big).state, scan.items), (NodeState::Pending, 3));
+        assert!(scan.children(big).is_empty() && !scan.is_complete());
+        scan.run(&AtomicBool::new(false), |_| ());
+        assert_eq!(scan.items, 6);
+        assert_eq!(scan.node(big).bytes, big_bytes);
+        let small = scan.find(&dir.join("small")).unwrap();
+        fs::remove_file(dir.join("small")).unwrap();
+        scan.forget(small);
+        assert_eq!(scan.children(Scan::ROOT), vec![big]);
+    }
+
+    #[test]
+    fn test_line() {
+        let dir = TestDir::new("line");
+        let mut scan = Scan::new(dir.to_path_buf(), false).unwrap();
+        let root = scan.nodes[Scan::ROOT].bytes;
+        scan.nodes.push(Node {
+            name: "half".into(),
+            parent: Some(Scan::ROOT),
+            is_dir: true,
+            bytes: 0,
+            state: NodeState::Pending,
+            children: vec![],
+            linked: None,
+        });
+        scan.nodes[Scan::ROOT].children.push(1);
+        scan.add_bytes(1, root);
+        let size = fmt::human_size(root);
+        assert_eq!(scan.line(1), format!("{:>6}  50.0% [#####     ] half/ (not scanned)", size));
+    }
+}
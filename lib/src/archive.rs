@@ -0,0 +1,315 @@
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+use crate::fileops::unique_path;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    TarGz,
+    TarZst,
+}
+
+/// (suffix, format), longest suffixes first
+const SUFFIXES: [(&str, Format); 5] = [
+    (".tar.zst", Format::TarZst),
+    (".tar.gz", Format::TarGz),
+    (".tzst", Format::TarZst),
+    (".tgz", Format::TarGz),
+    (".zip", Format::Zip),
+];
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Zip => "zip",
+            Format::TarGz => "tar.gz",
+            Format::TarZst => "tar.zst",
+        }
+    }
+
+    /// guessed from the file name
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let name = path.as_ref().file_name()?.to_str()?.to_lowercase();
+        SUFFIXES.iter().find(|(s, _)| name.ends_with(s)).map(|(_, f)| *f)
+    }
+}
+
+/// `name` without its archive suffix, e.g. `foo` for `foo.tar.gz`
+pub fn stem(name: &str) -> Option<&str> {
+    let lower = name.to_lowercase();
+    SUFFIXES
+        .iter()
+        .find(|(s, _)| lower.ends_with(s))
+        .map(|(s, _)| &name[..name.len() - s.len()])
+        .filter(|s| !s.is_empty())
+}
+
+/// Creates the archive `dest` (format guessed from its name) containing `sources`
+/// and everything below them, stored relative to their parent directory.
+/// `progress` is called with (entries written, total entries). Returns how many symlinks
+/// were left out, zip can't store them.
+pub fn compress(
+    sources: &[PathBuf],
+    dest: &Path,
+    mut progress: impl FnMut(u64, u64),
+) -> io::Result<usize> {
+    let format = Format::from_path(dest).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "unknown archive format")
+    })?;
+    let entries = collect(sources)?;
+    let total = entries.len() as u64;
+    // never clobber an existing file
+    let file = OpenOptions::new().write(true).create_new(true).open(dest)?;
+
+    let result = match format {
+        Format::Zip => write_zip(file, &entries, |n| progress(n, total)),
+        Format::TarGz => {
+            let gz = GzEncoder::new(file, Compression::default());
+            write_tar(gz, &entries, |n| progress(n, total)).and_then(|gz| gz.finish().map(|_| 0))
+        }
+        Format::TarZst => zstd::Encoder::new(file, 0)
+            .and_then(|zst| write_tar(zst, &entries, |n| progress(n, total)))
+            .and_then(|zst| zst.finish().map(|_| 0)),
+    };
+    if result.is_err() {
+        let _ = fs::remove_file(dest);
+    }
+    result
+}
+
+/// Extracts `archive` next to itself and returns the created directory.
+/// If everything in the archive lives under a single top-level directory that
+/// directory is used as is, otherwise a new one named after the archive is created.
+/// `progress` is called with (done, total) in entries for zip, compressed bytes for tar.
+pub fn extract(archive: &Path, mut progress: impl FnMut(u64, u64)) -> io::Result<PathBuf> {
+    let format = Format::from_path(archive).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "unknown archive format")
+    })?;
+    let parent = archive.parent().unwrap_or_else(|| Path::new("."));
+    let name = archive.file_name().map_or("archive".into(), |n| n.to_string_lossy());
+    let stem = stem(&name).unwrap_or(&name).to_owned();
+
+    // opened first, so nothing fails between creating `tmp` and cleaning it up
+    let file = File::open(archive)?;
+    let tmp = unique_path(parent, format!(".{}.part", stem));
+    fs::create_dir(&tmp)?;
+    let result = match format {
+        Format::Zip => read_zip(file, &tmp, &mut progress),
+        Format::TarGz => Counted::new(file, &mut progress)
+            .and_then(|input| read_tar(GzDecoder::new(input), &tmp)),
+        Format::TarZst => Counted::new(file, &mut progress)
+            .and_then(zstd::Decoder::new)
+            .and_then(|zst| read_tar(zst, &tmp)),
+    };
+    let result = result.and_then(|()| settle(&tmp, parent, &stem));
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&tmp);
+    }
+    result
+}
+
+/// moves what was extracted into `tmp` to its place in `parent`, see [`extract`]
+fn settle(tmp: &Path, parent: &Path, stem: &str) -> io::Result<PathBuf> {
+    let mut top = fs::read_dir(tmp)?.collect::<io::Result<Vec<_>>>()?;
+    if top.len() == 1 && top[0].file_type()?.is_dir() {
+        let inner = top.remove(0);
+        let dest = unique_path(parent, inner.file_name());
+        fs::rename(inner.path(), &dest)?;
+        fs::remove_dir(tmp)?;
+        Ok(dest)
+    } else {
+        let dest = unique_path(parent, stem);
+        fs::rename(tmp, &dest)?;
+        Ok(dest)
+    }
+}
+
+/// (path on disk, path inside the archive), parents before their children
+fn collect(sources: &[PathBuf]) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    fn walk(path: &Path, base: &Path, out: &mut Vec<(PathBuf, PathBuf)>) -> io::Result<()> {
+        let name = path.strip_prefix(base).unwrap_or(path).to_owned();
+        out.push((path.to_owned(), name));
+        // symlinked directories are stored as links, not followed
+        if fs::symlink_metadata(path)?.is_dir() {
+            let mut children = fs::read_dir(path)?
+                .map(|r| r.map(|de| de.path()))
+                .collect::<io::Result<Vec<_>>>()?;
+            children.sort();
+            for child in children {
+                walk(&child, base, out)?;
+            }
+        }
+        Ok(())
+    }
+    let mut out = vec![];
+    for source in sources {
+        let base = source.parent().unwrap_or_else(|| Path::new(""));
+        walk(source, base, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// returns how many symlinks were left out
+fn write_zip(
+    file: File,
+    entries: &[(PathBuf, PathBuf)],
+    mut progress: impl FnMut(u64),
+) -> io::Result<usize> {
+    let mut zip = ZipWriter::new(file);
+    let mut links = 0;
+    for (i, (path, name)) in entries.iter().enumerate() {
+        let meta = fs::symlink_metadata(path)?;
+        let options = FileOptions::default().unix_permissions(meta.permissions().mode());
+        let name = name.to_string_lossy();
+        if meta.file_type().is_symlink() {
+            links += 1;
+        } else if meta.is_dir() {
+            zip.add_directory(name, options)?;
+        } else {
+            zip.start_file(name, options)?;
+            io::copy(&mut File::open(path)?, &mut zip)?;
+        }
+        progress(i as u64 + 1);
+    }
+    zip.finish()?;
+    Ok(links)
+}
+
+fn write_tar<W: Write>(
+    out: W,
+    entries: &[(PathBuf, PathBuf)],
+    mut progress: impl FnMut(u64),
+) -> io::Result<W> {
+    let mut tar = tar::Builder::new(out);
+    tar.follow_symlinks(false);
+    for (i, (path, name)) in entries.iter().enumerate() {
+        tar.append_path_with_name(path, name)?;
+        progress(i as u64 + 1);
+    }
+    tar.into_inner()
+}
+
+fn read_zip(file: File, dest: &Path, mut progress: impl FnMut(u64, u64)) -> io::Result<()> {
+    let mut zip = ZipArchive::new(file)?;
+    let total = zip.len() as u64;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        // skip entries trying to escape `dest`
+        let out = match entry.enclosed_name() {
+            Some(name) => dest.join(name),
+            None => continue,
+        };
+        if entry.is_dir() {
+            fs::create_dir_all(&out)?;
+        } else {
+            if let Some(parent) = out.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut entry, &mut File::create(&out)?)?;
+            if let Some(mode) = entry.unix_mode() {
+                fs::set_permissions(&out, Permissions::from_mode(mode))?;
+            }
+        }
+        progress(i as u64 + 1, total);
+    }
+    Ok(())
+}
+
+fn read_tar(input: impl Read, dest: &Path) -> io::Result<()> {
+    let mut tar = tar::Archive::new(input);
+    for entry in tar.entries()? {
+        // `unpack_in` refuses paths escaping `dest`
+        entry?.unpack_in(dest)?;
+    }
+    Ok(())
+}
+
+/// reports how much of the underlying file has been read
+struct Counted<'a, R> {
+    inner: R,
+    read: u64,
+    total: u64,
+    progress: &'a mut dyn FnMut(u64, u64),
+}
+
+impl<'a> Counted<'a, File> {
+    fn new(file: File, progress: &'a mut dyn FnMut(u64, u64)) -> io::Result<Self> {
+        let total = file.metadata()?.len();
+        Ok(Self { inner: file, read: 0, total, progress })
+    }
+}
+
+impl<R: Read> Read for Counted<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        (self.progress)(self.read, self.total);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test_archive {
+    use super::*;
+    use crate::TestDir;
+
+    #[test]
+    fn test_stem() {
+        assert_eq!(stem("foo.tar.gz"), Some("foo"));
+        assert_eq!(stem("foo.bar.TGZ"), Some("foo.bar"));
+        assert_eq!(stem("foo.zip"), Some("foo"));
+        assert_eq!(stem(".zip"), None);
+        assert_eq!(stem("foo.txt"), None);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for format in &[Format::Zip, Format::TarGz, Format::TarZst] {
+            let dir = TestDir::new(format.extension());
+            fs::create_dir_all(dir.join("top/sub")).unwrap();
+            fs::write(dir.join("top/sub/a"), "a").unwrap();
+            fs::write(dir.join("b"), "b").unwrap();
+            std::os::unix::fs::symlink("sub", dir.join("top/dir_link")).unwrap();
+            std::os::unix::fs::symlink("missing", dir.join("top/dangling")).unwrap();
+
+            // single top-level directory is reused
+            let single = dir.join(format!("single.{}", format.extension()));
+            let links = compress(&[dir.join("top")], &single, |_, _| ()).unwrap();
+            fs::remove_dir_all(dir.join("top")).unwrap();
+            let out = extract(&single, |_, _| ()).unwrap();
+            assert_eq!(out, dir.join("top"));
+            assert_eq!(fs::read_to_string(out.join("sub/a")).unwrap(), "a");
+            // symlinks aren't followed, zip leaves them out
+            if *format == Format::Zip {
+                assert_eq!(links, 2);
+                assert!(fs::symlink_metadata(out.join("dir_link")).is_err());
+            } else {
+                assert_eq!(links, 0);
+                assert_eq!(fs::read_link(out.join("dir_link")).unwrap(), Path::new("sub"));
+                assert_eq!(fs::read_link(out.join("dangling")).unwrap(), Path::new("missing"));
+            }
+
+            // otherwise a directory is created after the archive
+            let multi = dir.join(format!("multi.{}", format.extension()));
+            compress(&[dir.join("top"), dir.join("b")], &multi, |_, _| ()).unwrap();
+            let out = extract(&multi, |_, _| ()).unwrap();
+            assert_eq!(out, dir.join("multi"));
+            assert_eq!(fs::read_to_string(out.join("b")).unwrap(), "b");
+            assert!(out.join("top/sub/a").is_file());
+
+            assert!(compress(&[dir.join("b")], &multi, |_, _| ()).is_err());
+
+            // nothing is left behind by a broken archive
+            let broken = dir.join(format!("broken.{}", format.extension()));
+            fs::write(&broken, "not an archive").unwrap();
+            assert!(extract(&broken, |_, _| ()).is_err());
+            assert!(!dir.join(".broken.part").exists());
+        }
+    }
+}
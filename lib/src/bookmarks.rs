@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::xdg;
+
+/// the location before the last jump, `''` jumps back to it
+pub const LAST: char = '\'';
+
+/// Single-key bookmarks, stored one `key:path` per line like ranger does.
+#[derive(Clone, Debug, Default)]
+pub struct Bookmarks {
+    marks: BTreeMap<char, PathBuf>,
+    file: Option<PathBuf>,
+}
+
+impl Bookmarks {
+    /// `$XDG_DATA_HOME/yukari/bookmarks`
+    pub fn default_file() -> Option<PathBuf> {
+        xdg::data_dir().map(|d| d.join("bookmarks"))
+    }
+
+    /// missing or unreadable files give empty bookmarks, malformed lines are skipped
+    pub fn load(file: impl Into<PathBuf>) -> Self {
+        let file = file.into();
+        let marks = fs::read_to_string(&file).map_or_else(|_| BTreeMap::new(), |s| Self::parse(&s));
+        Self { marks, file: Some(file) }
+    }
+
+    pub fn parse(s: &str) -> BTreeMap<char, PathBuf> {
+        s.lines()
+            .filter_map(|line| {
+                let mut chars = line.chars();
+                match (chars.next(), chars.next(), chars.as_str()) {
+                    (Some(key), Some(':'), path) if !path.is_empty() => Some((key, path.into())),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut s = String::new();
+        for (key, path) in &self.marks {
+            s.push_str(&format!("{}:{}\n", key, path.to_string_lossy()));
+        }
+        fs::write(file, s)
+    }
+
+    /// only printable, non-whitespace keys can be used
+    pub fn set(&mut self, key: char, path: PathBuf) -> bool {
+        if key.is_whitespace() || key.is_control() {
+            return false;
+        }
+        self.marks.insert(key, path);
+        true
+    }
+
+    pub fn get(&self, key: char) -> Option<&Path> {
+        self.marks.get(&key).map(PathBuf::as_path)
+    }
+
+    pub fn remove(&mut self, key: char) -> Option<PathBuf> {
+        self.marks.remove(&key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (char, &Path)> {
+        self.marks.iter().map(|(k, p)| (*k, p.as_path()))
+    }
+}
+
+#[cfg(test)]
+mod test_bookmarks {
+    use super::Bookmarks;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse() {
+        let marks = Bookmarks::parse("a:/tmp\n':/home/u:x\nbad\nc:\n\nd/nope\n");
+        assert_eq!(
+            marks.into_iter().collect::<Vec<_>>(),
+            vec![('\'', PathBuf::from("/home/u:x")), ('a', "/tmp".into())]
+        );
+    }
+}
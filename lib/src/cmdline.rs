@@ -0,0 +1,401 @@
+use druid::Data;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::xdg;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    UnterminatedQuote(char),
+    TrailingBackslash,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnterminatedQuote(q) => write!(f, "unterminated {} quote", q),
+            ParseError::TrailingBackslash => write!(f, "trailing backslash"),
+        }
+    }
+}
+
+/// (argument, byte offset where it starts)
+fn split(line: &str) -> Result<Vec<(String, usize)>, ParseError> {
+    let mut args = vec![];
+    let mut current: Option<(String, usize)> = None;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c.is_whitespace() {
+            args.extend(current.take());
+            continue;
+        }
+        let arg = &mut current.get_or_insert_with(|| (String::new(), i)).0;
+        match c {
+            '\\' => arg.push(chars.next().ok_or(ParseError::TrailingBackslash)?.1),
+            '\'' => loop {
+                match chars.next() {
+                    Some((_, '\'')) => break,
+                    Some((_, c)) => arg.push(c),
+                    None => return Err(ParseError::UnterminatedQuote('\'')),
+                }
+            },
+            '"' => loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    // only these are escaped between double quotes, like sh
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) if "\"\\$`".contains(c) => arg.push(c),
+                        Some((_, c)) => {
+                            arg.push('\\');
+                            arg.push(c);
+                        }
+                        None => return Err(ParseError::UnterminatedQuote('"')),
+                    },
+                    Some((_, c)) => arg.push(c),
+                    None => return Err(ParseError::UnterminatedQuote('"')),
+                }
+            },
+            c => arg.push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Splits a command line into arguments with sh-like quoting:
+/// `'...'` is literal, `"..."` and bare words honour backslash escapes.
+pub fn parse(line: &str) -> Result<Vec<String>, ParseError> {
+    split(line).map(|args| args.into_iter().map(|(arg, _)| arg).collect())
+}
+
+/// `arg` quoted so that [`parse`] gives it back as a single argument
+pub fn quote(arg: &str) -> String {
+    let plain = |c: char| c.is_alphanumeric() || "-_./~+=,:@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Completions of the last word of `line`, as whole new lines.
+/// The first word completes to one of `commands`, the others to paths relative to `cwd`.
+pub fn complete(line: &str, commands: &[&str], cwd: &Path) -> Vec<String> {
+    // an unterminated quote is closed for the purpose of completing
+    let args = split(line)
+        .or_else(|_| split(&format!("{}'", line)))
+        .or_else(|_| split(&format!("{}\"", line)))
+        .unwrap_or_default();
+    let (word, start, index) = match args.last() {
+        Some((arg, start)) if !line.ends_with(char::is_whitespace) => {
+            (arg.as_str(), *start, args.len() - 1)
+        }
+        _ => ("", line.len(), args.len()),
+    };
+    let candidates = if index == 0 {
+        let mut names: Vec<String> =
+            commands.iter().filter(|c| c.starts_with(word)).map(|&c| c.to_owned()).collect();
+        names.sort();
+        names.dedup();
+        names
+    } else {
+        complete_path(word, cwd)
+    };
+    candidates
+        .into_iter()
+        .map(|c| format!("{}{}", &line[..start], quote(&c)))
+        .collect()
+}
+
+/// entries starting with the last component of `word`, directories end with `/`
+fn complete_path(word: &str, cwd: &Path) -> Vec<String> {
+    let (dir_part, prefix) = match word.rfind('/') {
+        Some(i) => word.split_at(i + 1),
+        None => ("", word),
+    };
+    let dir = match dir_part.strip_prefix("~/") {
+        Some(rest) => env::var_os("HOME").map_or_else(|| "/".into(), PathBuf::from).join(rest),
+        None => cwd.join(dir_part),
+    };
+    let mut out: Vec<String> = fs::read_dir(&dir)
+        .map(|it| {
+            it.filter_map(Result::ok)
+                .filter_map(|de| {
+                    let name = de.file_name().to_string_lossy().into_owned();
+                    // hidden files only when asked for
+                    let hidden = name.starts_with('.') && !prefix.starts_with('.');
+                    if hidden || !name.starts_with(prefix) {
+                        return None;
+                    }
+                    let slash = if de.path().is_dir() { "/" } else { "" };
+                    Some(format!("{}{}{}", dir_part, name, slash))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    out.sort();
+    out
+}
+
+/// the line being edited, shown in place of the footer
+#[derive(Clone, Data, Default)]
+pub struct CmdLine {
+    pub text: String,
+    /// byte offset in `text`
+    pub cursor: usize,
+    /// for `/` lines, where the cursor was when it was opened, it goes back there if cancelled
+    pub search: Option<usize>,
+    /// whether the finder ranks the paths by what's typed, see [`crate::fuzzy::Finder`]
+    pub finder: bool,
+    /// index in the history while browsing it
+    #[data(ignore)]
+    history_pos: Option<usize>,
+    /// candidates and the one shown while cycling through completions
+    #[data(ignore)]
+    completion: Option<(Vec<String>, usize)>,
+}
+
+impl CmdLine {
+    pub fn new(text: impl Into<String>) -> Self {
+        let text = text.into();
+        Self { cursor: text.len(), text, ..Self::default() }
+    }
+
+    /// a `/` line, the cursor of the listing at `from`
+    pub fn search(from: usize) -> Self {
+        Self { search: Some(from), ..Self::default() }
+    }
+
+    /// the finder's line
+    pub fn finder() -> Self {
+        Self { finder: true, ..Self::default() }
+    }
+
+    fn set(&mut self, text: String) {
+        self.cursor = text.len();
+        self.text = text;
+    }
+
+    fn edited(&mut self) {
+        self.history_pos = None;
+        self.completion = None;
+    }
+
+    pub fn insert(&mut self, s: &str) {
+        self.text.insert_str(self.cursor, s);
+        self.cursor += s.len();
+        self.edited();
+    }
+
+    fn prev_boundary(&self, from: usize) -> usize {
+        self.text[..from].char_indices().next_back().map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self, from: usize) -> usize {
+        self.text[from..].chars().next().map_or(from, |c| from + c.len_utf8())
+    }
+
+    pub fn backspace(&mut self) {
+        let start = self.prev_boundary(self.cursor);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+        self.edited();
+    }
+
+    pub fn delete(&mut self) {
+        let end = self.next_boundary(self.cursor);
+        self.text.replace_range(self.cursor..end, "");
+        self.edited();
+    }
+
+    /// deletes the word before the cursor, like `^W`
+    pub fn delete_word(&mut self) {
+        let before = self.text[..self.cursor].trim_end();
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+        self.edited();
+    }
+
+    /// deletes everything before the cursor, like `^U`
+    pub fn delete_to_start(&mut self) {
+        self.text.replace_range(..self.cursor, "");
+        self.cursor = 0;
+        self.edited();
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.prev_boundary(self.cursor);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = self.next_boundary(self.cursor);
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// cycles through the completions of the line, backwards if `back`
+    pub fn complete(&mut self, back: bool, commands: &[&str], cwd: &Path) {
+        if let Some((candidates, i)) = &mut self.completion {
+            let n = candidates.len();
+            *i = if back { (*i + n - 1) % n } else { (*i + 1) % n };
+            let text = candidates[*i].clone();
+            self.set(text);
+            return;
+        }
+        let candidates = complete(&self.text, commands, cwd);
+        if let Some(first) = candidates.first().cloned() {
+            self.set(first);
+            // a single candidate is final
+            if candidates.len() > 1 {
+                self.completion = Some((candidates, 0));
+            }
+        }
+    }
+
+    /// shows an older (`back`) or newer entry of `history`
+    pub fn browse(&mut self, back: bool, history: &CmdHistory) {
+        let len = history.entries.len();
+        let pos = match (self.history_pos, back) {
+            (None, true) if len > 0 => len - 1,
+            (None, _) => return,
+            (Some(p), true) => p.saturating_sub(1),
+            (Some(p), false) if p + 1 < len => p + 1,
+            (Some(_), false) => {
+                self.history_pos = None;
+                self.set(String::new());
+                return;
+            }
+        };
+        self.set(history.entries[pos].clone());
+        self.history_pos = Some(pos);
+        self.completion = None;
+    }
+}
+
+/// older entries are forgotten past this
+const HISTORY_LEN: usize = 500;
+
+/// previously run command lines, oldest first, one per line in the file
+#[derive(Clone, Debug, Default)]
+pub struct CmdHistory {
+    entries: Vec<String>,
+    file: Option<PathBuf>,
+}
+
+impl CmdHistory {
+    /// `$XDG_DATA_HOME/yukari/history`
+    pub fn default_file() -> Option<PathBuf> {
+        xdg::data_dir().map(|d| d.join("history"))
+    }
+
+    pub fn load(file: impl Into<PathBuf>) -> Self {
+        let file = file.into();
+        let entries = fs::read_to_string(&file)
+            .map(|s| s.lines().filter(|l| !l.is_empty()).map(str::to_owned).collect())
+            .unwrap_or_default();
+        Self { entries, file: Some(file) }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut s = self.entries.join("\n");
+        s.push('\n');
+        fs::write(file, s)
+    }
+
+    /// moves `line` to the end if it was already there
+    pub fn push(&mut self, line: &str) {
+        if line.trim().is_empty() {
+            return;
+        }
+        self.entries.retain(|l| l != line);
+        self.entries.push(line.to_owned());
+        if self.entries.len() > HISTORY_LEN {
+            self.entries.remove(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_cmdline {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let p = |s: &str| parse(s).unwrap();
+        assert_eq!(p(""), Vec::<String>::new());
+        assert_eq!(p("  a  b "), vec!["a", "b"]);
+        assert_eq!(p(r"a\ b c"), vec!["a b", "c"]);
+        assert_eq!(p(r#"'a \b' "c \"d\" \e""#), vec![r"a \b", r#"c "d" \e"#]);
+        assert_eq!(p(r#"a'b'"c""#), vec!["abc"]);
+        assert_eq!(p("''"), vec![""]);
+        assert_eq!(parse("'a"), Err(ParseError::UnterminatedQuote('\'')));
+        assert_eq!(parse("\"a"), Err(ParseError::UnterminatedQuote('"')));
+        assert_eq!(parse("a\\"), Err(ParseError::TrailingBackslash));
+    }
+
+    #[test]
+    fn test_quote() {
+        for s in &["a", "a b", "it's", "", "$HOME", "a\\b", "ü"] {
+            assert_eq!(parse(&quote(s)).unwrap(), vec![s.to_string()]);
+        }
+        assert_eq!(quote("a/b.txt"), "a/b.txt");
+    }
+
+    #[test]
+    fn test_complete_commands() {
+        let cmds = ["tabnew", "tabclose", "back"];
+        let cwd = Path::new("/");
+        assert_eq!(complete("ta", &cmds, cwd), vec!["tabclose", "tabnew"]);
+        assert_eq!(complete("", &cmds, cwd).len(), 3);
+        assert_eq!(complete("x", &cmds, cwd), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_editing() {
+        let mut line = CmdLine::new("ab cd");
+        line.delete_word();
+        assert_eq!((line.text.as_str(), line.cursor), ("ab ", 3));
+        line.left();
+        line.insert("é");
+        assert_eq!(line.text, "abé ");
+        line.backspace();
+        line.backspace();
+        assert_eq!((line.text.as_str(), line.cursor), ("a ", 1));
+        line.delete_to_start();
+        assert_eq!((line.text.as_str(), line.cursor), (" ", 0));
+    }
+
+    #[test]
+    fn test_browse() {
+        let mut history = CmdHistory::default();
+        history.push("a");
+        history.push("b");
+        history.push("a");
+        let mut line = CmdLine::default();
+        line.browse(true, &history);
+        assert_eq!(line.text, "a");
+        line.browse(true, &history);
+        assert_eq!(line.text, "b");
+        line.browse(false, &history);
+        line.browse(false, &history);
+        assert_eq!(line.text, "");
+    }
+}
@@ -0,0 +1,340 @@
+//! `:compare`: what's only in one of two directories, or in both but different, and copying
+//! what's missing in one from the other.
+
+use std::collections::BTreeSet;
+use std::ffi::CString;
+use std::fs::{self, Metadata};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::dupes;
+use crate::fileops;
+
+/// how an entry of the two directories compares
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    OnlyLeft,
+    OnlyRight,
+    /// in both, but not the same size or modification time, or contents
+    Differ,
+    Same,
+    /// in both, but either side couldn't be read to compare them
+    Unreadable,
+}
+
+impl Status {
+    pub fn mark(self) -> char {
+        match self {
+            Status::OnlyLeft => '<',
+            Status::OnlyRight => '>',
+            Status::Differ => '\u{2260}',
+            Status::Same => '=',
+            Status::Unreadable => '?',
+        }
+    }
+}
+
+/// an entry of either directory, those in both are only compared if they're files or links
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diff {
+    /// from the directories compared
+    pub path: PathBuf,
+    /// whether it's a directory, on the side it's on if only on one
+    pub dir: bool,
+    pub status: Status,
+}
+
+/// the differences between two directories
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    pub left: PathBuf,
+    pub right: PathBuf,
+    /// whether files were compared by contents rather than by size and modification time
+    pub content: bool,
+    /// sorted by path, the entries of directories only on one side are left out
+    pub diffs: Vec<Diff>,
+}
+
+/// the names of the entries of `dir`, without the hidden ones unless `hidden`, an error if
+/// it can't be read
+fn names(dir: &Path, hidden: bool) -> io::Result<BTreeSet<PathBuf>> {
+    let mut names = BTreeSet::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        if hidden || !name.as_bytes().starts_with(b".") {
+            names.insert(PathBuf::from(name));
+        }
+    }
+    Ok(names)
+}
+
+/// whether the entries at `left` and `right`, neither a directory, are the same
+fn same(left: &Path, l: &Metadata, right: &Path, r: &Metadata, content: bool) -> io::Result<bool> {
+    let (l_link, r_link) = (l.file_type().is_symlink(), r.file_type().is_symlink());
+    if l_link || r_link {
+        return Ok(l_link && r_link && fs::read_link(left)? == fs::read_link(right)?);
+    }
+    if l.len() != r.len() {
+        return Ok(false);
+    }
+    if content {
+        return dupes::same_contents(left, right);
+    }
+    Ok((l.mtime(), l.mtime_nsec()) == (r.mtime(), r.mtime_nsec()))
+}
+
+impl Comparison {
+    /// Compares the trees under `left` and `right`, with the hidden entries if `hidden`,
+    /// files by contents if `content`. What can't be read under them is listed as
+    /// [`Status::Unreadable`], only `left` or `right` themselves failing is an error. `None`
+    /// if cancelled.
+    pub fn new(
+        left: &Path,
+        right: &Path,
+        content: bool,
+        hidden: bool,
+        cancel: &AtomicBool,
+    ) -> io::Result<Option<Self>> {
+        let mut comparison =
+            Self { left: left.to_owned(), right: right.to_owned(), content, diffs: vec![] };
+        let mut pending = vec![PathBuf::new()];
+        while let Some(dir) = pending.pop() {
+            if cancel.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+            let (left, right) = (left.join(&dir), right.join(&dir));
+            let all = match (names(&left, hidden), names(&right, hidden)) {
+                (Ok(mut all), Ok(right)) => {
+                    all.extend(right);
+                    all
+                }
+                (Err(e), _) | (_, Err(e)) if dir.as_os_str().is_empty() => return Err(e),
+                _ => {
+                    let status = Status::Unreadable;
+                    comparison.diffs.push(Diff { path: dir, dir: true, status });
+                    continue;
+                }
+            };
+            for name in all {
+                let (l, r) = (left.join(&name), right.join(&name));
+                let (path, l_meta, r_meta) =
+                    (dir.join(&name), l.symlink_metadata(), r.symlink_metadata());
+                let (status, is_dir) = match (l_meta.ok(), r_meta.ok()) {
+                    (Some(l), None) => (Status::OnlyLeft, l.is_dir()),
+                    (None, Some(r)) => (Status::OnlyRight, r.is_dir()),
+                    (Some(lm), Some(rm)) if lm.is_dir() && rm.is_dir() => {
+                        pending.push(path);
+                        continue;
+                    }
+                    (Some(lm), Some(rm)) if lm.is_dir() || rm.is_dir() => (Status::Differ, false),
+                    (Some(lm), Some(rm)) => match same(&l, &lm, &r, &rm, content) {
+                        Ok(true) => (Status::Same, false),
+                        Ok(false) => (Status::Differ, false),
+                        Err(_) => (Status::Unreadable, false),
+                    },
+                    (None, None) => continue,
+                };
+                comparison.diffs.push(Diff { path, dir: is_dir, status });
+            }
+        }
+        comparison.diffs.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Some(comparison))
+    }
+
+    /// `< only/left`, `> only/right/`, `≠ differs`, `= same`, `? unreadable`
+    pub fn lines(&self) -> Vec<String> {
+        let line = |diff: &Diff| {
+            let slash = if diff.dir { "/" } else { "" };
+            format!("{} {}{}", diff.status.mark(), diff.path.display(), slash)
+        };
+        self.diffs.iter().map(line).collect()
+    }
+
+    /// `2 only left, 1 only right, 3 differ, 10 same`, and `, 1 unreadable` if any are
+    pub fn summary(&self) -> String {
+        let count = |status| self.diffs.iter().filter(|diff| diff.status == status).count();
+        let summary = format!(
+            "{} only left, {} only right, {} differ, {} same",
+            count(Status::OnlyLeft),
+            count(Status::OnlyRight),
+            count(Status::Differ),
+            count(Status::Same)
+        );
+        match count(Status::Unreadable) {
+            0 => summary,
+            unreadable => format!("{}, {} unreadable", summary, unreadable),
+        }
+    }
+
+    /// the entries missing on the right if `to_right`, else on the left, and the differing
+    /// ones if `replace`
+    pub fn to_copy(&self, to_right: bool, replace: bool) -> Vec<PathBuf> {
+        let missing = if to_right { Status::OnlyLeft } else { Status::OnlyRight };
+        let copied =
+            |diff: &&Diff| diff.status == missing || replace && diff.status == Status::Differ;
+        self.diffs.iter().filter(copied).map(|diff| diff.path.clone()).collect()
+    }
+
+    /// Copies `paths`, from the directories compared, from the left to the right if
+    /// `to_right`, else the other way, swapping in for the files there unless they're newer.
+    /// Directories are never replaced, nor replace a file. Their modification times are kept
+    /// so they compare the same, with their extended attributes if `xattrs`. `progress` is
+    /// called with (entries done, total entries). Returns how many were copied and an error
+    /// for each that wasn't.
+    pub fn copy(
+        &self,
+        paths: &[PathBuf],
+        to_right: bool,
+        xattrs: bool,
+        mut progress: impl FnMut(u64, u64),
+    ) -> (usize, Vec<String>) {
+        let (from, to) =
+            if to_right { (&self.left, &self.right) } else { (&self.right, &self.left) };
+        let (mut copied, mut errors) = (0, vec![]);
+        for (i, path) in paths.iter().enumerate() {
+            let (src, dest) = (from.join(path), to.join(path));
+            let result = (|| {
+                if let Ok(dest_meta) = fs::symlink_metadata(&dest) {
+                    let src_meta = fs::symlink_metadata(&src)?;
+                    let refused = if dest_meta.is_dir() {
+                        Some("a directory, not replaced")
+                    } else if src_meta.is_dir() {
+                        Some("not a directory, not replaced by one")
+                    } else if (dest_meta.mtime(), dest_meta.mtime_nsec())
+                        > (src_meta.mtime(), src_meta.mtime_nsec())
+                    {
+                        Some("newer, not replaced")
+                    } else {
+                        None
+                    };
+                    if let Some(refused) = refused {
+                        return Err(io::Error::new(io::ErrorKind::AlreadyExists, refused));
+                    }
+                }
+                let dir = dest.parent().unwrap_or_else(|| Path::new("/"));
+                fs::create_dir_all(dir)?;
+                // copied beside it then swapped in, so `dest` isn't lost if copying fails
+                let name = dest.file_name().unwrap_or_default().to_string_lossy();
+                let temp = fileops::unique_path(dir, format!(".{}.yukari-sync", name));
+                let swapped = fileops::copy_all(&src, &temp, xattrs)
+                    .and_then(|()| copy_times(&src, &temp))
+                    .and_then(|()| fs::rename(&temp, &dest));
+                if swapped.is_err() {
+                    let _ = fileops::remove_all(&temp);
+                }
+                swapped
+            })();
+            match result {
+                Ok(()) => copied += 1,
+                Err(e) => errors.push(format!("{}: {}", dest.display(), e)),
+            }
+            progress(i as u64 + 1, paths.len() as u64);
+        }
+        (copied, errors)
+    }
+}
+
+/// gives `dest` and what's in it the modification times of `src` and what's in it, not
+/// following symlinks
+fn copy_times(src: &Path, dest: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(src)?;
+    if meta.is_dir() {
+        for entry in fs::read_dir(src)? {
+            let name = entry?.file_name();
+            copy_times(&src.join(&name), &dest.join(&name))?;
+        }
+    }
+    set_mtime(dest, meta.mtime(), meta.mtime_nsec())
+}
+
+/// sets the modification time of `path`, not following symlinks
+fn set_mtime(path: &Path, secs: i64, nsecs: i64) -> io::Result<()> {
+    let omit = libc::timespec { tv_sec: 0, tv_nsec: libc::UTIME_OMIT };
+    let mtime = libc::timespec { tv_sec: secs, tv_nsec: nsecs };
+    let dest = CString::new(path.as_os_str().as_bytes())?;
+    let times = [omit, mtime];
+    let flags = libc::AT_SYMLINK_NOFOLLOW;
+    match unsafe { libc::utimensat(libc::AT_FDCWD, dest.as_ptr(), times.as_ptr(), flags) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(test)]
+mod test_compare {
+    use super::*;
+    use crate::TestDir;
+
+    #[test]
+    fn test_compare() {
+        let dir = TestDir::new("compare");
+        let (left, right) = (dir.join("left"), dir.join("right"));
+        for sub in &["left/both", "left/only_dir/sub", "right/both", "right/.hidden"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        for (path, contents) in &[
+            ("left/both/same", "same"),
+            ("left/both/size", "big"),
+            ("left/only_dir/sub/f", "f"),
+            ("left/kind", "file"),
+            ("right/both/size", "bigger"),
+            ("right/only_file", "only"),
+        ] {
+            fs::write(dir.join(path), contents).unwrap();
+        }
+        fs::create_dir_all(right.join("kind")).unwrap();
+        set_mtime(&right.join("both/size"), 1, 0).unwrap();
+        fs::copy(left.join("both/same"), right.join("both/same")).unwrap();
+        copy_times(&left.join("both/same"), &right.join("both/same")).unwrap();
+        let cancel = AtomicBool::new(false);
+        let comparison = Comparison::new(&left, &right, false, false, &cancel).unwrap().unwrap();
+        assert_eq!(
+            comparison.lines(),
+            ["= both/same", "\u{2260} both/size", "\u{2260} kind", "< only_dir/", "> only_file"]
+        );
+        assert_eq!(comparison.summary(), "1 only left, 1 only right, 2 differ, 1 same");
+        let hidden = Comparison::new(&left, &right, true, true, &cancel).unwrap().unwrap();
+        assert_eq!(hidden.diffs[0].path, Path::new(".hidden"));
+        assert_eq!(hidden.diffs[0].status, Status::OnlyRight);
+        assert!(Comparison::new(&left, &right, false, false, &AtomicBool::new(true))
+            .unwrap()
+            .is_none());
+        assert!(Comparison::new(&dir.join("missing"), &right, false, false, &cancel).is_err());
+
+        assert_eq!(comparison.to_copy(false, false), [Path::new("only_file")]);
+        let to_right = comparison.to_copy(true, true);
+        assert_eq!(to_right, [Path::new("both/size"), Path::new("kind"), Path::new("only_dir")]);
+        let (copied, errors) = comparison.copy(&to_right, true, false, |_, _| ());
+        assert_eq!(copied, 2);
+        assert_eq!(
+            errors,
+            [format!("{}: a directory, not replaced", right.join("kind").display())]
+        );
+        let mut synced = Comparison::new(&left, &right, false, false, &cancel).unwrap().unwrap();
+        assert_eq!(synced.summary(), "0 only left, 1 only right, 1 differ, 3 same");
+        assert_eq!(fs::read(right.join("only_dir/sub/f")).unwrap(), b"f");
+        assert!(right.join("kind").is_dir());
+        // what's newer isn't replaced by what's older
+        set_mtime(&left.join("both/size"), 1, 0).unwrap();
+        let (copied, errors) = synced.copy(&[PathBuf::from("both/size")], true, false, |_, _| ());
+        assert_eq!((copied, errors.len()), (0, 1));
+        assert_eq!(fs::read(right.join("both/size")).unwrap(), b"big");
+        // nor lost if what replaces it can't be copied
+        fs::remove_file(left.join("both/size")).unwrap();
+        let _socket = std::os::unix::net::UnixListener::bind(left.join("both/size")).unwrap();
+        set_mtime(&right.join("both/size"), 1, 0).unwrap();
+        let (copied, errors) = synced.copy(&[PathBuf::from("both/size")], true, false, |_, _| ());
+        assert_eq!((copied, errors.len()), (0, 1));
+        assert_eq!(fs::read(right.join("both/size")).unwrap(), b"big");
+        assert_eq!(fs::read_dir(right.join("both")).unwrap().count(), 2);
+
+        let path = PathBuf::from("locked");
+        synced.diffs.push(Diff { path, dir: true, status: Status::Unreadable });
+        assert_eq!(synced.lines().last().unwrap(), "? locked/");
+        assert!(synced.summary().ends_with(", 3 same, 1 unreadable"));
+        assert!(!synced.to_copy(true, true).contains(&PathBuf::from("locked")));
+    }
+}
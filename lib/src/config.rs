@@ -0,0 +1,290 @@
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::details::DetailOptions;
+use crate::entry::ListOptions;
+use crate::grep::GrepOptions;
+use crate::keymap::{Keymap, Mode};
+use crate::opener::Opener;
+use crate::preview::PreviewOptions;
+use crate::theme::{self, parse_color, Rgba, COLOR_NAMES};
+use crate::xdg;
+
+/// What can be set in `config.toml`, every field is optional.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listing: ListOptions,
+    /// relative widths of the parent, current and preview columns
+    pub column_ratios: Vec<f64>,
+    pub preview: PreviewOptions,
+    pub details: DetailOptions,
+    pub grep: GrepOptions,
+    /// `/` takes regexes rather than substrings
+    pub search_regex: bool,
+    /// copies and moves across filesystems keep the extended attributes
+    pub preserve_xattrs: bool,
+    /// bundled or in [`theme::Theme::user_dir`]
+    pub theme: String,
+    /// `name = "#rrggbb"` over the theme's, see [`COLOR_NAMES`]
+    pub colors: BTreeMap<String, String>,
+    /// mode, then keys to command line, on top of the other bindings
+    pub keymap: BTreeMap<String, BTreeMap<String, String>>,
+    /// the first matching one is used, see [`crate::opener`]
+    pub opener: Vec<Opener>,
+    /// ranger's `rc.conf` to import, its options win over the ones set here,
+    /// see [`crate::ranger`]
+    pub ranger_rc: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listing: ListOptions::default(),
+            column_ratios: vec![1., 3., 4.],
+            preview: PreviewOptions::default(),
+            details: DetailOptions::default(),
+            grep: GrepOptions::default(),
+            search_regex: false,
+            preserve_xattrs: true,
+            theme: theme::DEFAULT.into(),
+            colors: BTreeMap::new(),
+            keymap: BTreeMap::new(),
+            opener: vec![],
+            ranger_rc: None,
+        }
+    }
+}
+
+/// 1-based number of the first line containing one of `needles`, in order of preference
+fn line_of(text: &str, needles: &[&str]) -> usize {
+    needles
+        .iter()
+        .find_map(|needle| text.lines().position(|l| l.contains(needle)))
+        .map_or(1, |i| i + 1)
+}
+
+/// 2 or 3 non-negative numbers, not all zero
+fn valid_ratios(ratios: &[f64]) -> bool {
+    let valid = |r: &f64| r.is_finite() && *r >= 0.;
+    (2..=3).contains(&ratios.len()) && ratios.iter().all(valid) && ratios.iter().any(|r| *r > 0.)
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/yukari/config.toml`
+    pub fn default_file() -> Option<PathBuf> {
+        xdg::config_dir().map(|d| d.join("config.toml"))
+    }
+
+    /// The defaults if `file` doesn't exist. Errors are prefixed with the file name and line.
+    pub fn load(file: &Path) -> Result<Self, Vec<String>> {
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(vec![format!("{}: {}", file.display(), e)]),
+        };
+        Self::parse(&text).map_err(|errors| {
+            errors.into_iter().map(|e| format!("{}:{}", file.display(), e)).collect()
+        })
+    }
+
+    /// Errors are `LINE: message`.
+    pub fn parse(text: &str) -> Result<Self, Vec<String>> {
+        let config: Self = toml::from_str(text).map_err(|e| {
+            let line = e.line_col().map_or(1, |(line, _)| line + 1);
+            let msg = e.to_string();
+            // the position is already given by the prefix
+            let msg = msg.split(" at line ").next().unwrap_or_default();
+            vec![format!("{}: {}", line, msg)]
+        })?;
+        let errors = config.validate(text);
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// what the types don't say
+    fn validate(&self, text: &str) -> Vec<String> {
+        let mut errors = vec![];
+        if !valid_ratios(&self.column_ratios) {
+            let line = line_of(text, &["column_ratios"]);
+            errors.push(format!("{}: column_ratios: expected 2 or 3 non-negative numbers", line));
+        }
+        if self.details.columns.is_empty() {
+            let line = line_of(text, &["columns", "[details]"]);
+            errors.push(format!("{}: details: columns can't be empty", line));
+        }
+        for (name, value) in &self.colors {
+            let line = line_of(text, &[name, "[colors]"]);
+            if !COLOR_NAMES.contains(&name.as_str()) {
+                let expected = COLOR_NAMES.join(", ");
+                let msg = format!("unknown color {}, expected one of {}", name, expected);
+                errors.push(format!("{}: {}", line, msg));
+            } else if parse_color(value).is_none() {
+                errors.push(format!("{}: {}: expected #rrggbb, got {:?}", line, name, value));
+            }
+        }
+        let mut keymap = Keymap::empty();
+        for (mode, bindings) in &self.keymap {
+            let section = format!("[keymap.{}]", mode);
+            let m = match Mode::from_name(mode) {
+                Some(m) => m,
+                None => {
+                    errors.push(format!("{}: unknown mode {}", line_of(text, &[&section]), mode));
+                    continue;
+                }
+            };
+            for (keys, command) in bindings {
+                if let Err(e) = keymap.bind(m, keys, command) {
+                    errors.push(format!("{}: {}", line_of(text, &[keys, &section]), e));
+                }
+            }
+        }
+        for opener in &self.opener {
+            if opener.pattern.is_empty() || opener.command.trim().is_empty() {
+                let line = line_of(text, &[&opener.pattern, "[[opener]]"]);
+                errors.push(format!("{}: opener: pattern and command can't be empty", line));
+            }
+        }
+        errors
+    }
+
+    /// `set NAME VALUE`: a listing option, `preview`, `details`, `search_regex`,
+    /// `preserve_xattrs`, `column_ratios` like `1,3,4` or `theme`
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let switch = |enabled: bool| match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            "toggle" => Ok(!enabled),
+            _ => Err(format!("{}: expected true, false or toggle", name)),
+        };
+        match name {
+            "preview" => self.preview.enabled = switch(self.preview.enabled)?,
+            "details" => self.details.enabled = switch(self.details.enabled)?,
+            "search_regex" => self.search_regex = switch(self.search_regex)?,
+            "preserve_xattrs" => self.preserve_xattrs = switch(self.preserve_xattrs)?,
+            "column_ratios" => {
+                let ratios: Result<Vec<f64>, _> =
+                    value.split(',').map(|r| r.trim().parse()).collect();
+                let ratios = ratios.map_err(|_| format!("column_ratios: not numbers: {}", value))?;
+                if !valid_ratios(&ratios) {
+                    return Err("column_ratios: expected 2 or 3 non-negative numbers".into());
+                }
+                self.column_ratios = ratios;
+            }
+            "theme" => self.theme = value.to_owned(),
+            _ => self.listing.set(name, value)?,
+        }
+        Ok(())
+    }
+
+    /// adds the `[keymap]` bindings to `keymap`
+    pub fn apply_keymap(&self, keymap: &mut Keymap) {
+        for (mode, bindings) in &self.keymap {
+            if let Some(mode) = Mode::from_name(mode) {
+                for (keys, command) in bindings {
+                    // validated when loading
+                    let _ = keymap.bind(mode, keys, command);
+                }
+            }
+        }
+    }
+
+    /// one of [`COLOR_NAMES`] as rgba, if set
+    pub fn color(&self, name: &str) -> Option<Rgba> {
+        self.colors.get(name).and_then(|c| parse_color(c))
+    }
+
+    /// the `[colors]` that are set, to put over the theme
+    pub fn colors(&self) -> impl Iterator<Item = (String, Rgba)> + '_ {
+        self.colors.iter().filter_map(|(name, c)| Some((name.clone(), parse_color(c)?)))
+    }
+}
+
+#[cfg(test)]
+mod test_config {
+    use super::*;
+    use crate::details::Column;
+    use crate::entry::SortKey;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            r##"
+column_ratios = [1, 2]
+
+[listing]
+show_hidden = true
+sort = "mtime"
+
+[details]
+enabled = true
+columns = ["name", "size", "target"]
+
+[grep]
+context = 5
+
+[colors]
+selection = "#336"
+
+[keymap.normal]
+"<C-d>" = "down 10"
+
+[[opener]]
+pattern = "*.pdf"
+command = "zathura"
+"##,
+        )
+        .unwrap();
+        assert!(config.listing.show_hidden);
+        assert_eq!(config.listing.sort, SortKey::Mtime);
+        assert_eq!(config.column_ratios, vec![1., 2.]);
+        assert_eq!(config.color("selection"), Some((0x33, 0x33, 0x66, 255)));
+        assert_eq!(config.opener[0].command, "zathura");
+        assert_eq!(config.preview, PreviewOptions::default());
+        assert_eq!(config.grep, GrepOptions { context: 5, ..GrepOptions::default() });
+        assert_eq!(config.details.columns, [Column::Name, Column::Size, Column::Target]);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_errors() {
+        let errors = |text| Config::parse(text).unwrap_err();
+        assert_eq!(
+            errors("\n[listing]\nshow_hidden = 3\n"),
+            vec!["3: invalid type: integer `3`, expected a boolean for key `listing.show_hidden`"]
+        );
+        assert_eq!(
+            errors("column_ratios = [1]\n\n[colors]\nfg = \"#fff\"\nborder = \"red\"\n"),
+            vec![
+                "1: column_ratios: expected 2 or 3 non-negative numbers",
+                "5: border: expected #rrggbb, got \"red\"",
+                "4: unknown color fg, expected one of background, foreground, selection, border, \
+                 footer",
+            ]
+        );
+        assert_eq!(
+            errors("[keymap.insert]\nx = \"y\"\n[keymap.normal]\n\"<Bad>\" = \"up\"\n"),
+            vec!["1: unknown mode insert", "4: unknown key <Bad>"]
+        );
+        assert_eq!(
+            errors("[details]\ncolumns = []\n"),
+            vec!["2: details: columns can't be empty"]
+        );
+        let mut config = Config::default();
+        config.set("details", "toggle").unwrap();
+        assert!(config.details.enabled);
+        config.set("search_regex", "true").unwrap();
+        assert!(config.search_regex);
+        config.set("preserve_xattrs", "toggle").unwrap();
+        assert!(!config.preserve_xattrs);
+        let error = config.set("details", "yes").unwrap_err();
+        assert_eq!(error, "details: expected true, false or toggle");
+    }
+}
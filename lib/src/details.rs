@@ -0,0 +1,281 @@
+//! Metadata columns of the detail view.
+
+use serde::Deserialize;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::unix::fs::MetadataExt;
+
+use crate::dirinfo::DirInfo;
+use crate::entry::{Entry, Link, SortKey};
+use crate::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    Name,
+    /// in bytes
+    Size,
+    HumanSize,
+    Mtime,
+    Permissions,
+    Owner,
+    Group,
+    Links,
+    /// of symlinks
+    Target,
+    /// the entry's status in the repository, see [`crate::git::Status::mark`]
+    Git,
+}
+
+impl Column {
+    pub const ALL: &'static [Column] = &[
+        Column::Name,
+        Column::Size,
+        Column::HumanSize,
+        Column::Mtime,
+        Column::Permissions,
+        Column::Owner,
+        Column::Group,
+        Column::Links,
+        Column::Target,
+        Column::Git,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Column::Name => "Name",
+            Column::Size => "Bytes",
+            Column::HumanSize => "Size",
+            Column::Mtime => "Modified",
+            Column::Permissions => "Permissions",
+            Column::Owner => "Owner",
+            Column::Group => "Group",
+            Column::Links => "Links",
+            Column::Target => "Target",
+            Column::Git => "Git",
+        }
+    }
+
+    /// in pixels, until resized
+    pub fn default_width(self) -> f64 {
+        match self {
+            Column::Name | Column::Target => 250.,
+            Column::Mtime => 130.,
+            Column::Permissions => 100.,
+            Column::Size => 90.,
+            Column::Owner | Column::Group => 80.,
+            Column::HumanSize => 60.,
+            Column::Links | Column::Git => 50.,
+        }
+    }
+
+    /// numbers are aligned to the right
+    pub fn right_aligned(self) -> bool {
+        matches!(self, Column::Size | Column::HumanSize | Column::Links)
+    }
+
+    /// what clicking its header sorts by, if anything
+    pub fn sort_key(self) -> Option<SortKey> {
+        match self {
+            Column::Name => Some(SortKey::Name),
+            Column::Size | Column::HumanSize => Some(SortKey::Size),
+            Column::Mtime => Some(SortKey::Mtime),
+            Column::Permissions => Some(SortKey::Permissions),
+            Column::Owner => Some(SortKey::Owner),
+            Column::Group => Some(SortKey::Group),
+            Column::Links => Some(SortKey::Links),
+            Column::Target => Some(SortKey::Target),
+            Column::Git => None,
+        }
+    }
+
+    /// The column's text for `entry`, directories' sizes are from `info`. `Git` is left to
+    /// `State::git_mark`. Nothing is read from the disk, user and group names are looked up
+    /// once.
+    pub fn cell(self, entry: &Entry, info: &DirInfo) -> String {
+        let meta = match (&entry.metadata, self) {
+            (_, Column::Name) => return entry.name.clone(),
+            (_, Column::Git) => return String::new(),
+            (Some(meta), _) => meta,
+            (None, _) => return "?".into(),
+        };
+        match self {
+            Column::Name | Column::Git => unreachable!(),
+            Column::Size if meta.is_dir() => info.du.map_or("-".into(), |du| du.bytes.to_string()),
+            Column::Size => meta.len().to_string(),
+            Column::HumanSize if meta.is_dir() => info.summary(),
+            Column::HumanSize => fmt::human_size(meta.len()),
+            Column::Mtime => meta.modified().map(fmt::local_time).unwrap_or_default(),
+            Column::Permissions => fmt::permissions(meta.file_type(), meta.mode()),
+            Column::Owner => user_name(meta.uid()),
+            Column::Group => group_name(meta.gid()),
+            Column::Links => meta.nlink().to_string(),
+            Column::Target => match &entry.link {
+                Some(Link { target: Some(target), .. }) => format!("-> {}", target.display()),
+                Some(Link { target: None, .. }) => "-> ?".into(),
+                None => String::new(),
+            },
+        }
+    }
+}
+
+thread_local! {
+    static USERS: RefCell<HashMap<u32, String>> = RefCell::new(HashMap::new());
+    static GROUPS: RefCell<HashMap<u32, String>> = RefCell::new(HashMap::new());
+}
+
+/// the name of the user `uid`, the number if it has none
+pub fn user_name(uid: u32) -> String {
+    USERS.with(|users| {
+        let mut users = users.borrow_mut();
+        let name = users.entry(uid);
+        name.or_insert_with(|| lookup_user(uid).unwrap_or_else(|| uid.to_string())).clone()
+    })
+}
+
+/// the name of the group `gid`, the number if it has none
+pub fn group_name(gid: u32) -> String {
+    GROUPS.with(|groups| {
+        let mut groups = groups.borrow_mut();
+        let name = groups.entry(gid);
+        name.or_insert_with(|| lookup_group(gid).unwrap_or_else(|| gid.to_string())).clone()
+    })
+}
+
+/// Calls a `getpwuid_r` like function with `buf` grown until it's big enough. The names it
+/// finds point into `buf`.
+fn with_buffer(
+    buf: &mut Vec<libc::c_char>,
+    mut lookup: impl FnMut(&mut [libc::c_char]) -> libc::c_int,
+) -> bool {
+    buf.resize(1024, 0);
+    loop {
+        match lookup(buf) {
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            0 => return true,
+            _ => return false,
+        }
+    }
+}
+
+fn lookup_user(uid: u32) -> Option<String> {
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let mut buf = vec![];
+    let found = with_buffer(&mut buf, |buf| unsafe {
+        libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result)
+    });
+    if !found || result.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(pwd.pw_name) }.to_string_lossy().into_owned())
+}
+
+fn lookup_group(gid: u32) -> Option<String> {
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let mut buf = vec![];
+    let found = with_buffer(&mut buf, |buf| unsafe {
+        libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut result)
+    });
+    if !found || result.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(grp.gr_name) }.to_string_lossy().into_owned())
+}
+
+/// the uid of the user `name`
+pub fn user_id(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let mut buf = vec![];
+    let found = with_buffer(&mut buf, |buf| unsafe {
+        libc::getpwnam_r(name.as_ptr(), &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result)
+    });
+    if !found || result.is_null() {
+        return None;
+    }
+    Some(pwd.pw_uid)
+}
+
+/// the gid of the group `name`
+pub fn group_id(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let mut buf = vec![];
+    let found = with_buffer(&mut buf, |buf| unsafe {
+        libc::getgrnam_r(name.as_ptr(), &mut grp, buf.as_mut_ptr(), buf.len(), &mut result)
+    });
+    if !found || result.is_null() {
+        return None;
+    }
+    Some(grp.gr_gid)
+}
+
+/// the detail view, `[details]` in the config
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DetailOptions {
+    /// instead of just the names
+    pub enabled: bool,
+    pub columns: Vec<Column>,
+}
+
+impl Default for DetailOptions {
+    fn default() -> Self {
+        let columns = vec![
+            Column::Name,
+            Column::Git,
+            Column::Permissions,
+            Column::Owner,
+            Column::HumanSize,
+            Column::Mtime,
+        ];
+        Self { enabled: false, columns }
+    }
+}
+
+#[cfg(test)]
+mod test_details {
+    use super::*;
+    use crate::entry::{read_entries, ListOptions};
+    use crate::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_cells() {
+        let dir = TestDir::new("details");
+        fs::write(dir.join("f"), "abc").unwrap();
+        std::os::unix::fs::symlink("f", dir.join("l")).unwrap();
+        let f = Entry::new(&dir, "f".into());
+        let l = Entry::new(&dir, "l".into());
+        let cell = |column: Column, entry| column.cell(entry, &DirInfo::default());
+        assert_eq!(cell(Column::Size, &f), "3");
+        assert_eq!(cell(Column::Links, &f), "1");
+        assert_eq!(cell(Column::Target, &l), "-> f");
+        assert_eq!(cell(Column::Target, &f), "");
+        let mtime = f.metadata.as_ref().unwrap().modified().unwrap();
+        assert_eq!(cell(Column::Mtime, &f), fmt::local_time(mtime));
+        // every header but git's sorts
+        let sorted = Column::ALL.iter().filter(|c| c.sort_key().is_some());
+        assert_eq!(sorted.count(), Column::ALL.len() - 1);
+        let options = ListOptions { sort: SortKey::Target, ..ListOptions::default() };
+        std::os::unix::fs::symlink("f", dir.join("0")).unwrap();
+        let entries = read_entries(&*dir, &options);
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["f", "0", "l"]);
+        assert!(cell(Column::Permissions, &l).starts_with('l'));
+        let parent = Entry::new(dir.parent().unwrap(), dir.file_name().unwrap().into());
+        let info = DirInfo { num_children: Some(2), du: None };
+        assert_eq!(Column::HumanSize.cell(&parent, &info), "2");
+        assert_eq!(Column::Size.cell(&parent, &info), "-");
+        // uid 0 isn't named root everywhere
+        assert_eq!(user_id(&user_name(0)), Some(0));
+        assert_eq!(group_id("no such group"), None);
+        assert_eq!(group_name(u32::MAX - 1), (u32::MAX - 1).to_string());
+    }
+}
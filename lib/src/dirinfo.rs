@@ -0,0 +1,253 @@
+//! What's known of directories beyond their metadata: how many entries they have and how much
+//! they weigh, both computed off the UI thread.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::fmt;
+
+/// counts are reported at least this often while a batch is counted
+const REPORT_EVERY: Duration = Duration::from_millis(100);
+
+/// a directory's recursive size, like `du`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Du {
+    /// of the files' contents
+    pub bytes: u64,
+    /// allocated for them on disk
+    pub disk: u64,
+    /// hard links are counted once
+    pub files: u64,
+    /// the directory included
+    pub dirs: u64,
+    /// entries that couldn't be read, they're left out
+    pub errors: u64,
+}
+
+impl Du {
+    /// `1.2G, 1.1G on disk, 340 files, 20 directories`
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "{}, {} on disk, {} files, {} directories",
+            fmt::human_size(self.bytes),
+            fmt::human_size(self.disk),
+            self.files,
+            self.dirs
+        );
+        if self.errors > 0 {
+            text.push_str(&format!(", {} unreadable", self.errors));
+        }
+        text
+    }
+}
+
+/// Walks `dir` without following symlinks. With `one_filesystem`, directories on other
+/// filesystems are skipped, like `du -x`.
+pub fn du(dir: &Path, one_filesystem: bool) -> io::Result<Du> {
+    let root = fs::symlink_metadata(dir)?;
+    if !root.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory"));
+    }
+    let device = root.dev();
+    let mut du = Du::default();
+    // (device, inode) of the files with several links already counted
+    let mut linked = HashSet::new();
+    let mut stack = vec![(dir.to_owned(), root)];
+    while let Some((dir, meta)) = stack.pop() {
+        du.dirs += 1;
+        du.bytes += meta.len();
+        du.disk += meta.blocks() * 512;
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => {
+                du.errors += 1;
+                continue;
+            }
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => {
+                    du.errors += 1;
+                    continue;
+                }
+            };
+            let meta = match entry.metadata() {
+                Ok(meta) => meta,
+                Err(_) => {
+                    du.errors += 1;
+                    continue;
+                }
+            };
+            if meta.is_dir() {
+                if !one_filesystem || meta.dev() == device {
+                    stack.push((entry.path(), meta));
+                }
+                continue;
+            }
+            if meta.nlink() > 1 && !linked.insert((meta.dev(), meta.ino())) {
+                continue;
+            }
+            du.files += 1;
+            du.bytes += meta.len();
+            du.disk += meta.blocks() * 512;
+        }
+    }
+    Ok(du)
+}
+
+/// the number of entries in `dir`, hidden ones included, `None` if it can't be read
+pub fn count_children(dir: &Path) -> Option<u32> {
+    let entries = fs::read_dir(dir).ok()?;
+    Some(entries.count().min(u32::MAX as usize) as u32)
+}
+
+/// what's known of a directory
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DirInfo {
+    /// `None` until counted, or if it can't be read
+    pub num_children: Option<u32>,
+    /// `None` until asked for with `:du`
+    pub du: Option<Du>,
+}
+
+impl DirInfo {
+    /// its size if known, else its number of entries
+    pub fn summary(&self) -> String {
+        match (self.du, self.num_children) {
+            (Some(du), _) => fmt::human_size(du.bytes),
+            (None, Some(n)) => n.to_string(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// The counts and sizes found so far, they're kept until the watcher reports a change.
+#[derive(Clone, Debug, Default)]
+pub struct DirCache {
+    /// `None` for the directories that couldn't be read
+    counts: HashMap<PathBuf, Option<u32>>,
+    sizes: HashMap<PathBuf, Du>,
+}
+
+impl DirCache {
+    pub fn info(&self, dir: &Path) -> DirInfo {
+        DirInfo {
+            num_children: self.counts.get(dir).copied().flatten(),
+            du: self.sizes.get(dir).copied(),
+        }
+    }
+
+    /// whether `dir` was counted, even if it couldn't be read
+    pub fn is_counted(&self, dir: &Path) -> bool {
+        self.counts.contains_key(dir)
+    }
+
+    pub fn set_count(&mut self, dir: PathBuf, count: Option<u32>) {
+        self.counts.insert(dir, count);
+    }
+
+    pub fn set_du(&mut self, dir: PathBuf, du: Du) {
+        self.sizes.insert(dir, du);
+    }
+
+    /// Forgets what `changed` may have changed: what's known of it and what's in it, its
+    /// parent's count and its ancestors' sizes. Everything if `None`.
+    pub fn invalidate(&mut self, changed: Option<&Path>) {
+        let changed = match changed {
+            Some(changed) => changed,
+            None => {
+                *self = Self::default();
+                return;
+            }
+        };
+        self.counts.retain(|dir, _| !dir.starts_with(changed));
+        self.sizes.retain(|dir, _| !dir.starts_with(changed) && !changed.starts_with(dir));
+        if let Some(parent) = changed.parent() {
+            self.counts.remove(parent);
+        }
+    }
+}
+
+/// Counts the children of directories on a thread of its own.
+pub struct Counter {
+    tx: Sender<Vec<PathBuf>>,
+}
+
+impl Counter {
+    /// `on_counted` is called from the counting thread with a few counts at a time
+    pub fn new(on_counted: impl Fn(Vec<(PathBuf, Option<u32>)>) + Send + 'static) -> Self {
+        let (tx, rx) = channel::<Vec<PathBuf>>();
+        thread::spawn(move || {
+            for dirs in rx {
+                let mut counted = vec![];
+                let mut reported = Instant::now();
+                for dir in dirs {
+                    let count = count_children(&dir);
+                    counted.push((dir, count));
+                    if reported.elapsed() >= REPORT_EVERY {
+                        on_counted(std::mem::take(&mut counted));
+                        reported = Instant::now();
+                    }
+                }
+                if !counted.is_empty() {
+                    on_counted(counted);
+                }
+            }
+        });
+        Self { tx }
+    }
+
+    /// counts `dirs` after the ones asked for before
+    pub fn count(&self, dirs: Vec<PathBuf>) {
+        // the thread only stops when `self` is dropped
+        let _ = self.tx.send(dirs);
+    }
+}
+
+#[cfg(test)]
+mod test_dirinfo {
+    use super::*;
+    use crate::TestDir;
+
+    #[test]
+    fn test_du() {
+        let dir = TestDir::new("du");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a"), vec![0; 1000]).unwrap();
+        fs::write(dir.join("sub/b"), vec![0; 24]).unwrap();
+        fs::hard_link(dir.join("a"), dir.join("sub/a")).unwrap();
+        std::os::unix::fs::symlink("/usr", dir.join("usr")).unwrap();
+        let du = du(&dir, true).unwrap();
+        let len = |path: &Path| fs::symlink_metadata(path).unwrap().len();
+        let dir_bytes = len(&dir) + len(&dir.join("sub"));
+        assert_eq!(du.bytes, 1024 + dir_bytes + len(&dir.join("usr")));
+        assert_eq!((du.files, du.dirs, du.errors), (3, 2, 0));
+        assert_eq!(count_children(&dir), Some(3));
+        assert_eq!(count_children(&dir.join("a")), None);
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let mut cache = DirCache::default();
+        let du = Du { bytes: 1, ..Du::default() };
+        for dir in &["/a", "/a/b", "/a/b/c", "/a/d"] {
+            cache.set_count(dir.into(), Some(1));
+            cache.set_du(dir.into(), du);
+        }
+        cache.invalidate(Some(Path::new("/a/b/c")));
+        assert_eq!(cache.info(Path::new("/a")), DirInfo { num_children: Some(1), du: None });
+        assert_eq!(cache.info(Path::new("/a/b")), DirInfo::default());
+        assert!(!cache.is_counted(Path::new("/a/b/c")));
+        assert_eq!(cache.info(Path::new("/a/d")), DirInfo { num_children: Some(1), du: Some(du) });
+        assert_eq!(cache.info(Path::new("/a")).summary(), "1");
+        cache.invalidate(None);
+        assert!(!cache.is_counted(Path::new("/a")));
+    }
+}
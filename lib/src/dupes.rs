@@ -0,0 +1,294 @@
+//! `:dupes`: the files under a directory with the same contents, found by size, then by a
+//! hash of their ends, then by a hash of all of it, and replacing them with hardlinks.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use ignore::WalkBuilder;
+
+use crate::fileops;
+use crate::fmt;
+
+/// progress is reported at most this often
+const REPORT_EVERY: Duration = Duration::from_millis(100);
+
+/// bytes of each end of a file hashed to tell apart files of the same size
+const END_LEN: u64 = 4096;
+
+/// files with the same contents
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Group {
+    /// of each of them
+    pub size: u64,
+    /// from the root of the scan, sorted
+    pub paths: Vec<PathBuf>,
+}
+
+impl Group {
+    /// what removing all of them but one would free
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+/// a hash of the first and last `END_LEN` bytes of a file of `size` bytes, of all of it if
+/// it's not bigger
+fn ends_hash(path: &Path, size: u64) -> io::Result<blake3::Hash> {
+    let mut file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    io::copy(&mut (&mut file).take(END_LEN), &mut hasher)?;
+    if size > END_LEN {
+        file.seek(SeekFrom::Start(size.saturating_sub(END_LEN).max(END_LEN)))?;
+        io::copy(&mut file, &mut hasher)?;
+    }
+    Ok(hasher.finalize())
+}
+
+fn full_hash(path: &Path) -> io::Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize())
+}
+
+/// Splits each of `groups` by `hash` of its files, keeping the parts of more than one. The
+/// files that can't be read are left out.
+fn split(
+    groups: Vec<Group>,
+    root: &Path,
+    cancel: &AtomicBool,
+    hash: &mut dyn FnMut(&Path, u64) -> io::Result<blake3::Hash>,
+) -> Option<Vec<Group>> {
+    let mut split = vec![];
+    for Group { size, paths } in groups {
+        let mut by_hash: HashMap<blake3::Hash, Vec<PathBuf>> = HashMap::new();
+        for path in paths {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            if let Ok(hash) = hash(&root.join(&path), size) {
+                by_hash.entry(hash).or_default().push(path);
+            }
+        }
+        let parts = by_hash.into_values().filter(|paths| paths.len() > 1);
+        split.extend(parts.map(|paths| Group { size, paths }));
+    }
+    Some(split)
+}
+
+/// Walks `root`, into hidden entries if `hidden`, for the groups of files with the same
+/// contents, the ones wasting the most first. Empty files and hardlinks of a file already
+/// seen are left out, and files on different filesystems are never grouped, since they
+/// can't be hardlinked. `progress` is called now and then with what's done so far. `None` if
+/// cancelled.
+pub fn scan(
+    root: &Path,
+    hidden: bool,
+    cancel: &AtomicBool,
+    mut progress: impl FnMut(String),
+) -> Option<Vec<Group>> {
+    let walk = WalkBuilder::new(root)
+        .standard_filters(false)
+        .hidden(!hidden)
+        // the first of hardlinks of the same file is the one kept
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+    let mut by_size: HashMap<(u64, u64), Vec<PathBuf>> = HashMap::new();
+    let mut inodes = HashSet::new();
+    let mut reported = Instant::now();
+    for (i, entry) in walk.flatten().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        if reported.elapsed() >= REPORT_EVERY {
+            progress(format!("{} entries read", i));
+            reported = Instant::now();
+        }
+        let meta = match entry.metadata() {
+            Ok(meta) if meta.is_file() && meta.len() > 0 => meta,
+            _ => continue,
+        };
+        if !inodes.insert((meta.dev(), meta.ino())) {
+            continue;
+        }
+        if let Ok(relative) = entry.path().strip_prefix(root) {
+            by_size.entry((meta.dev(), meta.len())).or_default().push(relative.to_owned());
+        }
+    }
+    let candidates = by_size.into_iter().filter(|(_, paths)| paths.len() > 1);
+    let groups: Vec<Group> = candidates.map(|((_, size), paths)| Group { size, paths }).collect();
+    let total: usize = groups.iter().map(|group| group.paths.len()).sum();
+    let mut hashed = 0;
+    let mut count = |progress: &mut dyn FnMut(String), what: &str| {
+        hashed += 1;
+        if reported.elapsed() >= REPORT_EVERY {
+            progress(format!("{} {} of {} candidates", what, hashed, total));
+            reported = Instant::now();
+        }
+    };
+    let groups = split(groups, root, cancel, &mut |path, size| {
+        count(&mut progress, "hashing the ends of");
+        ends_hash(path, size)
+    })?;
+    // the rest of the files that aren't all hashed yet
+    let (hashed_whole, partly): (Vec<Group>, Vec<Group>) =
+        groups.into_iter().partition(|group| group.size <= 2 * END_LEN);
+    let mut groups = split(partly, root, cancel, &mut |path, _| {
+        count(&mut progress, "hashing");
+        full_hash(path)
+    })?;
+    groups.extend(hashed_whole);
+    for group in &mut groups {
+        group.paths.sort();
+    }
+    groups.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.paths.cmp(&b.paths)));
+    Some(groups)
+}
+
+/// `dupes: 12 files in 5 groups, 3.2M wasted`
+pub fn summary(groups: &[Group]) -> String {
+    let files: usize = groups.iter().map(|group| group.paths.len()).sum();
+    let wasted = groups.iter().map(Group::wasted).sum();
+    format!("{} files in {} groups, {} wasted", files, groups.len(), fmt::human_size(wasted))
+}
+
+/// What to mark to keep one file of each group: all the files listed after the first of
+/// their group, as (name, group).
+pub fn all_but_first<'a>(listed: impl Iterator<Item = (&'a str, usize)>) -> Vec<&'a str> {
+    let mut seen = HashSet::new();
+    listed.filter(|&(_, group)| !seen.insert(group)).map(|(name, _)| name).collect()
+}
+
+/// The files `marked` paired with the first file of their group that isn't, to replace them
+/// with hardlinks of it, as (duplicate, original). The groups with all their files marked
+/// are left out, and counted.
+pub fn originals<'a>(
+    listed: impl Iterator<Item = (&'a str, usize)> + Clone,
+    marked: impl Fn(&str) -> bool,
+) -> (Vec<(&'a str, &'a str)>, usize) {
+    let mut kept = HashMap::new();
+    for (name, group) in listed.clone().filter(|&(name, _)| !marked(name)) {
+        kept.entry(group).or_insert(name);
+    }
+    let (mut pairs, mut groups_lost) = (vec![], HashSet::new());
+    for (name, group) in listed.filter(|&(name, _)| marked(name)) {
+        match kept.get(&group) {
+            Some(original) => pairs.push((name, *original)),
+            None => {
+                groups_lost.insert(group);
+            }
+        }
+    }
+    (pairs, groups_lost.len())
+}
+
+/// whether the files at `a` and `b` have the same contents, read side by side
+pub(crate) fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+    let (mut buf_a, mut buf_b) = (vec![0; 1 << 16], vec![0; 1 << 16]);
+    loop {
+        let read = a.read(&mut buf_a)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        b.read_exact(&mut buf_b[..read])?;
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Replaces the file at `duplicate` with a hardlink of the one at `original`, once checked
+/// they still have the same contents. It's swapped in, so `duplicate` isn't lost if linking
+/// fails.
+pub fn hardlink(original: &Path, duplicate: &Path) -> io::Result<()> {
+    let (a, b) = (fs::metadata(original)?, fs::symlink_metadata(duplicate)?);
+    if (a.dev(), a.ino()) == (b.dev(), b.ino()) {
+        return Ok(());
+    }
+    if !b.is_file() || !same_contents(original, duplicate)? {
+        let changed = format!("not the same as {} anymore", original.display());
+        return Err(io::Error::new(io::ErrorKind::InvalidData, changed));
+    }
+    let dir = duplicate.parent().unwrap_or_else(|| Path::new("/"));
+    let name = duplicate.file_name().unwrap_or_default().to_string_lossy();
+    let link = fileops::unique_path(dir, format!(".{}.yukari-link", name));
+    fs::hard_link(original, &link)?;
+    let renamed = fs::rename(&link, duplicate);
+    if renamed.is_err() {
+        let _ = fs::remove_file(&link);
+    }
+    renamed
+}
+
+#[cfg(test)]
+mod test_dupes {
+    use super::*;
+    use crate::TestDir;
+
+    #[test]
+    fn test_scan() {
+        let dir = TestDir::new("dupes");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        let big = |end: u8| {
+            let mut contents = vec![7; 3 * END_LEN as usize];
+            *contents.last_mut().unwrap() = end;
+            contents
+        };
+        // the middle of big files is only read once their ends match
+        let mut middle = big(0);
+        middle[END_LEN as usize + 1] = 1;
+        for (path, contents) in &[
+            ("a", b"same".to_vec()),
+            ("sub/a", b"same".to_vec()),
+            (".hidden/a", b"same".to_vec()),
+            ("b", b"diff".to_vec()),
+            ("empty", vec![]),
+            ("empty2", vec![]),
+            ("big", big(0)),
+            ("sub/big", big(0)),
+            ("big_end", big(1)),
+            ("big_middle", middle),
+        ] {
+            fs::write(dir.join(path), contents).unwrap();
+        }
+        fs::hard_link(dir.join("big"), dir.join("big_link")).unwrap();
+        let groups = scan(&dir, false, &AtomicBool::new(false), |_| ()).unwrap();
+        let paths = |group: &Group| -> Vec<String> {
+            group.paths.iter().map(|p| p.display().to_string()).collect()
+        };
+        assert_eq!(groups.len(), 2);
+        assert_eq!(paths(&groups[0]), ["big", "sub/big"]);
+        assert_eq!(paths(&groups[1]), ["a", "sub/a"]);
+        assert_eq!(groups[1].size, 4);
+        assert_eq!(summary(&groups), "4 files in 2 groups, 12K wasted");
+        let hidden = scan(&dir, true, &AtomicBool::new(false), |_| ()).unwrap();
+        assert_eq!(paths(&hidden[1]), [".hidden/a", "a", "sub/a"]);
+        assert_eq!(scan(&dir, false, &AtomicBool::new(true), |_| ()), None);
+
+        hardlink(&dir.join("a"), &dir.join("sub/a")).unwrap();
+        let (a, sub_a) = (fs::metadata(dir.join("a")).unwrap(), dir.join("sub/a"));
+        assert_eq!(a.ino(), fs::metadata(&sub_a).unwrap().ino());
+        assert_eq!(fs::read_dir(dir.join("sub")).unwrap().count(), 2);
+        assert!(hardlink(&dir.join("a"), &dir.join("b")).is_err());
+        assert_eq!(fs::read(dir.join("b")).unwrap(), b"diff");
+    }
+
+    #[test]
+    fn test_marks() {
+        let listed = [("a", 0), ("b", 1), ("c", 0), ("d", 1), ("e", 0), ("f", 2), ("g", 2)];
+        assert_eq!(all_but_first(listed.iter().copied()), ["c", "d", "e", "g"]);
+        let marked = |name: &str| ["a", "d", "f", "g"].contains(&name);
+        let (pairs, lost) = originals(listed.iter().copied(), marked);
+        assert_eq!(pairs, [("a", "c"), ("d", "b")]);
+        assert_eq!(lost, 1);
+    }
+}
@@ -0,0 +1,208 @@
+use serde::Deserialize;
+
+use std::cmp::Ordering;
+use std::ffi::OsString;
+use std::fs::{self, Metadata};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::details;
+
+/// a directory entry of the current listing
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// `file_name` for display, invalid UTF-8 replaced
+    pub name: String,
+    pub file_name: OsString,
+    /// not following symlinks, `None` if it couldn't be read
+    pub metadata: Option<Metadata>,
+    /// where it points to if it's a symlink
+    pub link: Option<Link>,
+}
+
+/// what a symlink points to, read once with its entry rather than each time it's drawn
+#[derive(Clone, Debug)]
+pub struct Link {
+    /// as the link has it, `None` if it couldn't be read
+    pub target: Option<PathBuf>,
+    /// of what it points to, following links, `None` if it's dangling
+    pub metadata: Option<Metadata>,
+}
+
+impl Entry {
+    /// reads the metadata of `name` in `dir`, and of what it points to if it's a symlink
+    pub fn new(dir: &Path, file_name: OsString) -> Self {
+        let path = dir.join(&file_name);
+        let metadata = fs::symlink_metadata(&path).ok();
+        let link = match &metadata {
+            Some(meta) if meta.file_type().is_symlink() => Some(Link {
+                target: fs::read_link(&path).ok(),
+                metadata: fs::metadata(&path).ok(),
+            }),
+            _ => None,
+        };
+        Self { name: file_name.to_string_lossy().into_owned(), file_name, metadata, link }
+    }
+
+    /// where the entry is if listed in `dir`
+    pub fn path(&self, dir: &Path) -> PathBuf {
+        dir.join(&self.file_name)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(&self.metadata, Some(m) if m.is_dir())
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        self.metadata.as_ref().map(Metadata::len)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Name,
+    Size,
+    Mtime,
+    /// then by name, like the other keys
+    Extension,
+    Permissions,
+    Owner,
+    Group,
+    Links,
+    /// of symlinks, the other entries first
+    Target,
+}
+
+impl SortKey {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(SortKey::Name),
+            "size" => Some(SortKey::Size),
+            "mtime" => Some(SortKey::Mtime),
+            "extension" => Some(SortKey::Extension),
+            "permissions" => Some(SortKey::Permissions),
+            "owner" => Some(SortKey::Owner),
+            "group" => Some(SortKey::Group),
+            "links" => Some(SortKey::Links),
+            "target" => Some(SortKey::Target),
+            _ => None,
+        }
+    }
+}
+
+/// which entries are listed and in what order
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListOptions {
+    pub show_hidden: bool,
+    pub sort: SortKey,
+    pub sort_reverse: bool,
+    /// directories before files, whatever the order
+    pub dirs_first: bool,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self { show_hidden: false, sort: SortKey::Name, sort_reverse: false, dirs_first: true }
+    }
+}
+
+/// the [`ListOptions`] set by name
+pub const OPTIONS: [&str; 4] = ["show_hidden", "sort", "sort_reverse", "dirs_first"];
+
+impl ListOptions {
+    /// sets an option by name, as in `:set sort size`; booleans also take `toggle`
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let flag = |current: bool| match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            "toggle" => Ok(!current),
+            _ => Err(format!("{}: expected true, false or toggle", name)),
+        };
+        match name {
+            "show_hidden" => self.show_hidden = flag(self.show_hidden)?,
+            "sort_reverse" => self.sort_reverse = flag(self.sort_reverse)?,
+            "dirs_first" => self.dirs_first = flag(self.dirs_first)?,
+            "sort" => {
+                self.sort = SortKey::from_name(value).ok_or(
+                    "sort: expected name, size, mtime, extension, permissions, owner, group, \
+                     links or target",
+                )?
+            }
+            _ => return Err(format!("unknown option {}", name)),
+        }
+        Ok(())
+    }
+
+    fn compare(&self, a: &Entry, b: &Entry) -> Ordering {
+        let by_key = match self.sort {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size().cmp(&b.size()),
+            SortKey::Mtime => {
+                let mtime = |e: &Entry| e.metadata.as_ref().and_then(|m| m.modified().ok());
+                mtime(a).cmp(&mtime(b))
+            }
+            SortKey::Extension => {
+                let ext = |e: &Entry| Path::new(&e.name).extension().map(|x| x.to_owned());
+                ext(a).cmp(&ext(b))
+            }
+            SortKey::Permissions => {
+                let mode = |e: &Entry| e.metadata.as_ref().map(|m| m.mode() & 0o7777);
+                mode(a).cmp(&mode(b))
+            }
+            SortKey::Owner => {
+                let owner = |e: &Entry| e.metadata.as_ref().map(|m| details::user_name(m.uid()));
+                owner(a).cmp(&owner(b))
+            }
+            SortKey::Group => {
+                let group = |e: &Entry| e.metadata.as_ref().map(|m| details::group_name(m.gid()));
+                group(a).cmp(&group(b))
+            }
+            SortKey::Links => {
+                let links = |e: &Entry| e.metadata.as_ref().map(MetadataExt::nlink);
+                links(a).cmp(&links(b))
+            }
+            SortKey::Target => {
+                let target = |e: &Entry| e.link.as_ref().map(|link| link.target.clone());
+                target(a).cmp(&target(b))
+            }
+        };
+        let order = by_key.then_with(|| a.name.cmp(&b.name));
+        let order = if self.sort_reverse { order.reverse() } else { order };
+        if self.dirs_first {
+            b.is_dir().cmp(&a.is_dir()).then(order)
+        } else {
+            order
+        }
+    }
+}
+
+/// entries of `dir` as listed with `options`, empty if it can't be read
+pub fn read_entries(dir: impl AsRef<Path>, options: &ListOptions) -> Vec<Entry> {
+    let dir = dir.as_ref();
+    let mut entries: Vec<Entry> = match fs::read_dir(dir) {
+        Ok(it) => it
+            .filter_map(Result::ok)
+            .map(|de| Entry::new(dir, de.file_name()))
+            .filter(|e| options.show_hidden || !e.name.starts_with('.'))
+            .collect(),
+        Err(_) => vec![],
+    };
+    sort_entries(&mut entries, options);
+    entries
+}
+
+/// whether `name` contains `part`, ignoring case unless `part` has capitals
+pub fn contains(name: &str, part: &str) -> bool {
+    if part.chars().any(char::is_uppercase) {
+        name.contains(part)
+    } else {
+        name.to_lowercase().contains(part)
+    }
+}
+
+/// in the order of `options`
+pub fn sort_entries(entries: &mut [Entry], options: &ListOptions) {
+    entries.sort_by(|a, b| options.compare(a, b));
+}
@@ -0,0 +1,162 @@
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::fmt;
+use crate::xattrs;
+
+/// `dir/name`, or `dir/name_N` for the first N that doesn't exist yet
+pub fn unique_path(dir: impl AsRef<Path>, name: impl AsRef<OsStr>) -> PathBuf {
+    let dir = dir.as_ref();
+    let name = name.as_ref();
+    let mut path = dir.join(name);
+    let mut n = 1;
+    while fs::symlink_metadata(&path).is_ok() {
+        let mut numbered = OsString::from(name);
+        numbered.push(format!("_{}", n));
+        path = dir.join(numbered);
+        n += 1;
+    }
+    path
+}
+
+fn destination(src: &Path, dir: &Path) -> io::Result<PathBuf> {
+    let name = src.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("{}: no file name", src.display()))
+    })?;
+    if dir.starts_with(src) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: can't copy a directory into itself", src.display()),
+        ));
+    }
+    Ok(unique_path(dir, name))
+}
+
+/// copies `src` recursively, symlinks are copied as links, with the extended attributes of
+/// files and directories if `xattrs`
+pub fn copy_all(src: &Path, dest: &Path, xattrs: bool) -> io::Result<()> {
+    let meta = fs::symlink_metadata(src)?;
+    if meta.file_type().is_symlink() {
+        return symlink(fs::read_link(src)?, dest);
+    }
+    if meta.is_dir() {
+        fs::create_dir(dest)?;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &dest.join(entry.file_name()), xattrs)?;
+        }
+    } else {
+        fs::copy(src, dest)?;
+    }
+    if xattrs {
+        xattrs::copy(src, dest)?;
+    }
+    // last, it may not be writable
+    if meta.is_dir() {
+        fs::set_permissions(dest, meta.permissions())?;
+    }
+    Ok(())
+}
+
+/// Copies every one of `sources` into `dir`, renaming them if the name is taken, see
+/// [`copy_all`]. `progress` is called with (entries done, total entries).
+pub fn copy_into(
+    sources: &[PathBuf],
+    dir: &Path,
+    xattrs: bool,
+    mut progress: impl FnMut(u64, u64),
+) -> io::Result<Vec<PathBuf>> {
+    let mut out = vec![];
+    for (i, src) in sources.iter().enumerate() {
+        let dest = destination(src, dir)?;
+        copy_all(src, &dest, xattrs)?;
+        out.push(dest);
+        progress(i as u64 + 1, sources.len() as u64);
+    }
+    Ok(out)
+}
+
+/// Renames `src` to `dest`, or copies then removes it if they're on different filesystems.
+/// Any other error is returned as is, so a failed move never leaves it half copied.
+fn rename_or_copy(src: &Path, dest: &Path, xattrs: bool) -> io::Result<()> {
+    match fs::rename(src, dest) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            if let Err(e) = copy_all(src, dest, xattrs) {
+                let _ = remove_all(dest);
+                return Err(e);
+            }
+            remove_all(src)
+        }
+        result => result,
+    }
+}
+
+/// Like [`copy_into`] but moves, copying and removing across filesystems.
+pub fn move_into(
+    sources: &[PathBuf],
+    dir: &Path,
+    xattrs: bool,
+    mut progress: impl FnMut(u64, u64),
+) -> io::Result<Vec<PathBuf>> {
+    let mut out = vec![];
+    for (i, src) in sources.iter().enumerate() {
+        if src.parent() == Some(dir) {
+            continue;
+        }
+        let dest = destination(src, dir)?;
+        rename_or_copy(src, &dest, xattrs)?;
+        out.push(dest);
+        progress(i as u64 + 1, sources.len() as u64);
+    }
+    Ok(out)
+}
+
+/// removes files, links and whole directories
+pub fn remove_all(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// `path` as a trash info file has it, with what's not plain percent-encoded like in URLs
+fn percent_encoded(path: &Path) -> String {
+    let plain = |b: u8| b.is_ascii_alphanumeric() || b"-_.~/".contains(&b);
+    let bytes = path.as_os_str().as_bytes().iter();
+    bytes.map(|&b| if plain(b) { (b as char).to_string() } else { format!("%{:02X}", b) }).collect()
+}
+
+/// Moves `path`, absolute, to `trash` as the freedesktop.org trash spec has it: into
+/// `files`, with an `info` file telling where it was and when it was trashed. Returns where
+/// it's been moved.
+pub fn trash(path: &Path, trash: &Path) -> io::Result<PathBuf> {
+    let (files, info) = (trash.join("files"), trash.join("info"));
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("{}: no file name", path.display()))
+    })?;
+    let dest = unique_path(&files, name);
+    let mut info_name = dest.file_name().unwrap_or(name).to_owned();
+    info_name.push(".trashinfo");
+    let info = info.join(info_name);
+    let mut file = OpenOptions::new().write(true).create_new(true).open(&info)?;
+    let date = fmt::local_iso_time(SystemTime::now());
+    write!(file, "[Trash Info]\nPath={}\nDeletionDate={}\n", percent_encoded(path), date)?;
+    match rename_or_copy(path, &dest, true) {
+        Ok(()) => Ok(dest),
+        Err(e) => {
+            // unless some of it was moved
+            if fs::symlink_metadata(&dest).is_err() {
+                let _ = fs::remove_file(&info);
+            }
+            Err(e)
+        }
+    }
+}
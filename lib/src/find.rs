@@ -0,0 +1,212 @@
+//! `:find`: the entries under a directory whose names match a pattern, skipping what
+//! `.gitignore` and `.ignore` files ignore.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::WalkBuilder;
+use regex::Regex;
+
+use crate::fuzzy::Matcher;
+
+/// results are reported at least this often while searching
+const REPORT_EVERY: Duration = Duration::from_millis(100);
+
+/// how a pattern is written
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    /// `*.rs`, matching the whole name, or the path from the root if it has a `/`
+    Glob,
+    /// anywhere in the name
+    Regex,
+    /// the pattern's characters in order in the name, ignoring case
+    Fuzzy,
+}
+
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    /// and whether it matches paths rather than names
+    Glob(GlobMatcher, bool),
+    Regex(Regex),
+    Fuzzy(Matcher),
+}
+
+impl Pattern {
+    pub fn new(syntax: Syntax, pattern: &str) -> Result<Self, String> {
+        Ok(match syntax {
+            Syntax::Glob => {
+                let glob = GlobBuilder::new(pattern).literal_separator(true).build();
+                let glob = glob.map_err(|e| e.kind().to_string())?;
+                Pattern::Glob(glob.compile_matcher(), pattern.contains('/'))
+            }
+            Syntax::Regex => Pattern::Regex(Regex::new(pattern).map_err(|e| e.to_string())?),
+            Syntax::Fuzzy => Pattern::Fuzzy(Matcher::new(&pattern.to_lowercase())),
+        })
+    }
+
+    /// whether the entry at `relative` from the root of the search matches
+    pub fn is_match(&self, relative: &Path) -> bool {
+        let name = relative.file_name().unwrap_or_default().to_string_lossy();
+        match self {
+            Pattern::Glob(glob, true) => glob.is_match(relative),
+            Pattern::Glob(glob, false) => glob.is_match(name.as_ref()),
+            Pattern::Regex(regex) => regex.is_match(&name),
+            Pattern::Fuzzy(matcher) => matcher.is_match(&name),
+        }
+    }
+}
+
+/// an entry found by a search
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Found {
+    /// from the search's root
+    pub path: PathBuf,
+    /// the 1-based numbers of the lines matching, for searches of the contents
+    pub lines: Vec<usize>,
+}
+
+/// what to look for and where not to
+#[derive(Clone, Debug)]
+pub struct Query {
+    pub pattern: Pattern,
+    /// look into hidden entries too
+    pub hidden: bool,
+    /// and into what ignore files ignore
+    pub ignored: bool,
+}
+
+/// Walks `root` for the entries matching `query`, without following symlinks, and passes
+/// them to `found` a few at a time. Returns whether it got to the end, rather than being
+/// cancelled.
+pub fn find(
+    root: &Path,
+    query: &Query,
+    cancel: &AtomicBool,
+    mut found: impl FnMut(Vec<Found>),
+) -> bool {
+    let respect = !query.ignored;
+    let walk = WalkBuilder::new(root)
+        .hidden(!query.hidden)
+        .parents(respect)
+        .ignore(respect)
+        .git_ignore(respect)
+        .git_global(respect)
+        .git_exclude(respect)
+        // .gitignore files apply outside of repositories too, like `.ignore`
+        .require_git(false)
+        .build();
+    let mut batch = vec![];
+    let mut reported = Instant::now();
+    for entry in walk.flatten() {
+        if cancel.load(Ordering::Relaxed) {
+            return false;
+        }
+        let relative = match entry.path().strip_prefix(root) {
+            Ok(relative) if entry.depth() > 0 => relative,
+            _ => continue,
+        };
+        if query.pattern.is_match(relative) {
+            batch.push(Found { path: relative.to_owned(), lines: vec![] });
+        }
+        if !batch.is_empty() && reported.elapsed() >= REPORT_EVERY {
+            found(std::mem::take(&mut batch));
+            reported = Instant::now();
+        }
+    }
+    if !batch.is_empty() {
+        found(batch);
+    }
+    true
+}
+
+/// results listed in place of a directory's entries, see `State::show_search`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Search {
+    /// tells the results of a search from another's
+    pub id: u64,
+    /// as typed
+    pub query: String,
+    pub found: usize,
+    pub done: bool,
+    /// for `:dupes`, the group of each result by name, they're listed group after group
+    /// rather than sorted
+    pub groups: Option<Arc<HashMap<String, usize>>>,
+}
+
+impl Search {
+    pub fn new(id: u64, query: impl Into<String>) -> Self {
+        Self { id, query: query.into(), found: 0, done: false, groups: None }
+    }
+
+    /// `find *.rs: 12 found, searching`
+    pub fn describe(&self) -> String {
+        let searching = if self.done { "" } else { ", searching" };
+        format!("{}: {} found{}", self.query, self.found, searching)
+    }
+}
+
+#[cfg(test)]
+mod test_find {
+    use super::*;
+    use crate::TestDir;
+    use std::fs;
+
+    fn names(pattern: Pattern, dir: &Path, hidden: bool, ignored: bool) -> Vec<String> {
+        let query = Query { pattern, hidden, ignored };
+        let mut found = vec![];
+        assert!(find(dir, &query, &AtomicBool::new(false), |f| found.extend(f)));
+        let mut names: Vec<String> = found.iter().map(|f| f.path.display().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_patterns() {
+        let glob = Pattern::new(Syntax::Glob, "*.rs").unwrap();
+        assert!(glob.is_match(Path::new("src/main.rs")));
+        assert!(!glob.is_match(Path::new("src/main.rso")));
+        let path = Pattern::new(Syntax::Glob, "src/*.rs").unwrap();
+        assert!(path.is_match(Path::new("src/main.rs")));
+        assert!(!path.is_match(Path::new("src/a/main.rs")));
+        let regex = Pattern::new(Syntax::Regex, "^ma.n").unwrap();
+        assert!(regex.is_match(Path::new("src/main.rs")));
+        assert!(!regex.is_match(Path::new("main/lib.rs")));
+        let fuzzy = Pattern::new(Syntax::Fuzzy, "MnRs").unwrap();
+        assert!(fuzzy.is_match(Path::new("main.rs")));
+        assert!(!fuzzy.is_match(Path::new("rs.main")));
+        assert!(Pattern::new(Syntax::Regex, "(").is_err());
+        assert!(Pattern::new(Syntax::Glob, "[").is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        let dir = TestDir::new("find");
+        fs::create_dir_all(dir.join("src/target")).unwrap();
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        for file in &["a.rs", "src/b.rs", "src/target/c.rs", ".hidden/d.rs", "e.txt"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let rs = || Pattern::new(Syntax::Glob, "*.rs").unwrap();
+        assert_eq!(names(rs(), &dir, false, false), ["a.rs", "src/b.rs"]);
+        assert_eq!(names(rs(), &dir, true, false), [".hidden/d.rs", "a.rs", "src/b.rs"]);
+        assert_eq!(names(rs(), &dir, false, true), ["a.rs", "src/b.rs", "src/target/c.rs"]);
+        let fuzzy = Pattern::new(Syntax::Fuzzy, "src").unwrap();
+        assert_eq!(names(fuzzy, &dir, false, false), ["src"]);
+        let query = Query { pattern: rs(), hidden: false, ignored: false };
+        assert!(!find(&dir, &query, &AtomicBool::new(true), |_| ()));
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut search = Search::new(1, "find *.rs");
+        search.found = 12;
+        assert_eq!(search.describe(), "find *.rs: 12 found, searching");
+        search.done = true;
+        assert_eq!(search.describe(), "find *.rs: 12 found");
+    }
+}
@@ -0,0 +1,167 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `1.5K`, `23M`, ... with 1024 based units
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut size = bytes as f64 / 1024.;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+    if size < 10. {
+        format!("{:.1}{}", size, UNITS[unit])
+    } else {
+        format!("{:.0}{}", size, UNITS[unit])
+    }
+}
+
+/// `drwxr-xr-x` like `ls -l`, from the file type and the mode bits
+pub fn permissions(file_type: std::fs::FileType, mode: u32) -> String {
+    use std::os::unix::fs::FileTypeExt;
+    let kind = if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_char_device() {
+        'c'
+    } else {
+        '-'
+    };
+    let mut s = String::with_capacity(10);
+    s.push(kind);
+    // shift of the rwx bits, the setuid, setgid or sticky bit, its letter with and without x
+    let classes = [(6, 0o4000, 's', 'S'), (3, 0o2000, 's', 'S'), (0, 0o1000, 't', 'T')];
+    for &(shift, special, set_x, set) in &classes {
+        let bits = mode >> shift;
+        s.push(if bits & 4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 2 != 0 { 'w' } else { '-' });
+        s.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => set_x,
+            (false, true) => set,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
+
+/// `YYYY-MM-DD HH:MM` in UTC
+pub fn time(t: SystemTime) -> String {
+    let secs = match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    let (y, m, d) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", y, m, d, rem / 3600, rem % 3600 / 60)
+}
+
+/// `YYYY-MM-DD HH:MM` in local time
+pub fn local_time(t: SystemTime) -> String {
+    let secs = match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    precise_time(secs + utc_offset(secs), 0)[..16].to_owned()
+}
+
+/// `YYYY-MM-DDTHH:MM:SS` in UTC, like ISO 8601 has it
+pub fn iso_time(t: SystemTime) -> String {
+    let secs = match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    precise_time(secs, 0)[..19].replacen(' ', "T", 1)
+}
+
+/// `YYYY-MM-DDTHH:MM:SS` in local time, without the offset, like the trash has it
+pub fn local_iso_time(t: SystemTime) -> String {
+    let secs = match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    precise_time(secs + utc_offset(secs), 0)[..19].replacen(' ', "T", 1)
+}
+
+/// seconds the local time is ahead of UTC at `secs` since the epoch, 0 if unknown
+fn utc_offset(secs: i64) -> i64 {
+    let time = secs as libc::time_t;
+    // fully written by localtime_r
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+/// `YYYY-MM-DD HH:MM:SS.NNNNNNNNN` in UTC, from seconds and nanoseconds since the epoch like
+/// `stat` has them
+pub fn precise_time(secs: i64, nanos: i64) -> String {
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    let (y, m, d) = civil_from_days(days);
+    let (hours, minutes, seconds) = (rem / 3600, rem % 3600 / 60, rem % 60);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:09}", y, m, d, hours, minutes, seconds, nanos)
+}
+
+/// (year, month, day) from days since 1970-01-01, see
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+#[cfg(test)]
+mod test_fmt {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0");
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(1536), "1.5K");
+        assert_eq!(human_size(20 * 1024 * 1024), "20M");
+        assert_eq!(human_size(u64::MAX), "16E");
+    }
+
+    #[test]
+    fn test_permissions() {
+        let dir = std::env::temp_dir();
+        let file_type = std::fs::metadata(&dir).unwrap().file_type();
+        assert_eq!(permissions(file_type, 0o1777), "drwxrwxrwt");
+        assert_eq!(permissions(file_type, 0o4644), "drwSr--r--");
+        assert_eq!(permissions(file_type, 0o2750), "drwxr-s---");
+    }
+
+    #[test]
+    fn test_time() {
+        assert_eq!(time(UNIX_EPOCH), "1970-01-01 00:00");
+        assert_eq!(time(UNIX_EPOCH + Duration::from_secs(951_782_400 + 3660)), "2000-02-29 01:01");
+        assert_eq!(time(UNIX_EPOCH - Duration::from_secs(60)), "1969-12-31 23:59");
+        assert_eq!(precise_time(951_782_400 + 3661, 5), "2000-02-29 01:01:01.000000005");
+        assert_eq!(precise_time(-1, 999_999_999), "1969-12-31 23:59:59.999999999");
+        assert_eq!(iso_time(UNIX_EPOCH + Duration::from_secs(61)), "1970-01-01T00:01:01");
+        let year = 365 * 86400;
+        let local = UNIX_EPOCH + Duration::from_secs((year + utc_offset(year)) as u64);
+        assert_eq!(local_iso_time(UNIX_EPOCH + Duration::from_secs(year as u64)), iso_time(local));
+        assert_eq!(local_time(UNIX_EPOCH + Duration::from_secs(year as u64)), time(local));
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::fuzzy::Matcher;
+use crate::xdg;
+
+/// once the ranks add up to this, they're all aged
+const MAX_TOTAL: f64 = 10_000.;
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+/// seconds between saves of the visits, what's left is saved when the database is dropped
+const SAVE_EVERY: u64 = 30;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Entry {
+    rank: f64,
+    /// seconds since the epoch
+    last: u64,
+}
+
+impl Entry {
+    /// rank weighted by how recent the last visit was, like zoxide
+    fn score(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last);
+        let factor = if age < HOUR {
+            4.
+        } else if age < DAY {
+            2.
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+        self.rank * factor
+    }
+}
+
+/// Visit counts and times of directories, stored one `path|rank|time` per line like `z`.
+#[derive(Clone, Debug, Default)]
+pub struct Frecency {
+    dirs: HashMap<PathBuf, Entry>,
+    file: Option<PathBuf>,
+    /// whether there are visits not saved yet
+    unsaved: bool,
+    /// when it was last saved, in seconds since the epoch
+    saved: u64,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+impl Frecency {
+    /// `$XDG_DATA_HOME/yukari/frecency`
+    pub fn default_file() -> Option<PathBuf> {
+        xdg::data_dir().map(|d| d.join("frecency"))
+    }
+
+    /// missing or unreadable files give an empty database, malformed lines are skipped
+    pub fn load(file: impl Into<PathBuf>) -> Self {
+        let file = file.into();
+        let mut dirs = HashMap::new();
+        for line in fs::read_to_string(&file).unwrap_or_default().lines() {
+            let mut fields = line.rsplitn(3, '|');
+            if let (Some(last), Some(rank), Some(path)) = (fields.next(), fields.next(), fields.next()) {
+                if let (Ok(rank), Ok(last)) = (rank.parse(), last.parse()) {
+                    dirs.insert(path.into(), Entry { rank, last });
+                }
+            }
+        }
+        Self { dirs, file: Some(file), unsaved: false, saved: 0 }
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        self.unsaved = false;
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut s = String::new();
+        for (path, e) in &self.dirs {
+            s.push_str(&format!("{}|{}|{}\n", path.to_string_lossy(), e.rank, e.last));
+        }
+        fs::write(file, s)
+    }
+
+    /// saves the visits not saved yet, unless the last save is more recent than `SAVE_EVERY`
+    pub fn save_soon(&mut self, now: u64) -> io::Result<()> {
+        if !self.unsaved || now < self.saved + SAVE_EVERY {
+            return Ok(());
+        }
+        self.saved = now;
+        self.save()
+    }
+
+    pub fn visit(&mut self, dir: &Path, now: u64) {
+        self.unsaved = true;
+        let entry = self.dirs.entry(dir.to_owned()).or_insert(Entry { rank: 0., last: now });
+        entry.rank += 1.;
+        entry.last = now;
+        if self.dirs.values().map(|e| e.rank).sum::<f64>() > MAX_TOTAL {
+            for e in self.dirs.values_mut() {
+                e.rank *= 0.9;
+            }
+            self.dirs.retain(|_, e| e.rank >= 1.);
+        }
+    }
+
+    /// Directories matching all the whitespace separated keywords, best first, but
+    /// `current`. The keywords' characters must appear in order (case insensitive) and
+    /// those of the last one in the directory's own name, how well they do weighs on the
+    /// frecency. Directories which no longer exist are forgotten.
+    pub fn query(&mut self, query: &str, current: &Path, now: u64) -> Vec<PathBuf> {
+        self.dirs.retain(|path, _| path.is_dir());
+        let (mut all, last) = matchers(query);
+        let mut found: Vec<(&PathBuf, f64)> = vec![];
+        for (path, e) in self.dirs.iter().filter(|(path, _)| *path != current) {
+            if let Some(score) = matches(path, &mut all, &last) {
+                found.push((path, e.score(now) * score.max(1) as f64));
+            }
+        }
+        found.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        found.into_iter().map(|(path, _)| path.clone()).collect()
+    }
+}
+
+impl Drop for Frecency {
+    fn drop(&mut self) {
+        if self.unsaved {
+            let _ = self.save();
+        }
+    }
+}
+
+/// for the keywords of `query` run together, and for the last one
+fn matchers(query: &str) -> (Matcher, Matcher) {
+    let keywords: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    let last = keywords.last().map_or("", String::as_str);
+    (Matcher::new(&keywords.concat()), Matcher::new(last))
+}
+
+/// how well `path` matches the keywords, `all` of them and the `last` one, see `matchers`
+fn matches(path: &Path, all: &mut Matcher, last: &Matcher) -> Option<i64> {
+    let name = path.file_name().map(|name| name.to_string_lossy());
+    if !last.is_empty() && !matches!(&name, Some(name) if last.is_match(name)) {
+        return None;
+    }
+    all.score(&path.to_string_lossy()).map(|m| m.score)
+}
+
+#[cfg(test)]
+mod test_frecency {
+    use super::*;
+    use crate::TestDir;
+
+    #[test]
+    fn test_matches() {
+        let path = Path::new("/home/u/Projects/yukari");
+        let score = |query: &str| {
+            let (mut all, last) = matchers(query);
+            matches(path, &mut all, &last)
+        };
+        assert!(score("yuk").is_some());
+        assert!(score("proj yuk").is_some());
+        assert!(score("prj yki").is_some());
+        assert!(score("").is_some());
+        assert!(score("yuk proj").is_none());
+        assert!(score("proj").is_none());
+        assert!(score("yuk") > score("yri"));
+    }
+
+    #[test]
+    fn test_score() {
+        let e = Entry { rank: 2., last: 0 };
+        assert_eq!(e.score(10), 8.);
+        assert_eq!(e.score(2 * HOUR), 4.);
+        assert_eq!(e.score(2 * DAY), 1.);
+        assert_eq!(e.score(2 * WEEK), 0.5);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut f = Frecency::default();
+        let (tmp, root) = (std::env::temp_dir(), PathBuf::from("/"));
+        f.visit(&tmp, 0);
+        f.visit(&root, 2 * WEEK);
+        f.visit(Path::new("/surely/not/a/dir"), 2 * WEEK);
+        // 1 recent visit beats 1 old one
+        let elsewhere = Path::new("/surely/not/a/dir");
+        assert_eq!(f.query("", elsewhere, 2 * WEEK), vec![root.clone(), tmp.clone()]);
+        for _ in 0..20 {
+            f.visit(&tmp, 0);
+        }
+        assert_eq!(f.query("", elsewhere, 2 * WEEK), vec![tmp.clone(), root.clone()]);
+        assert_eq!(f.dirs.len(), 2);
+        // not where it is already
+        assert_eq!(f.query("", &tmp, 2 * WEEK), vec![root]);
+    }
+
+    #[test]
+    fn test_save() {
+        let dir = TestDir::new("frecency");
+        let file = dir.join("frecency");
+        let mut f = Frecency::load(file.as_path());
+        f.visit(&dir, 100);
+        f.save_soon(100).unwrap();
+        assert_eq!(Frecency::load(file.as_path()).dirs.len(), 1);
+        // too soon
+        f.visit(Path::new("/"), 110);
+        f.save_soon(110).unwrap();
+        assert_eq!(Frecency::load(file.as_path()).dirs.len(), 1);
+        drop(f);
+        assert_eq!(Frecency::load(file.as_path()).dirs.len(), 2);
+    }
+}
@@ -0,0 +1,446 @@
+//! The finder: fzf-like fuzzy matching of the paths under a directory, the best matches
+//! listed in a popup as the pattern is typed. The matching is shared with `:find -f` and
+//! `:z`.
+
+use std::cmp::Reverse;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::find::{self, Pattern, Query};
+use crate::popup::{Popup, PopupKind};
+
+/// at most this many matches are listed
+const MAX_SHOWN: usize = 200;
+/// the finder stops listing paths past this many
+const MAX_PATHS: usize = 100_000;
+/// paths ranked between looks for a newer pattern
+const CHUNK: usize = 1000;
+/// each character matched
+const SCORE_MATCH: i64 = 16;
+/// for skipping characters, and for each skipped after the first
+const GAP_START: i64 = -3;
+const GAP_EXTENSION: i64 = -1;
+/// matching right after the previous character matched
+const BONUS_CONSECUTIVE: i64 = 4;
+/// matching the start of a path component
+const BONUS_SLASH: i64 = 10;
+/// matching the start of a word, after `_`, `-`, `.` or a space
+const BONUS_BOUNDARY: i64 = 8;
+/// matching a capital after a lowercase letter
+const BONUS_CAMEL: i64 = 7;
+/// matching in the last component of the path
+const BONUS_BASENAME: i64 = 6;
+
+/// how well a path matches
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    pub score: i64,
+    /// bytes of the path matched, merged when adjacent
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// what matching `c`, after `prev`, is worth besides `SCORE_MATCH`
+fn bonus(prev: Option<char>, c: char) -> i64 {
+    match prev {
+        None | Some('/') => BONUS_SLASH,
+        Some('_') | Some('-') | Some('.') | Some(' ') => BONUS_BOUNDARY,
+        Some(p) if p.is_lowercase() && c.is_uppercase() => BONUS_CAMEL,
+        Some(p) if !p.is_alphanumeric() && c.is_alphanumeric() => BONUS_BOUNDARY,
+        _ => 0,
+    }
+}
+
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// A pattern whose characters are looked for in order in paths, ignoring case unless it has
+/// capitals. What scoring needs is kept from one path to the next.
+#[derive(Clone, Debug, Default)]
+pub struct Matcher {
+    /// folded
+    pattern: Vec<char>,
+    ignore_case: bool,
+    scratch: Scratch,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Scratch {
+    /// of the path, with their byte offsets
+    chars: Vec<(usize, char)>,
+    /// what matching each of them is worth
+    worth: Vec<i64>,
+    /// scores[i * n + j]: the best with the `i`th character of the pattern matched at `j`,
+    /// from[i * n + j]: where the one before is matched then
+    scores: Vec<i64>,
+    from: Vec<usize>,
+}
+
+impl Matcher {
+    pub fn new(pattern: &str) -> Self {
+        let ignore_case = !pattern.chars().any(char::is_uppercase);
+        let fold = |c: char| if ignore_case { lower(c) } else { c };
+        Self { pattern: pattern.chars().map(fold).collect(), ignore_case, ..Self::default() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_empty()
+    }
+
+    /// whether the characters of the pattern appear in order in `path`, cheaper than scoring
+    pub fn is_match(&self, path: &str) -> bool {
+        let ignore_case = self.ignore_case;
+        let mut folded = path.chars().map(|c| if ignore_case { lower(c) } else { c });
+        self.pattern.iter().all(|&p| folded.any(|c| c == p))
+    }
+
+    /// the best way for the characters of the pattern to appear in order in `path`, if they do
+    pub fn score(&mut self, path: &str) -> Option<Match> {
+        // most paths don't match at all
+        if !self.is_match(path) {
+            return None;
+        }
+        if self.pattern.is_empty() {
+            return Some(Match { score: 0, ranges: vec![] });
+        }
+        let Self { pattern, ignore_case, scratch } = self;
+        let ignore_case = *ignore_case;
+        let fold = |c: char| if ignore_case { lower(c) } else { c };
+        let Scratch { chars, worth, scores, from } = scratch;
+        chars.clear();
+        chars.extend(path.char_indices());
+        let basename = path.trim_end_matches('/').rfind('/').map_or(0, |i| i + 1);
+        worth.clear();
+        worth.extend((0..chars.len()).map(|j| {
+            let prev = j.checked_sub(1).map(|k| chars[k].1);
+            let name = if chars[j].0 >= basename { BONUS_BASENAME } else { 0 };
+            SCORE_MATCH + bonus(prev, chars[j].1) + name
+        }));
+        const NONE: i64 = i64::MIN / 2;
+        let (m, n) = (pattern.len(), chars.len());
+        scores.clear();
+        scores.resize(m * n, NONE);
+        from.clear();
+        from.resize(m * n, 0);
+        for (i, &p) in pattern.iter().enumerate() {
+            let (row, above) = (i * n, i.saturating_sub(1) * n);
+            // the best of scores[i - 1][k] - GAP_EXTENSION * k, for the k before j - 1, and its k
+            let mut gapped = (NONE, 0);
+            for j in i..n {
+                if i > 0 && j >= 2 {
+                    let k = j - 2;
+                    let extended = scores[above + k] - GAP_EXTENSION * k as i64;
+                    if scores[above + k] > NONE && extended > gapped.0 {
+                        gapped = (extended, k);
+                    }
+                }
+                if fold(chars[j].1) != p {
+                    continue;
+                }
+                if i == 0 {
+                    scores[row + j] = worth[j];
+                    continue;
+                }
+                let consecutive = scores[above + j - 1] + BONUS_CONSECUTIVE;
+                // skipping the j - k - 1 characters in between
+                let skipped = gapped.0 + GAP_START + GAP_EXTENSION * (j as i64 - 2);
+                let (best, k) = if gapped.0 > NONE && skipped > consecutive {
+                    (skipped, gapped.1)
+                } else {
+                    (consecutive, j - 1)
+                };
+                if best > NONE / 2 {
+                    scores[row + j] = best + worth[j];
+                    from[row + j] = k;
+                }
+            }
+        }
+        let last_row = (m - 1) * n;
+        let last = (0..n)
+            .filter(|&j| scores[last_row + j] > NONE)
+            .max_by_key(|&j| scores[last_row + j])?;
+        let mut matched = vec![last];
+        for i in (1..m).rev() {
+            matched.push(from[i * n + *matched.last().unwrap()]);
+        }
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for &j in matched.iter().rev() {
+            let (start, c) = chars[j];
+            match ranges.last_mut() {
+                Some(range) if range.end == start => range.end += c.len_utf8(),
+                _ => ranges.push(start..start + c.len_utf8()),
+            }
+        }
+        Some(Match { score: scores[last_row + last], ranges })
+    }
+}
+
+/// The best way for the characters of `pattern` to appear in order in `path`, if they do.
+/// Case is ignored unless `pattern` has capitals.
+pub fn score(pattern: &str, path: &str) -> Option<Match> {
+    Matcher::new(pattern).score(path)
+}
+
+/// the best matches of a pattern among paths given a few at a time
+struct Ranker {
+    pattern: String,
+    matcher: Matcher,
+    limit: usize,
+    /// best first, shorter paths first among equals, as (score, length, index, match)
+    best: Vec<(Reverse<i64>, usize, usize, Match)>,
+}
+
+impl Ranker {
+    fn new(pattern: &str, limit: usize) -> Self {
+        Self { pattern: pattern.to_owned(), matcher: Matcher::new(pattern), limit, best: vec![] }
+    }
+
+    /// ranks `paths[range]` with those ranked before
+    fn add(&mut self, paths: &[String], range: Range<usize>) {
+        for i in range {
+            if let Some(m) = self.matcher.score(&paths[i]) {
+                self.best.push((Reverse(m.score), paths[i].len(), i, m));
+            }
+        }
+        self.best.sort_by_key(|&(score, len, i, _)| (score, len, i));
+        self.best.truncate(self.limit);
+    }
+
+    /// ranks the paths from `start` on, or all of them again by the newest of `patterns` if
+    /// one came in meanwhile
+    fn catch_up(&mut self, patterns: &Receiver<String>, paths: &[String], mut start: usize) {
+        loop {
+            if let Some(pattern) = patterns.try_iter().last() {
+                *self = Self::new(&pattern, self.limit);
+                start = 0;
+            }
+            let end = paths.len().min(start + CHUNK);
+            self.add(paths, start..end);
+            if end == paths.len() {
+                return;
+            }
+            start = end;
+        }
+    }
+
+    fn ranked(&self, paths: &[String], done: bool) -> Ranked {
+        let matches = self.best.iter().map(|(_, _, i, m)| (paths[*i].clone(), m.ranges.clone()));
+        Ranked {
+            pattern: self.pattern.clone(),
+            total: paths.len(),
+            done,
+            matches: matches.collect(),
+        }
+    }
+}
+
+/// The indices of the `paths` matching `pattern` and how, best first, shorter paths first
+/// among equals. At most `limit`.
+pub fn rank(pattern: &str, paths: &[String], limit: usize) -> Vec<(usize, Match)> {
+    let mut ranker = Ranker::new(pattern, limit);
+    ranker.add(paths, 0..paths.len());
+    ranker.best.into_iter().map(|(_, _, i, m)| (i, m)).collect()
+}
+
+/// the best matches of a finder's pattern among the paths its walk found so far
+#[derive(Clone, Debug, Default)]
+pub struct Ranked {
+    pub pattern: String,
+    /// paths found so far
+    pub total: usize,
+    /// whether all the paths are found, or as many as are kept
+    pub done: bool,
+    /// best first, from the root, with the bytes of them matched
+    pub matches: Vec<(String, Vec<Range<usize>>)>,
+}
+
+/// Walks `root` for the paths under it and ranks them by the newest of `patterns`, as they're
+/// found and then whenever the pattern changes, until `cancel` is set or `patterns` closes.
+fn walk(
+    root: &Path,
+    hidden: bool,
+    patterns: Receiver<String>,
+    cancel: &AtomicBool,
+    on_ranked: impl Fn(Ranked),
+) {
+    let query = Query { pattern: Pattern::Fuzzy(Matcher::default()), hidden, ignored: false };
+    let mut paths: Vec<String> = vec![];
+    let mut ranker = Ranker::new("", MAX_SHOWN);
+    // set once cancelled or there are enough paths
+    let stop = AtomicBool::new(false);
+    find::find(root, &query, &stop, |found| {
+        let start = paths.len();
+        let found = found.into_iter().take(MAX_PATHS - start);
+        paths.extend(found.map(|f| f.path.to_string_lossy().into_owned()));
+        if paths.len() >= MAX_PATHS || cancel.load(Ordering::Relaxed) {
+            stop.store(true, Ordering::Relaxed);
+        }
+        ranker.catch_up(&patterns, &paths, start);
+        on_ranked(ranker.ranked(&paths, false));
+    });
+    let mut start = paths.len();
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        ranker.catch_up(&patterns, &paths, start);
+        on_ranked(ranker.ranked(&paths, true));
+        match patterns.recv() {
+            Ok(pattern) => ranker = Ranker::new(&pattern, MAX_SHOWN),
+            Err(_) => return,
+        }
+        start = 0;
+    }
+}
+
+/// The paths under a directory, walked and ranked by what's typed on another thread. The
+/// walk stops when it's dropped.
+#[derive(Debug)]
+pub struct Finder {
+    /// tells the rankings of a walk from another's
+    pub id: u64,
+    pub root: PathBuf,
+    /// as typed
+    pattern: String,
+    /// the last the walk sent
+    ranked: Ranked,
+    patterns: Sender<String>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Finder {
+    /// Starts walking `root`, looking into hidden entries if `hidden`. `on_ranked` is called
+    /// from the walk's thread as paths are found and the pattern changes.
+    pub fn new(
+        id: u64,
+        root: PathBuf,
+        hidden: bool,
+        on_ranked: impl Fn(Ranked) + Send + 'static,
+    ) -> Self {
+        let (patterns, rx) = channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (walked, stop) = (root.clone(), cancel.clone());
+        thread::spawn(move || walk(&walked, hidden, rx, &stop, on_ranked));
+        let (pattern, ranked) = (String::new(), Ranked::default());
+        Self { id, root, pattern, ranked, patterns, cancel }
+    }
+
+    /// ranks the paths by `pattern` from now on, whether it changed
+    pub fn set_pattern(&mut self, pattern: &str) -> bool {
+        if self.pattern == pattern {
+            return false;
+        }
+        self.pattern = pattern.to_owned();
+        // the walk only stops when `self` is dropped
+        let _ = self.patterns.send(self.pattern.clone());
+        true
+    }
+
+    /// keeps the ranking the walk sent, whether it's by the same pattern as the one before
+    pub fn set_ranked(&mut self, ranked: Ranked) -> bool {
+        let same = ranked.pattern == self.ranked.pattern;
+        self.ranked = ranked;
+        same
+    }
+
+    /// the best matches, the one at `cursor` selected
+    pub fn popup(&self, cursor: usize) -> Popup {
+        let ranked = &self.ranked;
+        let searching = match (ranked.done, ranked.total >= MAX_PATHS) {
+            (false, _) => ", searching",
+            (true, true) => ", no more listed",
+            (true, false) => "",
+        };
+        let title = format!("{}: {} paths{}", self.root.display(), ranked.total, searching);
+        let lines = ranked.matches.iter().map(|(path, _)| path.clone()).collect();
+        let mut popup = Popup::new(PopupKind::Finder, title, lines);
+        let paths = ranked.matches.iter().map(|(path, _)| self.root.join(path));
+        popup.paths = Arc::new(paths.collect());
+        popup.matched = Arc::new(ranked.matches.iter().map(|(_, m)| m.clone()).collect());
+        popup.move_cursor(cursor as isize);
+        popup
+    }
+}
+
+impl Drop for Finder {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test_fuzzy {
+    use super::*;
+    use crate::TestDir;
+    use std::fs;
+
+    fn ranked(pattern: &str, paths: &[&str]) -> Vec<String> {
+        let paths: Vec<String> = paths.iter().map(|&p| p.to_owned()).collect();
+        rank(pattern, &paths, 10).into_iter().map(|(i, _)| paths[i].clone()).collect()
+    }
+
+    #[test]
+    fn test_score() {
+        assert_eq!(score("xyz", "src/main.rs"), None);
+        assert!(score("sm", "SRC/M").is_some());
+        assert_eq!(score("SM", "src/main.rs"), None);
+        let found = score("mrs", "src/main.rs").unwrap();
+        assert_eq!(found.ranges, [4..5, 9..11]);
+        let found = score("main", "src/main.rs").unwrap();
+        assert_eq!(found.ranges, [Range { start: 4, end: 8 }]);
+        assert_eq!(score("", "a").unwrap().score, 0);
+        assert_eq!(score("é", "café").unwrap().ranges, [Range { start: 3, end: 5 }]);
+        let mut matcher = Matcher::new("mrs");
+        assert_eq!(matcher.score("src/main.rs"), score("mrs", "src/main.rs"));
+        assert_eq!(matcher.score("x"), None);
+        assert_eq!(matcher.score("mrs"), score("mrs", "mrs"));
+        assert!(matcher.is_match("MRS") && !Matcher::new("M").is_match("m"));
+    }
+
+    #[test]
+    fn test_rank() {
+        // consecutive characters
+        assert_eq!(ranked("abc", &["axbxc", "abc"]), ["abc", "axbxc"]);
+        // word boundaries
+        assert_eq!(ranked("fb", &["xfxb", "foo_bar"]), ["foo_bar", "xfxb"]);
+        assert_eq!(ranked("fb", &["afxb", "fooBar"]), ["fooBar", "afxb"]);
+        // the file's name rather than its directories
+        let paths = ["lib/src/main.rs", "src/lib.rs"];
+        assert_eq!(ranked("lib", &paths), ["src/lib.rs", "lib/src/main.rs"]);
+        // shorter first among equals
+        assert_eq!(ranked("a", &["a/b/c", "a/b", "x"]), ["a/b", "a/b/c"]);
+    }
+
+    #[test]
+    fn test_finder() {
+        let dir = TestDir::new("finder");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.rs"), "").unwrap();
+        fs::write(dir.join("README"), "").unwrap();
+        let (tx, rx) = channel();
+        let mut finder = Finder::new(1, dir.to_path_buf(), false, move |ranked| {
+            let _ = tx.send(ranked);
+        });
+        let title = |paths: &str| format!("{}: {}", dir.display(), paths);
+        assert_eq!(finder.popup(0).title, title("0 paths, searching"));
+        let done = |pattern: &str| loop {
+            let ranked = rx.recv().unwrap();
+            if ranked.done && ranked.pattern == pattern {
+                return ranked;
+            }
+        };
+        assert!(finder.set_ranked(done("")));
+        assert!(finder.set_pattern("rs"));
+        assert!(!finder.set_pattern("rs"));
+        assert!(!finder.set_ranked(done("rs")));
+        let popup = finder.popup(5);
+        assert_eq!(*popup.lines, ["src/main.rs"]);
+        assert_eq!(popup.selected_path(), Some(dir.join("src/main.rs").as_path()));
+        assert_eq!(*popup.matched, [vec![Range { start: 9, end: 11 }]]);
+        assert_eq!(popup.title, title("3 paths"));
+    }
+}
@@ -0,0 +1,249 @@
+//! Status of the entries of a git repository, and of its branch, for the listings and the
+//! footer.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use git2::{Branch, ErrorCode, Repository, StatusOptions};
+
+/// what git says of an entry, directories have what their entries have
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Status {
+    /// changes in the index
+    pub staged: bool,
+    /// changes not in the index
+    pub modified: bool,
+    pub untracked: bool,
+    pub ignored: bool,
+    pub conflicted: bool,
+}
+
+impl Status {
+    fn from_git(status: git2::Status) -> Self {
+        use git2::Status as S;
+        Self {
+            staged: status.intersects(
+                S::INDEX_NEW
+                    | S::INDEX_MODIFIED
+                    | S::INDEX_DELETED
+                    | S::INDEX_RENAMED
+                    | S::INDEX_TYPECHANGE,
+            ),
+            modified: status.intersects(
+                S::WT_MODIFIED | S::WT_DELETED | S::WT_RENAMED | S::WT_TYPECHANGE,
+            ),
+            untracked: status.contains(S::WT_NEW),
+            ignored: status.contains(S::IGNORED),
+            conflicted: status.contains(S::CONFLICTED),
+        }
+    }
+
+    /// adds what an entry of a directory has, an ignored entry doesn't make it ignored
+    fn merge(&mut self, other: Status) {
+        self.staged |= other.staged;
+        self.modified |= other.modified;
+        self.untracked |= other.untracked;
+        self.conflicted |= other.conflicted;
+    }
+
+    /// `U` if conflicted, or `+` staged, `M` modified and `?` untracked, `!` if ignored
+    pub fn mark(self) -> String {
+        if self.conflicted {
+            return "U".into();
+        }
+        let marks = [(self.staged, '+'), (self.modified, 'M'), (self.untracked, '?')];
+        let mark: String = marks.iter().filter(|(set, _)| *set).map(|(_, c)| c).collect();
+        if mark.is_empty() && self.ignored {
+            "!".into()
+        } else {
+            mark
+        }
+    }
+}
+
+/// the status of a repository's working tree and branch
+#[derive(Debug)]
+pub struct Repo {
+    /// of the working tree
+    pub root: PathBuf,
+    /// the branch checked out, or the commit if it's detached
+    pub head: String,
+    /// commits ahead of and behind the upstream, if the branch has one
+    pub upstream: Option<(usize, usize)>,
+    /// by path from `root`: files, and directories untracked or ignored as a whole
+    entries: HashMap<PathBuf, Status>,
+    /// what the entries of the directories with some have, by path from `root`
+    dirs: HashMap<PathBuf, Status>,
+}
+
+impl Repo {
+    /// the repository `dir` is in, if any and it has a working tree
+    pub fn open(dir: &Path) -> Result<Option<Self>, git2::Error> {
+        let repo = match Repository::discover(dir) {
+            Ok(repo) => repo,
+            Err(e) if e.code() == ErrorCode::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let root = match repo.workdir() {
+            Some(root) => root.to_owned(),
+            None => return Ok(None),
+        };
+        let (head, upstream) = head(&repo)?;
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .include_ignored(true)
+            .recurse_untracked_dirs(false)
+            .recurse_ignored_dirs(false);
+        let mut entries = HashMap::new();
+        let mut dirs: HashMap<PathBuf, Status> = HashMap::new();
+        for entry in repo.statuses(Some(&mut options))?.iter() {
+            // untracked and ignored directories end with a `/`
+            let path = match entry.path() {
+                Some(path) => PathBuf::from(path.trim_end_matches('/')),
+                None => continue,
+            };
+            let status = Status::from_git(entry.status());
+            for dir in path.ancestors().skip(1) {
+                dirs.entry(dir.to_owned()).or_default().merge(status);
+            }
+            entries.insert(path, status);
+        }
+        Ok(Some(Self { root, head, upstream, entries, dirs }))
+    }
+
+    /// of the entry at `path`, nothing if it's not in the working tree or unchanged
+    pub fn status(&self, path: &Path) -> Status {
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) => relative,
+            Err(_) => return Status::default(),
+        };
+        // in a directory untracked or ignored as a whole
+        let whole = relative.ancestors().skip(1).filter_map(|dir| self.entries.get(dir));
+        if let Some(&status) = whole.last() {
+            return status;
+        }
+        let mut status = self.entries.get(relative).copied().unwrap_or_default();
+        if let Some(&dir) = self.dirs.get(relative) {
+            status.merge(dir);
+        }
+        status
+    }
+
+    /// `main ↑1 ↓2`, the branch and how many commits it's ahead and behind its upstream
+    pub fn describe(&self) -> String {
+        match self.upstream {
+            Some((ahead, behind)) => {
+                let mut description = self.head.clone();
+                if ahead > 0 {
+                    description += &format!(" \u{2191}{}", ahead);
+                }
+                if behind > 0 {
+                    description += &format!(" \u{2193}{}", behind);
+                }
+                description
+            }
+            None => self.head.clone(),
+        }
+    }
+}
+
+/// the branch or commit checked out, and how it compares to its upstream
+fn head(repo: &Repository) -> Result<(String, Option<(usize, usize)>), git2::Error> {
+    let head = match repo.head() {
+        Ok(head) => head,
+        // nothing committed yet, HEAD names a branch still to be born
+        Err(e) if e.code() == ErrorCode::UnbornBranch => {
+            let head = repo.find_reference("HEAD")?;
+            let target = head.symbolic_target().unwrap_or("HEAD");
+            return Ok((target.trim_start_matches("refs/heads/").to_owned(), None));
+        }
+        Err(e) => return Err(e),
+    };
+    let oid = head.target();
+    if !head.is_branch() {
+        let short = oid.map(|oid| oid.to_string()[..7].to_owned()).unwrap_or_default();
+        return Ok((format!("({})", short), None));
+    }
+    let name = head.shorthand().unwrap_or("HEAD").to_owned();
+    let upstream = Branch::wrap(head).upstream().ok().and_then(|up| up.get().target());
+    let upstream = match (oid, upstream) {
+        (Some(local), Some(upstream)) => Some(repo.graph_ahead_behind(local, upstream)?),
+        _ => None,
+    };
+    Ok((name, upstream))
+}
+
+#[cfg(test)]
+mod test_git {
+    use super::*;
+    use crate::TestDir;
+    use git2::{RepositoryInitOptions, Signature};
+    use std::fs;
+
+    fn commit(repo: &Repository, paths: &[&str]) {
+        let mut index = repo.index().unwrap();
+        for path in paths {
+            index.add_path(Path::new(path)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("yukari", "yukari@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, "commit", &tree, &parents).unwrap();
+    }
+
+    #[test]
+    fn test_status() {
+        let dir = TestDir::new("git");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let mut options = RepositoryInitOptions::new();
+        let repo = Repository::init_opts(&*dir, options.initial_head("main")).unwrap();
+        let opened = Repo::open(&dir).unwrap().unwrap();
+        assert_eq!(opened.describe(), "main");
+        for (file, text) in &[(".gitignore", "target/\n"), ("a", "a"), ("sub/b", "b")] {
+            fs::write(dir.join(file), text).unwrap();
+        }
+        commit(&repo, &[".gitignore", "a", "sub/b"]);
+        fs::write(dir.join("a"), "changed").unwrap();
+        fs::write(dir.join("sub/b"), "staged").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("sub/b")).unwrap();
+        index.write().unwrap();
+        fs::create_dir_all(dir.join("new/deep")).unwrap();
+        fs::write(dir.join("new/deep/c"), "c").unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        fs::write(dir.join("target/d"), "d").unwrap();
+
+        let opened = Repo::open(&dir.join("sub")).unwrap().unwrap();
+        let mark = |path: &str| opened.status(&dir.join(path)).mark();
+        assert_eq!(mark("a"), "M");
+        assert_eq!(mark("sub/b"), "+");
+        assert_eq!(mark("sub"), "+");
+        assert_eq!(mark("new"), "?");
+        assert_eq!(mark("new/deep/c"), "?");
+        assert_eq!(mark("target/d"), "!");
+        assert_eq!(mark(".gitignore"), "");
+        assert_eq!(opened.status(&dir).mark(), "+M?");
+        assert_eq!(opened.status(Path::new("/elsewhere")), Status::default());
+
+        // ahead of an upstream
+        let first = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("up", &first, false).unwrap();
+        commit(&repo, &["sub/b"]);
+        let mut main = repo.find_branch("main", git2::BranchType::Local).unwrap();
+        main.set_upstream(Some("up")).unwrap();
+        assert_eq!(Repo::open(&dir).unwrap().unwrap().describe(), "main \u{2191}1");
+    }
+
+    #[test]
+    fn test_mark() {
+        let status = Status { conflicted: true, staged: true, ..Status::default() };
+        assert_eq!(status.mark(), "U");
+        assert_eq!(Status::default().mark(), "");
+        let mut dir = Status::default();
+        dir.merge(Status { ignored: true, ..Status::default() });
+        assert_eq!(dir, Status::default());
+    }
+}
@@ -0,0 +1,225 @@
+//! `:grep`: the files under a directory whose contents match a pattern, read on several
+//! threads.
+
+use serde::Deserialize;
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ignore::{WalkBuilder, WalkState};
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::find::Found;
+use crate::preview::SNIFF_LEN;
+
+/// results are reported at least this often while searching
+const REPORT_EVERY: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GrepOptions {
+    /// bigger files aren't searched
+    pub max_size: u64,
+    /// lines shown around each match in the preview
+    pub context: usize,
+}
+
+impl Default for GrepOptions {
+    fn default() -> Self {
+        Self { max_size: 16 << 20, context: 2 }
+    }
+}
+
+/// what to look for and where not to
+#[derive(Clone, Debug)]
+pub struct Query {
+    pub regex: Regex,
+    /// look into hidden entries too
+    pub hidden: bool,
+    /// and into what ignore files ignore
+    pub ignored: bool,
+    pub max_size: u64,
+}
+
+/// `pattern` as a regex, or as is if `literal`, `^` and `$` match at the lines' ends
+pub fn regex(pattern: &str, literal: bool, ignore_case: bool) -> Result<Regex, String> {
+    let pattern = if literal { regex::escape(pattern) } else { pattern.to_owned() };
+    let mut regex = RegexBuilder::new(&pattern);
+    let regex = regex.case_insensitive(ignore_case).multi_line(true).build();
+    regex.map_err(|e| e.to_string())
+}
+
+/// the 1-based numbers of the lines of `path` matching `regex`, none for binary files and the
+/// ones bigger than `max_size`
+fn matching_lines(path: &Path, regex: &Regex, max_size: u64) -> io::Result<Vec<usize>> {
+    let mut file = File::open(path)?;
+    if file.metadata()?.len() > max_size {
+        return Ok(vec![]);
+    }
+    let mut contents = vec![];
+    file.read_to_end(&mut contents)?;
+    if contents.iter().take(SNIFF_LEN).any(|&b| b == 0) || !regex.is_match(&contents) {
+        return Ok(vec![]);
+    }
+    let lines = contents.split(|&b| b == b'\n').enumerate();
+    Ok(lines.filter(|(_, line)| regex.is_match(line)).map(|(i, _)| i + 1).collect())
+}
+
+/// Reads the files under `root` for the lines matching `query`, without following symlinks,
+/// and passes the files with matches to `found` a few at a time. Returns whether it got to
+/// the end, rather than being cancelled.
+pub fn grep(
+    root: &Path,
+    query: &Query,
+    cancel: &AtomicBool,
+    mut found: impl FnMut(Vec<Found>),
+) -> bool {
+    // the walk owns what it reads, `cancel` is passed on to it as `stop`
+    let stop = Arc::new(AtomicBool::new(false));
+    let (root_owned, query_owned, walk_stop) = (root.to_owned(), query.clone(), stop.clone());
+    let (tx, rx) = channel();
+    let walker = thread::spawn(move || {
+        let (root, query, stop) = (&root_owned, &query_owned, &walk_stop);
+        let respect = !query.ignored;
+        let walk = WalkBuilder::new(root)
+            .hidden(!query.hidden)
+            .parents(respect)
+            .ignore(respect)
+            .git_ignore(respect)
+            .git_global(respect)
+            .git_exclude(respect)
+            .require_git(false)
+            .build_parallel();
+        walk.run(|| {
+            let tx = tx.clone();
+            Box::new(move |entry| {
+                if stop.load(Ordering::Relaxed) {
+                    return WalkState::Quit;
+                }
+                let entry = match entry {
+                    Ok(entry) if matches!(entry.file_type(), Some(t) if t.is_file()) => entry,
+                    _ => return WalkState::Continue,
+                };
+                let path = entry.path();
+                let lines = matching_lines(path, &query.regex, query.max_size);
+                match (lines, path.strip_prefix(root)) {
+                    (Ok(lines), Ok(path)) if !lines.is_empty() => {
+                        let _ = tx.send(Found { path: path.to_owned(), lines });
+                    }
+                    _ => (),
+                }
+                WalkState::Continue
+            })
+        });
+    });
+    let mut batch = vec![];
+    let mut reported = Instant::now();
+    loop {
+        match rx.recv_timeout(REPORT_EVERY) {
+            Ok(file) => batch.push(file),
+            Err(RecvTimeoutError::Timeout) => (),
+            // the walk is over and its senders dropped
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if cancel.load(Ordering::Relaxed) {
+            stop.store(true, Ordering::Relaxed);
+        }
+        if !batch.is_empty() && reported.elapsed() >= REPORT_EVERY {
+            found(std::mem::take(&mut batch));
+            reported = Instant::now();
+        }
+    }
+    let _ = walker.join();
+    if !batch.is_empty() {
+        found(batch);
+    }
+    !cancel.load(Ordering::Relaxed)
+}
+
+/// The matching `lines` of `path` and `context` lines around them, `12:match` and
+/// `11-around` like `grep -n`, with `--` between the groups. At most `max_lines`.
+pub fn context(path: &Path, lines: &[usize], context: usize, max_lines: usize) -> Vec<String> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) => return vec![format!("({})", e)],
+    };
+    let text: Vec<&[u8]> = contents.split(|&b| b == b'\n').collect();
+    let mut shown = vec![];
+    let mut last = 0;
+    for &line in lines {
+        let first = line.saturating_sub(context).max(last + 1);
+        if last > 0 && first > last + 1 {
+            shown.push("--".to_owned());
+        }
+        for n in first..=(line + context).min(text.len()) {
+            let separator = if lines.binary_search(&n).is_ok() { ':' } else { '-' };
+            let line = String::from_utf8_lossy(text[n - 1]);
+            let line = line.trim_end_matches('\r').replace('\t', "    ");
+            shown.push(format!("{}{}{}", n, separator, line));
+            last = n;
+        }
+        if shown.len() >= max_lines {
+            shown.truncate(max_lines);
+            break;
+        }
+    }
+    shown
+}
+
+#[cfg(test)]
+mod test_grep {
+    use super::*;
+    use crate::TestDir;
+
+    #[test]
+    fn test_grep() {
+        let dir = TestDir::new("grep");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), "one\nTwo\nthree\n").unwrap();
+        fs::write(dir.join("sub/b.txt"), "two (2)\n").unwrap();
+        fs::write(dir.join("binary"), b"two\0").unwrap();
+        fs::write(dir.join("big"), "two\n".repeat(10)).unwrap();
+        let mut query = Query {
+            regex: regex("two (2)", true, true).unwrap(),
+            hidden: false,
+            ignored: false,
+            max_size: 20,
+        };
+        let search = |query: &Query| {
+            let mut found = vec![];
+            assert!(grep(&dir, query, &AtomicBool::new(false), |f| found.extend(f)));
+            found.sort_by(|a: &Found, b: &Found| a.path.cmp(&b.path));
+            found
+        };
+        let found = search(&query);
+        assert_eq!(found, [Found { path: "sub/b.txt".into(), lines: vec![1] }]);
+        query.regex = regex("^t", false, true).unwrap();
+        let found = search(&query);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0], Found { path: "a.txt".into(), lines: vec![2, 3] });
+        assert!(regex("(", false, false).is_err());
+    }
+
+    #[test]
+    fn test_context() {
+        let dir = TestDir::new("context");
+        let file = dir.join("lines");
+        let text: Vec<String> = (1..=20).map(|n| format!("line {}", n)).collect();
+        fs::write(&file, text.join("\n")).unwrap();
+        let shown = context(&file, &[2, 4, 10, 20], 1, 100);
+        assert_eq!(
+            shown,
+            [
+                "1-line 1", "2:line 2", "3-line 3", "4:line 4", "5-line 5", "--", "9-line 9",
+                "10:line 10", "11-line 11", "--", "19-line 19", "20:line 20",
+            ]
+        );
+        assert_eq!(context(&file, &[2, 4, 10, 20], 1, 3).len(), 3);
+    }
+}
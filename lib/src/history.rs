@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// older entries are dropped past this
+const MAX_LEN: usize = 256;
+
+/// Back/forward stacks of visited directories, and the entry that was selected
+/// in each of them so coming back lands on the same one.
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    back: Vec<PathBuf>,
+    forward: Vec<PathBuf>,
+    cursors: HashMap<PathBuf, String>,
+}
+
+impl History {
+    /// `from` is being left for a new directory, this forgets the forward stack
+    pub fn visit(&mut self, from: PathBuf) {
+        if self.back.last() != Some(&from) {
+            self.back.push(from);
+        }
+        if self.back.len() > MAX_LEN {
+            self.back.remove(0);
+        }
+        self.forward.clear();
+    }
+
+    pub fn back(&mut self, current: &Path) -> Option<PathBuf> {
+        let to = self.back.pop()?;
+        self.forward.push(current.to_owned());
+        Some(to)
+    }
+
+    pub fn forward(&mut self, current: &Path) -> Option<PathBuf> {
+        let to = self.forward.pop()?;
+        self.back.push(current.to_owned());
+        Some(to)
+    }
+
+    /// the jump list, oldest first, and the index of `current` in it
+    pub fn list(&self, current: &Path) -> (Vec<PathBuf>, usize) {
+        let mut list = self.back.clone();
+        list.push(current.to_owned());
+        list.extend(self.forward.iter().rev().cloned());
+        (list, self.back.len())
+    }
+
+    /// moves to `index` in [`History::list`], as if going back or forward repeatedly
+    pub fn jump(&mut self, current: &Path, index: usize) -> Option<PathBuf> {
+        let pos = self.back.len();
+        let mut at = current.to_owned();
+        if index < pos {
+            for _ in index..pos {
+                at = self.back(&at)?;
+            }
+        } else {
+            for _ in pos..index {
+                at = self.forward(&at)?;
+            }
+        }
+        Some(at)
+    }
+
+    pub fn remember(&mut self, dir: &Path, entry: &str) {
+        self.cursors.insert(dir.to_owned(), entry.to_owned());
+    }
+
+    /// name of the entry last selected in `dir`
+    pub fn cursor(&self, dir: &Path) -> Option<&str> {
+        self.cursors.get(dir).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test_history {
+    use super::History;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_back_forward() {
+        let mut h = History::default();
+        h.visit("/a".into());
+        h.visit("/b".into());
+        assert_eq!(h.back(Path::new("/c")), Some(PathBuf::from("/b")));
+        assert_eq!(h.back(Path::new("/b")), Some(PathBuf::from("/a")));
+        assert_eq!(h.back(Path::new("/a")), None);
+        assert_eq!(h.forward(Path::new("/a")), Some(PathBuf::from("/b")));
+        h.visit("/b".into());
+        assert_eq!(h.forward(Path::new("/d")), None);
+        assert_eq!(h.list(Path::new("/d")).0, vec![PathBuf::from("/a"), "/b".into(), "/d".into()]);
+    }
+
+    #[test]
+    fn test_jump() {
+        let mut h = History::default();
+        h.visit("/a".into());
+        h.visit("/b".into());
+        h.visit("/c".into());
+        let (list, pos) = h.list(Path::new("/d"));
+        assert_eq!(pos, 3);
+        assert_eq!(h.jump(Path::new("/d"), 0), Some(PathBuf::from("/a")));
+        assert_eq!(h.list(Path::new("/a")), (list.clone(), 0));
+        assert_eq!(h.jump(Path::new("/a"), 2), Some(PathBuf::from("/c")));
+        assert_eq!(h.list(Path::new("/c")), (list, 2));
+    }
+}
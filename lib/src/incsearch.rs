@@ -0,0 +1,75 @@
+//! `/`: searching the names listed as the pattern is typed, `n` and `N` go through the
+//! matches.
+
+use druid::Data;
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use regex::{Regex, RegexBuilder};
+
+/// what `/` looks for, ignoring case unless the pattern has capitals
+#[derive(Clone, Debug, Data)]
+pub struct IncSearch {
+    pub pattern: String,
+    regex: Arc<Regex>,
+}
+
+impl IncSearch {
+    /// a substring, or a regex if `regex`
+    pub fn new(pattern: &str, regex: bool) -> Result<Self, String> {
+        let escaped = if regex { pattern.to_owned() } else { regex::escape(pattern) };
+        let ignore_case = !pattern.chars().any(char::is_uppercase);
+        let built = RegexBuilder::new(&escaped).case_insensitive(ignore_case).build();
+        let built = built.map_err(|e| e.to_string())?;
+        Ok(Self { pattern: pattern.to_owned(), regex: Arc::new(built) })
+    }
+
+    /// where it first matches in `name`, in bytes
+    pub fn find(&self, name: &str) -> Option<Range<usize>> {
+        self.regex.find(name).map(|m| m.range())
+    }
+
+    /// The index of the first of `names` matching from `from`, or before it if `back`, round
+    /// the end. `from` itself is skipped unless `here`.
+    pub fn next(&self, names: &[&str], from: usize, back: bool, here: bool) -> Option<usize> {
+        let len = names.len();
+        let skip = if here { 0 } else { 1 };
+        let offsets = (skip..len + skip).map(|i| if back { len + from - i } else { from + i });
+        offsets.map(|i| i % len).find(|&i| self.regex.is_match(names[i]))
+    }
+}
+
+#[cfg(test)]
+mod test_incsearch {
+    use super::*;
+
+    #[test]
+    fn test_smart_case() {
+        let search = IncSearch::new("rs", false).unwrap();
+        assert_eq!(search.find("main.RS"), Some(5..7));
+        let search = IncSearch::new("RS", false).unwrap();
+        assert_eq!(search.find("main.rs"), None);
+        let search = IncSearch::new("a.n", false).unwrap();
+        assert_eq!(search.find("main"), None);
+        let search = IncSearch::new("a.n", true).unwrap();
+        assert_eq!(search.find("main"), Some(1..4));
+        assert!(IncSearch::new("(", true).is_err());
+    }
+
+    #[test]
+    fn test_next() {
+        let names = ["a.rs", "b.txt", "c.rs", "d"];
+        let search = IncSearch::new(".rs", false).unwrap();
+        let next = |from, back, here| search.next(&names, from, back, here);
+        assert_eq!(next(0, false, true), Some(0));
+        assert_eq!(next(0, false, false), Some(2));
+        assert_eq!(next(2, false, false), Some(0));
+        assert_eq!(next(1, true, false), Some(0));
+        assert_eq!(next(0, true, false), Some(2));
+        assert_eq!(next(3, true, true), Some(2));
+        let none = IncSearch::new("zzz", false).unwrap();
+        assert_eq!(none.next(&names, 0, false, true), None);
+        assert_eq!(none.next(&[], 0, false, true), None);
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread;
+
+pub type JobId = u64;
+
+#[derive(Clone, Debug)]
+pub enum JobEvent {
+    Started { id: JobId, name: String },
+    /// `total == 0` when the amount of work is unknown
+    Progress { id: JobId, done: u64, total: u64 },
+    /// `Ok` carries a short summary to show to the user
+    Finished { id: JobId, result: Result<String, String> },
+}
+
+type Sink = Arc<dyn Fn(JobEvent) + Send + Sync>;
+type Task = Box<dyn FnOnce(&mut Progress) -> Result<String, String> + Send>;
+
+/// handed to a running job to report how far along it is
+pub struct Progress {
+    id: JobId,
+    last: Option<(u64, u64)>,
+    sink: Sink,
+}
+
+impl Progress {
+    /// only forwarded when the percentage changes, so jobs can call this as often as they like
+    pub fn report(&mut self, done: u64, total: u64) {
+        let step = match (done * 100).checked_div(total) {
+            Some(percent) => (percent, total),
+            None => (done, 0),
+        };
+        if self.last != Some(step) {
+            self.last = Some(step);
+            (self.sink)(JobEvent::Progress { id: self.id, done, total });
+        }
+    }
+}
+
+/// jobs are run one after the other on a single worker thread
+pub struct Jobs {
+    next: JobId,
+    tx: Sender<(JobId, String, Task)>,
+}
+
+impl Jobs {
+    pub fn new(sink: impl Fn(JobEvent) + Send + Sync + 'static) -> Self {
+        let sink: Sink = Arc::new(sink);
+        let (tx, rx) = channel::<(JobId, String, Task)>();
+        thread::spawn(move || {
+            for (id, name, task) in rx {
+                sink(JobEvent::Started { id, name });
+                let mut progress = Progress { id, last: None, sink: sink.clone() };
+                let result = task(&mut progress);
+                sink(JobEvent::Finished { id, result });
+            }
+        });
+        Self { next: 0, tx }
+    }
+
+    pub fn push(
+        &mut self,
+        name: impl Into<String>,
+        task: impl FnOnce(&mut Progress) -> Result<String, String> + Send + 'static,
+    ) -> JobId {
+        let id = self.next;
+        self.next += 1;
+        // the worker only stops when `self` is dropped
+        let _ = self.tx.send((id, name.into(), Box::new(task)));
+        id
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cmdline;
+use crate::xdg;
+
+/// Which map a key is looked up in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Normal,
+    /// a range of entries is being selected
+    Visual,
+    /// the command line is open
+    Command,
+    /// a popup is shown
+    Prompt,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 4] = [Mode::Normal, Mode::Visual, Mode::Command, Mode::Prompt];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Visual => "visual",
+            Mode::Command => "command",
+            Mode::Prompt => "prompt",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|m| m.name() == name)
+    }
+}
+
+/// In a key sequence, stands for any single character, which replaces `%k` in the command.
+pub const ANY: &str = "<Any>";
+
+/// counts are capped to this
+const MAX_COUNT: usize = 9999;
+
+/// normalized name of a special key, `None` if unknown
+fn special(name: &str) -> Option<&'static str> {
+    const NAMES: &[&str] = &[
+        "Any", "Enter", "Esc", "Tab", "BS", "Del", "Space", "Up", "Down", "Left", "Right",
+        "Home", "End", "PageUp", "PageDown", "Insert", "F1", "F2", "F3", "F4", "F5", "F6", "F7",
+        "F8", "F9", "F10", "F11", "F12",
+    ];
+    let name = match name.to_ascii_lowercase().as_str() {
+        "cr" | "return" => "Enter",
+        "escape" => "Esc",
+        "backspace" => "BS",
+        "delete" => "Del",
+        _ => name,
+    };
+    NAMES.iter().find(|n| n.eq_ignore_ascii_case(name)).copied()
+}
+
+/// one `<...>` key, like `<C-w>` or `<S-Tab>`, modifiers in the order C, A, S
+fn parse_special(inner: &str) -> Result<String, String> {
+    let (mut ctrl, mut alt, mut shift) = (false, false, false);
+    let mut rest = inner;
+    // a modifier needs something after the dash, so `<C-->` is ctrl and `-`
+    while rest.len() > 2 && rest.as_bytes()[1] == b'-' {
+        match rest.as_bytes()[0].to_ascii_uppercase() {
+            b'C' => ctrl = true,
+            b'A' | b'M' => alt = true,
+            b'S' => shift = true,
+            _ => break,
+        }
+        rest = &rest[2..];
+    }
+    let name = if rest.chars().count() == 1 {
+        rest.to_owned()
+    } else if rest.eq_ignore_ascii_case("lt") {
+        "<".to_owned()
+    } else {
+        special(rest).ok_or_else(|| format!("unknown key <{}>", inner))?.to_owned()
+    };
+    let mods = [(ctrl, "C-"), (alt, "A-"), (shift, "S-")];
+    let prefix: String = mods.iter().filter(|(on, _)| *on).map(|(_, m)| *m).collect();
+    if prefix.is_empty() && name.chars().count() == 1 {
+        // `<x>` is just `x`
+        return Ok(name);
+    }
+    Ok(format!("<{}{}>", prefix, name))
+}
+
+/// Splits `gg` or `<C-w>j` into keys, the inverse of concatenating them.
+pub fn parse_keys(s: &str) -> Result<Vec<String>, String> {
+    let mut keys = vec![];
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        let close = if c == '<' { rest[1..].find('>') } else { None };
+        match close {
+            // `<>` is `<` and `>`
+            Some(end) if end > 0 => {
+                keys.push(parse_special(&rest[1..end + 1])?);
+                rest = &rest[end + 2..];
+            }
+            _ => {
+                keys.push(c.to_string());
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if keys.is_empty() {
+        return Err("no keys".into());
+    }
+    Ok(keys)
+}
+
+/// whether `pattern` (which may be [`ANY`]) matches the typed `key`
+fn matches(pattern: &str, key: &str) -> bool {
+    pattern == key || (pattern == ANY && key.chars().count() == 1)
+}
+
+/// keys typed so far towards a binding
+#[derive(Clone, Debug, Default)]
+pub struct Pending {
+    pub count: Option<usize>,
+    pub keys: Vec<String>,
+}
+
+impl Pending {
+    pub fn is_empty(&self) -> bool {
+        self.count.is_none() && self.keys.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Feed {
+    /// a digit was added to the count
+    Count,
+    /// the keys so far are the start of a binding
+    Pending,
+    /// run `line` `times` times
+    Run { line: String, times: usize },
+    /// the keys typed (count excluded) are bound to nothing
+    Unbound(Vec<String>),
+}
+
+/// key sequences bound to command lines, per mode
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    maps: HashMap<Mode, BTreeMap<Vec<String>, String>>,
+}
+
+impl Default for Keymap {
+    /// the built-in bindings
+    fn default() -> Self {
+        let mut keymap = Self::empty();
+        let mut bind = |mode, keys: &str, command: &str| {
+            keymap.bind(mode, keys, command).expect("valid default binding")
+        };
+        for &mode in &[Mode::Normal, Mode::Visual, Mode::Prompt] {
+            bind(mode, "j", "down %c");
+            bind(mode, "<Down>", "down %c");
+            bind(mode, "k", "up %c");
+            bind(mode, "<Up>", "up %c");
+            bind(mode, "gg", "top");
+            bind(mode, "G", "bottom");
+        }
+        for &(keys, command) in &[
+            ("h", "leave"),
+            ("<Left>", "leave"),
+            ("<BS>", "leave"),
+            ("l", "enter"),
+            ("<Right>", "enter"),
+            ("<Enter>", "enter"),
+            ("H", "back"),
+            ("L", "forward"),
+            (":", "console"),
+            ("!", "console !"),
+            ("/", "search"),
+            ("n", "search_next"),
+            ("N", "search_prev"),
+            ("<C-p>", "finder"),
+            ("=", "permissions"),
+            ("<A-Enter>", "properties"),
+            ("<Space>", "mark"),
+            ("v", "visual"),
+            ("yy", "yank"),
+            ("dd", "cut"),
+            ("pp", "paste"),
+            ("q", "quit"),
+            ("m<Any>", "bookmark_set %k"),
+            ("'<Any>", "bookmark_go %k"),
+            ("um<Any>", "bookmark_delete %k"),
+            ("gn", "tabnew"),
+            ("gc", "tabclose"),
+            ("gt", "tabnext"),
+            ("gT", "tabprev"),
+            ("<Tab>", "focus_other"),
+            ("<F5>", "copy_other"),
+            ("<F6>", "move_other"),
+            ("zh", "set show_hidden toggle"),
+            ("zd", "set details toggle"),
+            ("dc", "du"),
+            ("dD", "delete"),
+            ("dT", "trash"),
+            ("du", "analyze"),
+        ] {
+            bind(Mode::Normal, keys, command);
+        }
+        for n in 1..=9 {
+            bind(Mode::Normal, &format!("<A-{}>", n), &format!("tab {}", n));
+        }
+        for &(keys, command) in &[("v", "visual"), ("<Esc>", "visual"), (":", "console")] {
+            bind(Mode::Visual, keys, command);
+        }
+        for &(keys, command) in &[
+            ("<Enter>", "accept"),
+            ("l", "enter"),
+            ("<Right>", "enter"),
+            ("h", "leave"),
+            ("<Left>", "leave"),
+            ("<BS>", "leave"),
+            ("d", "analyze_delete"),
+            ("<Esc>", "close"),
+            ("q", "close"),
+        ] {
+            bind(Mode::Prompt, keys, command);
+        }
+        for &(keys, op) in &[
+            ("<Enter>", "run"),
+            ("<Esc>", "cancel"),
+            ("<Tab>", "complete"),
+            ("<S-Tab>", "complete_back"),
+            ("<Up>", "older"),
+            ("<Down>", "newer"),
+            ("<Left>", "left"),
+            ("<Right>", "right"),
+            ("<Home>", "home"),
+            ("<C-a>", "home"),
+            ("<End>", "end"),
+            ("<C-e>", "end"),
+            ("<BS>", "backspace"),
+            ("<Del>", "delete"),
+            ("<C-w>", "kill_word"),
+            ("<C-u>", "kill_line"),
+        ] {
+            bind(Mode::Command, keys, &format!("cmdline {}", op));
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    pub fn empty() -> Self {
+        Self { maps: Mode::ALL.iter().map(|&m| (m, BTreeMap::new())).collect() }
+    }
+
+    /// `$XDG_CONFIG_HOME/yukari/keys.conf`
+    pub fn default_file() -> Option<PathBuf> {
+        xdg::config_dir().map(|d| d.join("keys.conf"))
+    }
+
+    /// [`Keymap::apply`] of `file` if it exists, errors are prefixed with its name
+    pub fn apply_file(&mut self, file: &Path) -> Vec<String> {
+        let errors = match fs::read_to_string(file) {
+            Ok(s) => self.apply(&s),
+            Err(_) => vec![],
+        };
+        errors.into_iter().map(|e| format!("{}:{}", file.display(), e)).collect()
+    }
+
+    /// Applies `map MODE KEYS COMMAND...` and `unmap MODE KEYS` lines, `#` starts a comment.
+    /// Returns the errors as `LINE: message`, the other lines still apply.
+    pub fn apply(&mut self, text: &str) -> Vec<String> {
+        let mut errors = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Err(e) = self.apply_line(line) {
+                errors.push(format!("{}: {}", i + 1, e));
+            }
+        }
+        errors
+    }
+
+    fn apply_line(&mut self, line: &str) -> Result<(), String> {
+        let args = cmdline::parse(line).map_err(|e| e.to_string())?;
+        let mode =
+            |name: &str| Mode::from_name(name).ok_or_else(|| format!("unknown mode {}", name));
+        match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            ["map", m, keys, command @ ..] if !command.is_empty() => {
+                let command: Vec<String> = command.iter().map(|a| cmdline::quote(a)).collect();
+                self.bind(mode(m)?, keys, &command.join(" "))
+            }
+            ["unmap", m, keys] => match self.unbind(mode(m)?, keys)? {
+                true => Ok(()),
+                false => Err(format!("{} is not mapped", keys)),
+            },
+            [directive, ..] if *directive == "map" || *directive == "unmap" => {
+                Err("usage: map MODE KEYS COMMAND, unmap MODE KEYS".into())
+            }
+            [directive, ..] => Err(format!("unknown directive {}", directive)),
+            [] => Ok(()),
+        }
+    }
+
+    /// Binds `keys` to `command`, replacing what was bound to it.
+    pub fn bind(&mut self, mode: Mode, keys: &str, command: &str) -> Result<(), String> {
+        let keys = parse_keys(keys)?;
+        self.maps.entry(mode).or_default().insert(keys, command.to_owned());
+        Ok(())
+    }
+
+    /// whether something was bound to `keys`
+    pub fn unbind(&mut self, mode: Mode, keys: &str) -> Result<bool, String> {
+        let keys = parse_keys(keys)?;
+        Ok(self.maps.entry(mode).or_default().remove(&keys).is_some())
+    }
+
+    /// what `keys` are bound to
+    pub fn lookup(&self, mode: Mode, keys: &str) -> Result<Option<&str>, String> {
+        let keys = parse_keys(keys)?;
+        Ok(self.maps.get(&mode).and_then(|m| m.get(&keys)).map(String::as_str))
+    }
+
+    /// `(keys, command)` for every binding of `mode`
+    pub fn bindings(&self, mode: Mode) -> impl Iterator<Item = (String, &str)> {
+        self.maps[&mode].iter().map(|(keys, command)| (keys.concat(), command.as_str()))
+    }
+
+    /// bindings starting with `keys`, with what is left to type
+    fn continuations<'a>(
+        &'a self,
+        mode: Mode,
+        keys: &'a [String],
+    ) -> impl Iterator<Item = (&'a [String], &'a str)> {
+        self.maps[&mode].iter().filter_map(move |(seq, command)| {
+            let prefix = seq.get(..keys.len())?;
+            if prefix.iter().zip(keys).all(|(p, k)| matches(p, k)) {
+                Some((&seq[keys.len()..], command.as_str()))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// what can be typed after `pending`, as `(keys, command)`
+    pub fn hints(&self, mode: Mode, pending: &Pending) -> Vec<(String, String)> {
+        self.continuations(mode, &pending.keys)
+            .map(|(rest, command)| (rest.concat(), command.to_owned()))
+            .collect()
+    }
+
+    /// Handles one more typed key, see [`Feed`]. `pending` is cleared once a binding is
+    /// complete or can't be anymore.
+    pub fn feed(&self, mode: Mode, pending: &mut Pending, key: &str) -> Feed {
+        let digit = key.parse::<usize>().ok().filter(|_| key.len() == 1);
+        if let Some(d) = digit {
+            // no counts while typing a command, and `0` only continues one
+            let counting = mode != Mode::Command && (d > 0 || pending.count.is_some());
+            let bound = self.continuations(mode, &[key.to_owned()]).next().is_some();
+            if counting && pending.keys.is_empty() && !bound {
+                let count = pending.count.unwrap_or(0).saturating_mul(10) + d;
+                pending.count = Some(count.min(MAX_COUNT));
+                return Feed::Count;
+            }
+        }
+        pending.keys.push(key.to_owned());
+        let mut complete = None;
+        let mut longer = false;
+        for (rest, command) in self.continuations(mode, &pending.keys) {
+            if rest.is_empty() {
+                complete = complete.or_else(|| Some(command.to_owned()));
+            } else {
+                longer = true;
+            }
+        }
+        // like vim without a timeout, a longer binding wins over a complete one
+        if longer {
+            return Feed::Pending;
+        }
+        let Pending { count, keys } = std::mem::take(pending);
+        let command = match complete {
+            Some(command) => command,
+            None => return Feed::Unbound(keys),
+        };
+        let any = self.maps[&mode]
+            .keys()
+            .find(|seq| {
+                seq.len() == keys.len() && seq.iter().zip(&keys).all(|(p, k)| matches(p, k))
+            })
+            .and_then(|seq| seq.iter().zip(&keys).find(|(p, _)| *p == ANY))
+            .map(|(_, k)| k.as_str());
+        let mut line = command;
+        if let Some(k) = any {
+            line = line.replace("%k", &cmdline::quote(k));
+        }
+        if line.contains("%c") {
+            line = line.replace("%c", &count.unwrap_or(1).to_string());
+            Feed::Run { line, times: 1 }
+        } else {
+            Feed::Run { line, times: count.unwrap_or(1) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_keymap {
+    use super::*;
+
+    fn feed_all(keymap: &Keymap, mode: Mode, keys: &str) -> Feed {
+        let mut pending = Pending::default();
+        let mut last = Feed::Pending;
+        for key in parse_keys(keys).unwrap() {
+            last = keymap.feed(mode, &mut pending, &key);
+        }
+        last
+    }
+
+    fn run(line: &str, times: usize) -> Feed {
+        Feed::Run { line: line.into(), times }
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let p = |s| parse_keys(s).unwrap();
+        assert_eq!(p("gg"), vec!["g", "g"]);
+        assert_eq!(p("<c-w>j"), vec!["<C-w>", "j"]);
+        assert_eq!(p("<s-C-tab>"), vec!["<C-S-Tab>"]);
+        assert_eq!(p("<cr><Esc><x><lt>"), vec!["<Enter>", "<Esc>", "x", "<"]);
+        assert_eq!(p("<>"), vec!["<", ">"]);
+        assert_eq!(p("<C-->"), vec!["<C-->"]);
+        assert!(parse_keys("<Nope>").is_err());
+        assert!(parse_keys("").is_err());
+    }
+
+    #[test]
+    fn test_chords_and_counts() {
+        let keymap = Keymap::default();
+        assert_eq!(feed_all(&keymap, Mode::Normal, "g"), Feed::Pending);
+        assert_eq!(feed_all(&keymap, Mode::Normal, "gg"), run("top", 1));
+        assert_eq!(feed_all(&keymap, Mode::Normal, "5j"), run("down 5", 1));
+        assert_eq!(feed_all(&keymap, Mode::Normal, "12k"), run("up 12", 1));
+        assert_eq!(feed_all(&keymap, Mode::Normal, "3gt"), run("tabnext", 3));
+        assert_eq!(feed_all(&keymap, Mode::Normal, "0"), Feed::Unbound(vec!["0".into()]));
+        let gx = Feed::Unbound(vec!["g".into(), "x".into()]);
+        assert_eq!(feed_all(&keymap, Mode::Normal, "gx"), gx);
+        let quoted = format!("bookmark_set {}", cmdline::quote("'"));
+        assert_eq!(feed_all(&keymap, Mode::Normal, "m'"), run(&quoted, 1));
+        assert_eq!(feed_all(&keymap, Mode::Command, "5"), Feed::Unbound(vec!["5".into()]));
+        assert_eq!(feed_all(&keymap, Mode::Command, "<C-u>"), run("cmdline kill_line", 1));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut keymap = Keymap::default();
+        let errors = keymap.apply(
+            "# comment\n\
+             map normal <C-d> down 'a b'\n\
+             unmap normal gg\n\
+             map bogus x y\n\
+             unmap normal zz\n",
+        );
+        assert_eq!(errors, vec!["4: unknown mode bogus", "5: zz is not mapped"]);
+        assert_eq!(feed_all(&keymap, Mode::Normal, "<C-d>"), run("down 'a b'", 1));
+        let gg = Feed::Unbound(vec!["g".into(), "g".into()]);
+        assert_eq!(feed_all(&keymap, Mode::Normal, "gg"), gg);
+        let hints = keymap.hints(Mode::Normal, &Pending { count: None, keys: vec!["g".into()] });
+        assert!(hints.contains(&("t".into(), "tabnext".into())));
+    }
+}
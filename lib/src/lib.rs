@@ -1,3 +1,79 @@
-
 pub mod state;
 pub use state::State;
+pub use tabs::Tabs;
+
+pub mod analyzer;
+pub mod archive;
+pub mod bookmarks;
+pub mod cmdline;
+pub mod compare;
+pub mod config;
+pub mod details;
+pub mod dupes;
+pub mod dirinfo;
+pub mod entry;
+pub mod fileops;
+pub mod find;
+pub mod fmt;
+pub mod fuzzy;
+pub mod git;
+pub mod frecency;
+pub mod grep;
+pub mod history;
+pub mod incsearch;
+pub mod job;
+pub mod keymap;
+pub mod opener;
+pub mod perms;
+pub mod popup;
+pub mod preview;
+pub mod props;
+pub mod ranger;
+pub mod shell;
+pub mod tabs;
+pub mod theme;
+pub mod watch;
+pub mod xattrs;
+pub mod xdg;
+
+/// A fresh directory for a test, removed once dropped, so a failed assertion doesn't leave
+/// it behind.
+#[cfg(test)]
+pub(crate) struct TestDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    /// `yukari-test-NAME-…` in the temporary directory, unique to this run
+    pub fn new(name: &str) -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        let unique = format!(
+            "yukari-test-{}-{}-{}-{}",
+            name,
+            std::process::id(),
+            nanos,
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let dir = std::env::temp_dir().join(unique);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestDir {
+    type Target = std::path::Path;
+    fn deref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
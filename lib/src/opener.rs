@@ -0,0 +1,117 @@
+use serde::Deserialize;
+
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::shell;
+
+/// the program files matching `pattern` are opened with
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Opener {
+    /// matched against the file name, `*` and `?` are wildcards
+    pub pattern: String,
+    /// run by `sh`, `%f` is the quoted path, appended if missing
+    pub command: String,
+}
+
+/// used when no opener matches
+pub const FALLBACK: &str = "xdg-open";
+
+/// whether `name` matches `pattern` as a whole, `*` matching any run of characters and `?` any
+/// single one
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // position after the last `*`, and where in `name` it is tried to resume
+    let (mut p, mut n) = (0, 0);
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // let the `*` swallow one more character
+                Some((after, from)) => {
+                    star = Some((after, from + 1));
+                    p = after;
+                    n = from + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// the command line to open `path` with, `FALLBACK` if no opener matches
+pub fn command_for(openers: &[Opener], path: &Path) -> OsString {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let command = openers
+        .iter()
+        .find(|o| glob_match(&o.pattern, &name))
+        .map_or(FALLBACK, |o| o.command.as_str());
+    let quoted = shell::quote(path.as_os_str());
+    if command.contains("%f") {
+        let parts: Vec<OsString> = command.split("%f").map(OsString::from).collect();
+        parts.join(quoted.as_os_str())
+    } else {
+        let mut line = OsString::from(command);
+        line.push(" ");
+        line.push(quoted);
+        line
+    }
+}
+
+/// Runs `command` with `sh` in the background, detached from the terminal's input.
+/// Its exit status is collected by a thread so no zombie is left.
+pub fn spawn(command: &OsStr, cwd: &Path) -> io::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_opener {
+    use super::*;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.pdf", "a.pdf"));
+        assert!(glob_match("*.pdf", ".pdf"));
+        assert!(!glob_match("*.pdf", "a.pdf.txt"));
+        assert!(glob_match("a?c*", "abcdef"));
+        assert!(glob_match("*a*b*", "xxaxxbxx"));
+        assert!(!glob_match("*a*b", "xxaxxbxx"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("?", ""));
+    }
+
+    #[test]
+    fn test_command_for() {
+        let openers = vec![
+            Opener { pattern: "*.pdf".into(), command: "zathura %f &".into() },
+            Opener { pattern: "*".into(), command: "less".into() },
+        ];
+        assert_eq!(command_for(&openers, Path::new("/a b.pdf")), "zathura '/a b.pdf' &");
+        assert_eq!(command_for(&openers, Path::new("/x")), "less /x");
+        assert_eq!(command_for(&[], Path::new("/x")), "xdg-open /x");
+        let path = Path::new(OsStr::from_bytes(b"/caf\xe9.pdf"));
+        assert_eq!(command_for(&openers, path).as_bytes(), b"zathura '/caf\xe9.pdf' &");
+    }
+}
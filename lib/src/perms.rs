@@ -0,0 +1,288 @@
+//! `:chmod` and `:chown`: the permissions and owners of entries, and of what's in them, and
+//! the permissions popup editing them.
+
+use std::ffi::CString;
+use std::fs::{self, Metadata, Permissions};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::details;
+use crate::fmt;
+
+/// the setuid, setgid, sticky and rwx bits
+const MODE_BITS: u32 = 0o7777;
+
+/// what `:chmod` does to the mode of an entry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModeChange {
+    /// `644`, the bits as they'll be
+    Octal(u32),
+    /// `u+x,go-w`
+    Symbolic(Vec<Clause>),
+}
+
+/// a clause of a symbolic mode: who it's for, `+`, `-` or `=`, and the permissions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Clause {
+    who: String,
+    op: char,
+    perms: String,
+}
+
+impl ModeChange {
+    /// `755` or `u+x,go-rw` like chmod, `+x` is for everyone whatever the umask
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid mode: {}", spec);
+        if !spec.is_empty() && spec.chars().all(|c| c.is_ascii_digit()) {
+            return match u32::from_str_radix(spec, 8) {
+                Ok(bits) if spec.len() <= 4 => Ok(ModeChange::Octal(bits)),
+                _ => Err(invalid()),
+            };
+        }
+        let mut clauses = vec![];
+        for clause in spec.split(',') {
+            let op_at = clause.find(|c| "+-=".contains(c)).ok_or_else(invalid)?;
+            let (who, rest) = clause.split_at(op_at);
+            let mut rest = rest.chars();
+            let op = rest.next().ok_or_else(invalid)?;
+            let perms: String = rest.collect();
+            let valid = |text: &str, allowed: &str| text.chars().all(|c| allowed.contains(c));
+            if !valid(who, "ugoa") || !valid(&perms, "rwxXst") {
+                return Err(invalid());
+            }
+            clauses.push(Clause { who: who.to_owned(), op, perms });
+        }
+        Ok(ModeChange::Symbolic(clauses))
+    }
+
+    /// the mode bits of an entry that had `mode`
+    pub fn apply(&self, mode: u32, is_dir: bool) -> u32 {
+        let clauses = match self {
+            ModeChange::Octal(bits) => return bits & MODE_BITS,
+            ModeChange::Symbolic(clauses) => clauses,
+        };
+        let mut mode = mode & MODE_BITS;
+        for clause in clauses {
+            let all = clause.who.is_empty() || clause.who.contains('a');
+            let who = |c| all || clause.who.contains(c);
+            // the rwx bits of the classes the clause is for, and their setuid, setgid or
+            // sticky bit
+            let classes = [('u', 0o700, 0o4000), ('g', 0o070, 0o2000), ('o', 0o007, 0o1000)];
+            let (rwx, special) = classes.iter().filter(|(c, _, _)| who(*c)).fold(
+                (0, 0),
+                |(rwx, special), (_, bits, bit)| (rwx | bits, special | bit),
+            );
+            let mut bits = 0;
+            for perm in clause.perms.chars() {
+                bits |= match perm {
+                    'r' => rwx & 0o444,
+                    'w' => rwx & 0o222,
+                    'x' => rwx & 0o111,
+                    // for directories, and files executable by someone already
+                    'X' if is_dir || mode & 0o111 != 0 => rwx & 0o111,
+                    's' => special & 0o6000,
+                    't' => special & 0o1000,
+                    _ => 0,
+                };
+            }
+            mode = match clause.op {
+                '+' => mode | bits,
+                '-' => mode & !bits,
+                _ => (mode & !(rwx | special)) | bits,
+            };
+        }
+        mode
+    }
+}
+
+/// `user`, `user:group` or `:group` as ids, names or numbers
+pub fn parse_owner(spec: &str) -> Result<(Option<u32>, Option<u32>), String> {
+    let (user, group) = match spec.find(':') {
+        Some(at) => (&spec[..at], Some(&spec[at + 1..])),
+        None => (spec, None),
+    };
+    let id = |name: &str, lookup: fn(&str) -> Option<u32>, what: &str| {
+        match name.parse().ok().or_else(|| lookup(name)) {
+            Some(id) => Ok(id),
+            None => Err(format!("no such {}: {}", what, name)),
+        }
+    };
+    let uid = match user {
+        "" => None,
+        user => Some(id(user, details::user_id, "user")?),
+    };
+    let gid = match group {
+        None | Some("") => None,
+        Some(group) => Some(id(group, details::group_id, "group")?),
+    };
+    if uid.is_none() && gid.is_none() {
+        return Err(format!("invalid owner: {}", spec));
+    }
+    Ok((uid, gid))
+}
+
+/// Calls `change` on each of `paths`, and on what's in them if `recursive`, without
+/// following symlinks. Returns how many were changed and an error for each that wasn't.
+pub(crate) fn change_all(
+    paths: &[PathBuf],
+    recursive: bool,
+    change: &mut dyn FnMut(&Path, &Metadata) -> io::Result<()>,
+) -> (usize, Vec<String>) {
+    let mut changed = 0;
+    let mut errors = vec![];
+    let mut pending: Vec<PathBuf> = paths.iter().rev().cloned().collect();
+    while let Some(path) = pending.pop() {
+        let result = fs::symlink_metadata(&path).and_then(|meta| {
+            change(&path, &meta)?;
+            // changed before its entries, which it may have made readable
+            if recursive && meta.is_dir() {
+                for entry in fs::read_dir(&path)? {
+                    pending.push(entry?.path());
+                }
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => changed += 1,
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    (changed, errors)
+}
+
+/// `:chmod`, symlinks are left as they are
+pub fn chmod(paths: &[PathBuf], mode: &ModeChange, recursive: bool) -> (usize, Vec<String>) {
+    change_all(paths, recursive, &mut |path, meta| {
+        if meta.file_type().is_symlink() {
+            return Ok(());
+        }
+        let bits = mode.apply(meta.mode(), meta.is_dir());
+        fs::set_permissions(path, Permissions::from_mode(bits))
+    })
+}
+
+/// `:chown`, of symlinks rather than what they point to
+pub fn chown(
+    paths: &[PathBuf],
+    uid: Option<u32>,
+    gid: Option<u32>,
+    recursive: bool,
+) -> (usize, Vec<String>) {
+    // -1 leaves it as it is
+    let (uid, gid) = (uid.unwrap_or(u32::MAX), gid.unwrap_or(u32::MAX));
+    change_all(paths, recursive, &mut |path, _| {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        match unsafe { libc::lchown(path.as_ptr(), uid, gid) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    })
+}
+
+/// The bits the lines of the permissions popup after the first stand for, and the chmod
+/// clause setting them. Then come the owner's and the group's lines.
+pub const BITS: &[(&str, u32, &str)] = &[
+    ("owner read", 0o400, "u+r"),
+    ("owner write", 0o200, "u+w"),
+    ("owner execute", 0o100, "u+x"),
+    ("group read", 0o040, "g+r"),
+    ("group write", 0o020, "g+w"),
+    ("group execute", 0o010, "g+x"),
+    ("others read", 0o004, "o+r"),
+    ("others write", 0o002, "o+w"),
+    ("others execute", 0o001, "o+x"),
+    ("setuid", 0o4000, "u+s"),
+    ("setgid", 0o2000, "g+s"),
+    ("sticky", 0o1000, "o+t"),
+];
+
+/// the popup's lines for an entry: its mode, the `BITS`, its owner and group
+pub fn lines(meta: &Metadata) -> Vec<String> {
+    let mode = meta.mode() & MODE_BITS;
+    let symbolic = fmt::permissions(meta.file_type(), mode);
+    let mut lines = vec![format!("mode    {} {:04o}", symbolic, mode)];
+    for &(name, bit, _) in BITS {
+        let set = if mode & bit != 0 { 'x' } else { ' ' };
+        lines.push(format!("[{}] {}", set, name));
+    }
+    lines.push(format!("owner   {}", details::user_name(meta.uid())));
+    lines.push(format!("group   {}", details::group_name(meta.gid())));
+    lines
+}
+
+/// The chmod clause toggling the bit of line `line` of the popup for an entry with `mode`,
+/// if it's one of the `BITS`' lines.
+pub fn toggle(line: usize, mode: u32) -> Option<String> {
+    let &(_, bit, clause) = BITS.get(line.checked_sub(1)?)?;
+    Some(if mode & bit != 0 { clause.replace('+', "-") } else { clause.to_owned() })
+}
+
+#[cfg(test)]
+mod test_perms {
+    use super::*;
+    use crate::TestDir;
+
+    fn apply(spec: &str, mode: u32, is_dir: bool) -> u32 {
+        ModeChange::parse(spec).unwrap().apply(mode, is_dir)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ModeChange::parse("644"), Ok(ModeChange::Octal(0o644)));
+        assert!(ModeChange::parse("8").is_err());
+        assert!(ModeChange::parse("77777").is_err());
+        assert!(ModeChange::parse("u+q").is_err());
+        assert!(ModeChange::parse("z+x").is_err());
+        assert!(ModeChange::parse("rwx").is_err());
+        assert!(ModeChange::parse("").is_err());
+        assert_eq!(parse_owner("0:0"), Ok((Some(0), Some(0))));
+        assert_eq!(parse_owner("root"), Ok((Some(0), None)));
+        assert_eq!(parse_owner(":0"), Ok((None, Some(0))));
+        assert!(parse_owner(":").is_err());
+        assert!(parse_owner("no such user").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(apply("4755", 0o644, false), 0o4755);
+        assert_eq!(apply("u+x", 0o644, false), 0o744);
+        assert_eq!(apply("+x", 0o644, false), 0o755);
+        assert_eq!(apply("go-w", 0o666, false), 0o644);
+        assert_eq!(apply("u=rw,go=r", 0o777, false), 0o644);
+        assert_eq!(apply("a+X", 0o644, false), 0o644);
+        assert_eq!(apply("a+X", 0o644, true), 0o755);
+        assert_eq!(apply("a+X", 0o744, false), 0o755);
+        assert_eq!(apply("ug+s,+t", 0o755, true), 0o7755);
+        assert_eq!(apply("u=", 0o4755, false), 0o055);
+        assert_eq!(apply("o+s", 0o755, false), 0o755);
+    }
+
+    #[test]
+    fn test_chmod() {
+        let dir = TestDir::new("chmod");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/f"), "").unwrap();
+        std::os::unix::fs::symlink("missing", dir.join("link")).unwrap();
+        let mode = |path: &str| fs::symlink_metadata(dir.join(path)).unwrap().mode() & 0o7777;
+        let change = ModeChange::parse("go-rwx").unwrap();
+        let paths = [dir.join("sub"), dir.join("link"), dir.join("missing")];
+        let (changed, errors) = chmod(&paths, &change, true);
+        assert_eq!(changed, 3);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with(&dir.join("missing").display().to_string()));
+        assert_eq!(mode("sub") & 0o077, 0);
+        assert_eq!(mode("sub/f") & 0o077, 0);
+        let meta = fs::symlink_metadata(dir.join("sub/f")).unwrap();
+        let lines = lines(&meta);
+        assert_eq!(lines.len(), BITS.len() + 3);
+        assert_eq!(lines[4], "[ ] group read");
+        assert_eq!(toggle(1, meta.mode()), Some("u-r".into()));
+        assert_eq!(toggle(4, meta.mode()), Some("g+r".into()));
+        assert_eq!(toggle(0, meta.mode()), None);
+        // chown to who already owns it is allowed
+        let (uid, gid) = (meta.uid(), meta.gid());
+        assert_eq!(chown(&[dir.join("sub")], Some(uid), Some(gid), true), (2, vec![]));
+    }
+}
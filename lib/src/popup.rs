@@ -0,0 +1,64 @@
+use druid::Data;
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// what accepting a line of the popup does
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PopupKind {
+    /// lines are [`crate::history::History::list`]
+    History,
+    /// lines stand for `Popup::paths`, accepting one goes there
+    Jump,
+    /// only to be read, accepting closes it
+    Text,
+    /// lines are the finder's best matches, `paths` what they stand for
+    Finder,
+    /// Lines are [`crate::perms::lines`] of the first of `paths`, accepting one toggles the
+    /// bit of all of them or edits their mode or owner.
+    Permissions,
+    /// Lines are [`crate::props::lines`] of the first of `paths`, accepting one computes the
+    /// checksum it's for, edits the extended attribute or copies its value.
+    Properties,
+    /// Lines are `/..` and the entries of a directory of a scan, biggest first. `paths`
+    /// are the directory and the entries.
+    Analyzer,
+    /// Lines are [`crate::compare::Comparison::lines`], accepting one copies it to the side
+    /// it's missing on.
+    Compare,
+}
+
+/// a list shown in place of the columns, e.g. `:history`
+#[derive(Clone, Data)]
+pub struct Popup {
+    pub title: String,
+    pub lines: Arc<Vec<String>>,
+    pub cursor: usize,
+    #[data(same_fn = "PartialEq::eq")]
+    pub kind: PopupKind,
+    pub paths: Arc<Vec<PathBuf>>,
+    /// bytes of each line to emphasize
+    pub matched: Arc<Vec<Vec<Range<usize>>>>,
+}
+
+impl Popup {
+    pub fn new(kind: PopupKind, title: impl Into<String>, lines: Vec<String>) -> Self {
+        let (paths, matched) = Default::default();
+        Self { title: title.into(), lines: Arc::new(lines), cursor: 0, kind, paths, matched }
+    }
+
+    /// a [`PopupKind::Jump`] popup
+    pub fn jump(title: impl Into<String>, lines: Vec<String>, paths: Vec<PathBuf>) -> Self {
+        Self { paths: Arc::new(paths), ..Self::new(PopupKind::Jump, title, lines) }
+    }
+
+    pub fn selected_path(&self) -> Option<&Path> {
+        self.paths.get(self.cursor).map(PathBuf::as_path)
+    }
+
+    pub fn move_cursor(&mut self, delta: isize) {
+        let last = self.lines.len().saturating_sub(1) as isize;
+        self.cursor = (self.cursor as isize).saturating_add(delta).max(0).min(last) as usize;
+    }
+}
@@ -0,0 +1,109 @@
+use serde::Deserialize;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+
+use crate::entry::{self, ListOptions};
+use crate::fmt;
+use crate::grep;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreviewOptions {
+    pub enabled: bool,
+    /// bigger files aren't read
+    pub max_size: u64,
+    pub max_lines: usize,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self { enabled: true, max_size: 1 << 20, max_lines: 200 }
+    }
+}
+
+/// files with a NUL byte in this many first bytes are binary
+pub(crate) const SNIFF_LEN: usize = 8192;
+
+fn file_preview(path: &Path, options: &PreviewOptions) -> io::Result<Vec<String>> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    if size > options.max_size {
+        return Ok(vec![format!("({} file, too big to preview)", fmt::human_size(size))]);
+    }
+    let mut head = vec![];
+    (&mut file).take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+    if head.contains(&0) {
+        return Ok(vec![format!("({} binary file)", fmt::human_size(size))]);
+    }
+    let reader = BufReader::new(head.as_slice().chain(file));
+    let mut lines = vec![];
+    for line in reader.split(b'\n').take(options.max_lines) {
+        let line = line?;
+        lines.push(String::from_utf8_lossy(&line).trim_end_matches('\r').replace('\t', "    "));
+    }
+    Ok(lines)
+}
+
+/// what is shown of `path` next to the listing: the first lines of a file or the entries of
+/// a directory
+pub fn preview(path: &Path, options: &PreviewOptions, listing: &ListOptions) -> Vec<String> {
+    if path.is_dir() {
+        let entries = entry::read_entries(path, listing);
+        entries.into_iter().take(options.max_lines).map(|e| e.name).collect()
+    } else {
+        file_preview(path, options).unwrap_or_else(|e| vec![format!("({})", e)])
+    }
+}
+
+/// what's needed to read the preview of an entry, see [`crate::State::refresh_preview`]
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub path: PathBuf,
+    /// the lines of it `:grep` matched, shown with `context` lines around them
+    pub matched: Option<Vec<usize>>,
+    pub context: usize,
+    pub options: PreviewOptions,
+    pub listing: ListOptions,
+}
+
+impl Request {
+    pub fn read(&self) -> Vec<String> {
+        match &self.matched {
+            Some(lines) => grep::context(&self.path, lines, self.context, self.options.max_lines),
+            None => preview(&self.path, &self.options, &self.listing),
+        }
+    }
+}
+
+/// Reads previews on a thread of its own, the ones asked for before the last one waiting
+/// are skipped.
+pub struct Previewer {
+    tx: Sender<Request>,
+}
+
+impl Previewer {
+    /// `on_read` is called from the reading thread with the path and the lines of each preview
+    pub fn new(on_read: impl Fn(PathBuf, Vec<String>) + Send + 'static) -> Self {
+        let (tx, rx) = channel::<Request>();
+        thread::spawn(move || {
+            while let Ok(mut request) = rx.recv() {
+                // the cursor moved on from the others
+                while let Ok(later) = rx.try_recv() {
+                    request = later;
+                }
+                let lines = request.read();
+                on_read(request.path, lines);
+            }
+        });
+        Self { tx }
+    }
+
+    pub fn read(&self, request: Request) {
+        // the thread only stops when `self` is dropped
+        let _ = self.tx.send(request);
+    }
+}
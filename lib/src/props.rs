@@ -0,0 +1,311 @@
+//! The properties popup: everything `stat` says of an entry, its MIME type and extended
+//! attributes, and checksums of its contents once asked for.
+
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::Digest;
+
+use crate::details;
+use crate::fmt;
+use crate::preview::SNIFF_LEN;
+use crate::xattrs;
+
+/// the labels are padded to this width, values follow
+const LABEL_WIDTH: usize = 10;
+
+/// the value of a checksum not computed yet
+pub const PENDING: &str = "(enter to compute)";
+/// the value of a checksum being computed
+pub const COMPUTING: &str = "(computing)";
+
+/// a checksum of the popup
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Blake3,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 4] =
+        [Algorithm::Md5, Algorithm::Sha1, Algorithm::Sha256, Algorithm::Blake3];
+
+    /// its label in the popup
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "md5",
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Blake3 => "blake3",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|algorithm| algorithm.name() == name)
+    }
+}
+
+/// a line of the popup
+pub fn line(label: &str, value: impl std::fmt::Display) -> String {
+    format!("{:<width$}{}", label, value, width = LABEL_WIDTH)
+}
+
+/// the label and the value of a line of the popup
+pub fn split(line: &str) -> (&str, &str) {
+    match (line.get(..LABEL_WIDTH), line.get(LABEL_WIDTH..)) {
+        (Some(label), Some(value)) => (label.trim_end(), value),
+        _ => (line.trim_end(), ""),
+    }
+}
+
+/// `(major, minor)` of a device number, like glibc's `gnu_dev_major` and `gnu_dev_minor`
+fn major_minor(dev: u64) -> (u64, u64) {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    (major, minor)
+}
+
+fn kind(meta: &Metadata) -> &'static str {
+    let file_type = meta.file_type();
+    if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_symlink() {
+        "symbolic link"
+    } else if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_block_device() {
+        "block device"
+    } else if file_type.is_char_device() {
+        "character device"
+    } else {
+        "regular file"
+    }
+}
+
+/// from the magic numbers of a file, `text/plain` or `application/octet-stream` if it has
+/// none known, and `inode/...` for what's not a file, like `file --mime-type`
+pub fn mime_type(path: &Path, meta: &Metadata) -> io::Result<String> {
+    let file_type = meta.file_type();
+    let inode = if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_block_device() {
+        "blockdevice"
+    } else if file_type.is_char_device() {
+        "chardevice"
+    } else if meta.len() == 0 {
+        "x-empty"
+    } else {
+        let mut head = vec![];
+        File::open(path)?.take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+        return Ok(match infer::get(&head) {
+            Some(known) => known.mime_type().to_owned(),
+            None if head.contains(&0) => "application/octet-stream".to_owned(),
+            None => "text/plain".to_owned(),
+        });
+    };
+    Ok(format!("inode/{}", inode))
+}
+
+/// seconds and nanoseconds since the epoch, before it if negative
+fn since_epoch(t: SystemTime) -> (i64, i64) {
+    match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, d.subsec_nanos() as i64),
+        Err(e) => {
+            let d = e.duration();
+            match d.subsec_nanos() {
+                0 => (-(d.as_secs() as i64), 0),
+                nanos => (-(d.as_secs() as i64) - 1, 1_000_000_000 - nanos as i64),
+            }
+        }
+    }
+}
+
+/// the lines of the popup for the entry at `path`, not following it if it's a symlink,
+/// with a line per checksum to compute if it's a file
+pub fn lines(path: &Path) -> io::Result<Vec<String>> {
+    let meta = fs::symlink_metadata(path)?;
+    let mode = meta.mode() & 0o7777;
+    let mut lines = vec![line("path", path.display())];
+    match fs::read_link(path) {
+        Ok(target) => lines.push(line("type", format!("symbolic link to {}", target.display()))),
+        Err(_) => lines.push(line("type", kind(&meta))),
+    }
+    let mime = mime_type(path, &meta).unwrap_or_else(|e| format!("({})", e));
+    lines.push(line("mime", mime));
+    lines.push(line("size", format!("{} ({})", meta.len(), fmt::human_size(meta.len()))));
+    let blocks = format!("{} of 512 bytes, io block {}", meta.blocks(), meta.blksize());
+    lines.push(line("blocks", blocks));
+    lines.push(line("mode", format!("{} {:04o}", fmt::permissions(meta.file_type(), mode), mode)));
+    lines.push(line("owner", format!("{} ({})", details::user_name(meta.uid()), meta.uid())));
+    lines.push(line("group", format!("{} ({})", details::group_name(meta.gid()), meta.gid())));
+    lines.push(line("links", meta.nlink()));
+    lines.push(line("inode", meta.ino()));
+    let (major, minor) = major_minor(meta.dev());
+    lines.push(line("device", format!("{}:{} ({})", major, minor, meta.dev())));
+    let file_type = meta.file_type();
+    if file_type.is_block_device() || file_type.is_char_device() {
+        let (major, minor) = major_minor(meta.rdev());
+        lines.push(line("rdev", format!("{}:{}", major, minor)));
+    }
+    lines.push(line("accessed", fmt::precise_time(meta.atime(), meta.atime_nsec())));
+    lines.push(line("modified", fmt::precise_time(meta.mtime(), meta.mtime_nsec())));
+    lines.push(line("changed", fmt::precise_time(meta.ctime(), meta.ctime_nsec())));
+    if let Ok(created) = meta.created() {
+        let (secs, nanos) = since_epoch(created);
+        lines.push(line("created", fmt::precise_time(secs, nanos)));
+    }
+    // not supported by every filesystem
+    let attributes = xattrs::list(path).unwrap_or_default();
+    lines.extend(attributes.iter().map(|(name, value)| line("xattr", xattrs::show(name, value))));
+    if file_type.is_file() {
+        lines.extend(Algorithm::ALL.iter().map(|algorithm| line(algorithm.name(), PENDING)));
+    }
+    Ok(lines)
+}
+
+/// Copies the checksums computed, or being computed, in `old` into `new`, both lines of
+/// the same file, unless its size or modification time changed in between.
+pub fn keep_checksums(old: &[String], new: &mut [String]) {
+    let value = |lines: &[String], label: &str| {
+        let found = lines.iter().map(|line| split(line)).find(|(l, _)| *l == label);
+        found.map(|(_, value)| value.to_owned())
+    };
+    if ["size", "modified"].iter().any(|label| value(old, label) != value(new, label)) {
+        return;
+    }
+    for kept in new.iter_mut() {
+        let label = split(kept).0.to_owned();
+        if let (Some(_), Some(value)) = (Algorithm::from_name(&label), value(old, &label)) {
+            *kept = line(&label, value);
+        }
+    }
+}
+
+/// passes what `reader` reads to `update` a buffer at a time, false if cancelled
+fn feed(
+    reader: &mut impl Read,
+    cancel: &AtomicBool,
+    mut update: impl FnMut(&[u8]),
+) -> io::Result<bool> {
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(true),
+            Ok(read) => update(&buffer[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// the hex digest of `reader`'s contents with a RustCrypto hash, `None` if cancelled
+fn digest<D: Digest>(reader: &mut impl Read, cancel: &AtomicBool) -> io::Result<Option<String>> {
+    let mut hasher = D::new();
+    if !feed(reader, cancel, |bytes| hasher.update(bytes))? {
+        return Ok(None);
+    }
+    Ok(Some(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()))
+}
+
+/// the hex checksum of the contents of the file at `path`, `None` if cancelled
+pub fn checksum(
+    path: &Path,
+    algorithm: Algorithm,
+    cancel: &AtomicBool,
+) -> io::Result<Option<String>> {
+    let mut file = File::open(path)?;
+    match algorithm {
+        Algorithm::Md5 => digest::<md5::Md5>(&mut file, cancel),
+        Algorithm::Sha1 => digest::<sha1::Sha1>(&mut file, cancel),
+        Algorithm::Sha256 => digest::<sha2::Sha256>(&mut file, cancel),
+        Algorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            if !feed(&mut file, cancel, |bytes| {
+                hasher.update(bytes);
+            })? {
+                return Ok(None);
+            }
+            Ok(Some(hasher.finalize().to_hex().to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_props {
+    use super::*;
+    use crate::TestDir;
+
+    #[test]
+    fn test_lines() {
+        let dir = TestDir::new("props");
+        let file = dir.join("f");
+        fs::write(&file, "abc").unwrap();
+        let lines = lines(&file).unwrap();
+        let value = |label: &str| {
+            let found = lines.iter().map(|l| split(l)).find(|(l, _)| *l == label);
+            found.map(|(_, value)| value.to_owned())
+        };
+        assert_eq!(value("path"), Some(file.display().to_string()));
+        assert_eq!(value("type").as_deref(), Some("regular file"));
+        assert_eq!(value("mime").as_deref(), Some("text/plain"));
+        assert_eq!(value("size").as_deref(), Some("3 (3)"));
+        assert_eq!(value("sha256").as_deref(), Some(PENDING));
+        assert_eq!(value("rdev"), None);
+        let dir_lines = super::lines(&dir).unwrap();
+        assert!(dir_lines.contains(&line("mime", "inode/directory")));
+        assert!(!dir_lines.iter().any(|l| split(l).0 == "md5"));
+
+        let cancel = AtomicBool::new(false);
+        let sum = |algorithm| checksum(&file, algorithm, &cancel).unwrap().unwrap();
+        let sums: Vec<_> = Algorithm::ALL.iter().map(|&a| sum(a)).collect();
+        assert_eq!(sums[0], "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(sums[1], "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(
+            sums[2],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(sums[3].len(), 64);
+        assert_eq!(checksum(&file, Algorithm::Md5, &AtomicBool::new(true)).unwrap(), None);
+
+        let mut old = super::lines(&file).unwrap();
+        let md5 = old.iter().position(|l| split(l).0 == "md5").unwrap();
+        old[md5] = line("md5", &sums[0]);
+        let mut new = super::lines(&file).unwrap();
+        keep_checksums(&old, &mut new);
+        assert_eq!(new[md5], old[md5]);
+        fs::write(&file, "abcd").unwrap();
+        let mut changed = super::lines(&file).unwrap();
+        keep_checksums(&old, &mut changed);
+        assert_eq!(split(&changed[md5]), ("md5", PENDING));
+        fs::write(&file, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        assert_eq!(mime_type(&file, &fs::metadata(&file).unwrap()).unwrap(), "image/png");
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split(&line("md5", PENDING)), ("md5", PENDING));
+        assert_eq!(split("short"), ("short", ""));
+        assert_eq!(Algorithm::from_name("blake3"), Some(Algorithm::Blake3));
+        assert_eq!(Algorithm::from_name("size"), None);
+        assert_eq!(major_minor(0x0803), (8, 3));
+        let before = UNIX_EPOCH - std::time::Duration::from_millis(1500);
+        assert_eq!(since_epoch(before), (-2, 500_000_000));
+    }
+}
@@ -0,0 +1,275 @@
+//! Import of ranger's `rc.conf`: `set`, `map`, `copymap`, `unmap` and `alias`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cmdline;
+use crate::config::Config;
+use crate::keymap::{Keymap, Mode};
+use crate::shell;
+use crate::xdg;
+
+/// `$XDG_CONFIG_HOME/ranger/rc.conf`
+pub fn default_file() -> Option<PathBuf> {
+    xdg::config_home().map(|d| d.join("ranger").join("rc.conf"))
+}
+
+/// ranger's boolean, which is spelled `True` as often as `true`
+fn boolean(value: &str) -> Result<&'static str, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" => Ok("true"),
+        "false" => Ok("false"),
+        _ => Err(format!("not a boolean: {}", value)),
+    }
+}
+
+/// ranger's `set NAME VALUE` as ours, for `Config::set`
+fn option(name: &str, value: &str) -> Result<(&'static str, String), String> {
+    let sort = |value: &str| match value {
+        "natural" | "basename" | "basename_natural" => Ok("name"),
+        "size" => Ok("size"),
+        "mtime" => Ok("mtime"),
+        "extension" => Ok("extension"),
+        _ => Err(format!("unsupported sort {}", value)),
+    };
+    Ok(match name {
+        "show_hidden" => ("show_hidden", boolean(value)?.into()),
+        "sort" => ("sort", sort(value)?.into()),
+        "sort_reverse" => ("sort_reverse", boolean(value)?.into()),
+        "sort_directories_first" => ("dirs_first", boolean(value)?.into()),
+        "preview_files" => ("preview", boolean(value)?.into()),
+        "column_ratios" => ("column_ratios", value.into()),
+        _ => return Err(format!("unsupported option {}", name)),
+    })
+}
+
+/// the name of a ranger option toggled by `set NAME!` or `toggle_option NAME`
+fn toggled(name: &str) -> Result<String, String> {
+    let (name, _) = option(name, "true")?;
+    if name == "sort" || name == "column_ratios" {
+        return Err(format!("{} can't be toggled", name));
+    }
+    Ok(format!("set {} toggle", name))
+}
+
+/// `(key, value)` of a `key=value` argument of `move`
+fn move_arg(arg: &str) -> Option<(&str, i64)> {
+    let (key, value) = arg.split_once('=')?;
+    Some((key, value.parse().ok()?))
+}
+
+/// ranger's `shell` when its flags and macros mean the same here
+fn shell(line: &str) -> Result<String, String> {
+    let shell = shell::parse(line).unwrap_or_else(|| Err("not a shell command".into()))?;
+    // ranger's `%t` are the tagged files, uppercase macros are about the other tab
+    let mut chars = shell.command.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        match chars.next() {
+            Some(m) if m.is_ascii_alphabetic() && !"fsd".contains(m) => {
+                return Err(format!("unsupported macro %{}", m))
+            }
+            _ => (),
+        }
+    }
+    Ok(line.trim_start().to_owned())
+}
+
+/// A ranger command line as one of ours, `%any` becomes `%k`.
+fn command(line: &str) -> Result<String, String> {
+    let args: Vec<&str> = line.split_whitespace().collect();
+    let unsupported = || Err(format!("unsupported command {}", args.first().unwrap_or(&"")));
+    let translated = match args.as_slice() {
+        ["move", arg] => match move_arg(arg) {
+            Some(("down", 1)) => "down".into(),
+            Some(("up", 1)) => "up".into(),
+            Some(("down", n)) => format!("down {}", n),
+            Some(("up", n)) => format!("up {}", n),
+            Some(("to", 0)) => "top".into(),
+            Some(("to", -1)) => "bottom".into(),
+            Some(("left", 1)) => "leave".into(),
+            Some(("right", 1)) => "enter".into(),
+            _ => return unsupported(),
+        },
+        ["history_go", "-1"] => "back".into(),
+        ["history_go", "1"] => "forward".into(),
+        ["console"] => "console".into(),
+        ["console", text @ ..] if !text[0].starts_with('-') => {
+            // the text is typed as is, trailing space included
+            let text = line.trim_start()["console".len()..].trim_start();
+            format!("console {}", cmdline::quote(&text.replace("%space", " ")))
+        }
+        ["cd", path] => format!("cd {}", cmdline::quote(path)),
+        ["shell", ..] => shell(line)?,
+        ["mark_files", "toggle=True"] => "mark".into(),
+        ["toggle_visual_mode"] => "visual".into(),
+        ["copy"] => "yank".into(),
+        ["cut"] => "cut".into(),
+        ["paste"] => "paste".into(),
+        [quit] if ["quit", "quit!", "quitall", "quitall!"].contains(quit) => "quit".into(),
+        ["tab_new"] => "tabnew".into(),
+        ["tab_close"] => "tabclose".into(),
+        ["tab_move", "1"] => "tabnext".into(),
+        ["tab_move", "-1"] => "tabprev".into(),
+        ["tab_open", n] if n.parse::<usize>().is_ok() => format!("tab {}", n),
+        ["set_bookmark", "%any"] => "bookmark_set %k".into(),
+        ["enter_bookmark", "%any"] => "bookmark_go %k".into(),
+        ["unset_bookmark", "%any"] => "bookmark_delete %k".into(),
+        ["draw_bookmarks"] => "bookmarks".into(),
+        ["get_cumulative_size"] => "du".into(),
+        ["search_next"] => "search_next".into(),
+        ["search_next", "forward=False"] => "search_prev".into(),
+        ["set", name] if name.ends_with('!') => toggled(&name[..name.len() - 1])?,
+        ["toggle_option", name] => toggled(name)?,
+        ["set", name, value] => {
+            let (name, value) = option(name, value)?;
+            format!("set {} {}", name, cmdline::quote(&value))
+        }
+        _ => return unsupported(),
+    };
+    Ok(translated)
+}
+
+/// `line` with its first word replaced if it's an alias
+fn expand<'a>(aliases: &HashMap<String, String>, line: &'a str) -> std::borrow::Cow<'a, str> {
+    let line = line.trim_start();
+    let (name, rest) = line.split_at(line.find(char::is_whitespace).unwrap_or(line.len()));
+    match aliases.get(name) {
+        Some(expansion) => format!("{}{}", expansion, rest).into(),
+        None => line.into(),
+    }
+}
+
+/// one line of `rc.conf`
+fn import_line(
+    line: &str,
+    aliases: &mut HashMap<String, String>,
+    keymap: &mut Keymap,
+    config: &mut Config,
+) -> Result<(), String> {
+    let mut words = line.splitn(3, char::is_whitespace);
+    let directive = words.next().unwrap_or_default();
+    let (first, rest) = (words.next().unwrap_or_default(), words.next().unwrap_or_default());
+    let rest = rest.trim();
+    match directive {
+        "set" if !first.is_empty() && !rest.is_empty() => {
+            let (name, value) = option(first, rest)?;
+            config.set(name, &value)
+        }
+        "map" if !first.is_empty() && !rest.is_empty() => {
+            let translated = command(&expand(aliases, rest))?;
+            keymap.bind(Mode::Normal, first, &translated)
+        }
+        "copymap" if !first.is_empty() && !rest.is_empty() => {
+            let bound = keymap.lookup(Mode::Normal, first)?;
+            let command = bound.ok_or_else(|| format!("{} is not mapped", first))?.to_owned();
+            rest.split_whitespace().try_for_each(|keys| keymap.bind(Mode::Normal, keys, &command))
+        }
+        "unmap" if !first.is_empty() => {
+            let mut keys = std::iter::once(first).chain(rest.split_whitespace());
+            keys.try_for_each(|keys| keymap.unbind(Mode::Normal, keys).map(drop))
+        }
+        "alias" if !first.is_empty() && !rest.is_empty() => {
+            let expansion = expand(aliases, rest).into_owned();
+            aliases.insert(first.to_owned(), expansion);
+            Ok(())
+        }
+        "set" | "map" | "copymap" | "unmap" | "alias" => Err("missing arguments".into()),
+        _ => Err(format!("unsupported directive {}", directive)),
+    }
+}
+
+/// Applies the `rc.conf` in `text` to `keymap` and `config`. Returns what wasn't imported
+/// as `LINE: message`, the other lines still apply.
+pub fn import(text: &str, keymap: &mut Keymap, config: &mut Config) -> Vec<String> {
+    let mut aliases = HashMap::new();
+    let mut unsupported = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Err(e) = import_line(line, &mut aliases, keymap, config) {
+            unsupported.push(format!("{}: {}: {}", i + 1, e, line));
+        }
+    }
+    unsupported
+}
+
+/// [`import`] of a file, whose name prefixes the messages
+pub fn import_file(
+    file: &Path,
+    keymap: &mut Keymap,
+    config: &mut Config,
+) -> io::Result<Vec<String>> {
+    let text = fs::read_to_string(file)?;
+    let unsupported = import(&text, keymap, config);
+    Ok(unsupported.into_iter().map(|e| format!("{}:{}", file.display(), e)).collect())
+}
+
+#[cfg(test)]
+mod test_ranger {
+    use super::*;
+    use crate::entry::SortKey;
+
+    #[test]
+    fn test_command() {
+        assert_eq!(command("move down=1").unwrap(), "down");
+        assert_eq!(command("move up=5").unwrap(), "up 5");
+        assert_eq!(command("move to=-1").unwrap(), "bottom");
+        assert_eq!(command("console shell%space").unwrap(), "console 'shell '");
+        assert_eq!(command("cd ~/Downloads").unwrap(), "cd ~/Downloads");
+        assert_eq!(command("set show_hidden!").unwrap(), "set show_hidden toggle");
+        assert_eq!(command("set sort_directories_first False").unwrap(), "set dirs_first false");
+        assert_eq!(command("enter_bookmark %any").unwrap(), "bookmark_go %k");
+        assert_eq!(command("unset_bookmark %any").unwrap(), "bookmark_delete %k");
+        assert_eq!(command("get_cumulative_size").unwrap(), "du");
+        assert_eq!(command("search_next forward=False").unwrap(), "search_prev");
+        assert!(command("move down=0.5 pages=True").is_err());
+        assert!(command("chain cut; paste").is_err());
+        assert!(command("set sort!").is_err());
+        assert_eq!(command("shell -f mpv %s").unwrap(), "shell -f mpv %s");
+        assert_eq!(command("shell -p cat x%t").unwrap_err(), "unsupported macro %t");
+    }
+
+    #[test]
+    fn test_import() {
+        let mut keymap = Keymap::empty();
+        let mut config = Config::default();
+        let text = "\
+# comment
+set show_hidden true
+set sort mtime
+set column_ratios 1,2
+set draw_borders both
+alias up2 move up=2
+map J up2
+map gh cd ~
+map x chain cut; paste
+copymap J K L
+unmap gh
+cmap <C-a> eval fm.ui.console.move(left=1000)
+";
+        let unsupported = import(text, &mut keymap, &mut config);
+        assert_eq!(
+            unsupported,
+            vec![
+                "5: unsupported option draw_borders: set draw_borders both",
+                "9: unsupported command chain: map x chain cut; paste",
+                "12: unsupported directive cmap: cmap <C-a> eval fm.ui.console.move(left=1000)",
+            ]
+        );
+        assert!(config.listing.show_hidden);
+        assert_eq!(config.listing.sort, SortKey::Mtime);
+        assert_eq!(config.column_ratios, vec![1., 2.]);
+        let bindings: Vec<_> = keymap.bindings(Mode::Normal).collect();
+        assert_eq!(
+            bindings,
+            vec![("J".into(), "up 2"), ("K".into(), "up 2"), ("L".into(), "up 2")]
+        );
+    }
+}
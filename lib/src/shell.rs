@@ -0,0 +1,286 @@
+//! `:shell` and `!`, with ranger style macros.
+
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+
+/// captured output longer than this is cut
+const MAX_LINES: usize = 10_000;
+
+/// how a command is run
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// waited for in the background, its exit status is reported
+    Background,
+    /// like `Background`, its output is shown when it's done
+    Pager,
+    /// left on its own, not even if it fails is reported
+    Detached,
+}
+
+/// a parsed `:shell [-p|-d] COMMAND` or `!COMMAND`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShellCommand {
+    pub mode: Mode,
+    /// as typed, macros not expanded yet
+    pub command: String,
+}
+
+/// `None` if `line` isn't a shell command. `-f` is ranger's spelling of `-d`.
+pub fn parse(line: &str) -> Option<Result<ShellCommand, String>> {
+    let line = line.trim_start();
+    let rest = match line.strip_prefix('!') {
+        Some(rest) => rest,
+        None => match line.strip_prefix("shell") {
+            Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => rest,
+            _ => return None,
+        },
+    };
+    let mut rest = rest.trim_start();
+    let mut mode = Mode::Background;
+    while let Some(flags) = rest.strip_prefix('-') {
+        let end = flags.find(char::is_whitespace).unwrap_or(flags.len());
+        for flag in flags[..end].chars() {
+            mode = match (flag, mode) {
+                ('p', Mode::Background) | ('p', Mode::Pager) => Mode::Pager,
+                ('d', Mode::Background) | ('f', Mode::Background) => Mode::Detached,
+                ('d', Mode::Detached) | ('f', Mode::Detached) => Mode::Detached,
+                ('p', _) | ('d', _) | ('f', _) => {
+                    return Some(Err("shell: -p and -d can't be combined".into()))
+                }
+                _ => return Some(Err(format!("shell: unknown flag -{}", flag))),
+            };
+        }
+        rest = flags[end..].trim_start();
+    }
+    if rest.is_empty() {
+        return Some(Err("shell: no command".into()));
+    }
+    Some(Ok(ShellCommand { mode, command: rest.to_owned() }))
+}
+
+/// `s` as one word for `sh`, whatever bytes it's made of
+pub fn quote(s: &OsStr) -> OsString {
+    let bytes = s.as_bytes();
+    let plain = |b: &u8| b.is_ascii_alphanumeric() || b"-_./+,:@%".contains(b);
+    if !bytes.is_empty() && bytes.iter().all(plain) {
+        return s.to_owned();
+    }
+    let mut quoted = vec![b'\''];
+    for &b in bytes {
+        if b == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(b);
+        }
+    }
+    quoted.push(b'\'');
+    OsString::from_vec(quoted)
+}
+
+/// what the macros stand for
+#[derive(Clone, Debug, Default)]
+pub struct Macros {
+    /// `%f`
+    pub selected: Option<PathBuf>,
+    /// `%s`, the marked entries or the selected one
+    pub targets: Vec<PathBuf>,
+    /// `%d`, where the command runs
+    pub dir: PathBuf,
+    /// `%t`, the other tab's directory
+    pub other: Option<PathBuf>,
+}
+
+/// `path` relative to `dir`, where the command runs, as is if it's not under it
+fn relative(path: &Path, dir: &Path) -> OsString {
+    let name = path.strip_prefix(dir).unwrap_or(path).as_os_str();
+    // so a file named `-rf` isn't taken for options
+    if name.as_bytes().starts_with(b"-") {
+        Path::new(".").join(name).into_os_string()
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Replaces `%f`, `%s`, `%d` and `%t` in `command` by the quoted paths, relative to `%d` if
+/// under it, and `%%` by `%`. Other `%` are left as they are.
+pub fn expand(command: &str, macros: &Macros) -> Result<OsString, String> {
+    let mut expanded = OsString::new();
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c.encode_utf8(&mut [0; 4]));
+            continue;
+        }
+        let next = chars.clone().next();
+        let words: Vec<OsString> = match next {
+            Some('f') => {
+                let selected = macros.selected.as_ref().ok_or("%f: nothing selected")?;
+                vec![relative(selected, &macros.dir)]
+            }
+            Some('s') if macros.targets.is_empty() => return Err("%s: nothing selected".into()),
+            Some('s') => macros.targets.iter().map(|p| relative(p, &macros.dir)).collect(),
+            Some('d') => vec![macros.dir.clone().into_os_string()],
+            Some('t') => {
+                let other = macros.other.as_ref().ok_or("%t: there's no other tab")?;
+                vec![other.clone().into_os_string()]
+            }
+            Some('%') => {
+                chars.next();
+                expanded.push("%");
+                continue;
+            }
+            _ => {
+                expanded.push("%");
+                continue;
+            }
+        };
+        chars.next();
+        let quoted: Vec<OsString> = words.iter().map(|w| quote(w)).collect();
+        expanded.push(quoted.join(OsStr::new(" ")));
+    }
+    Ok(expanded)
+}
+
+/// `sh -c command` in `dir`
+fn sh(command: &OsStr, dir: &Path) -> Command {
+    let mut sh = Command::new("sh");
+    sh.arg("-c").arg(command).current_dir(dir).stdin(Stdio::null());
+    sh
+}
+
+/// Runs `command` and waits for it. With `capture` its output, then its errors, are returned
+/// as lines, else they go where ours go.
+pub fn run(
+    command: &OsStr,
+    dir: &Path,
+    capture: bool,
+) -> io::Result<(ExitStatus, Vec<String>)> {
+    if !capture {
+        return Ok((sh(command, dir).status()?, vec![]));
+    }
+    let output = sh(command, dir).output()?;
+    let text = [output.stdout, output.stderr].concat();
+    let mut lines: Vec<String> =
+        text.split(|&b| b == b'\n').map(|l| String::from_utf8_lossy(l).into_owned()).collect();
+    // the last line ends with a newline
+    if matches!(lines.last(), Some(line) if line.is_empty()) {
+        lines.pop();
+    }
+    if lines.len() > MAX_LINES {
+        lines.truncate(MAX_LINES);
+        lines.push(format!("(cut after {} lines)", MAX_LINES));
+    }
+    Ok((output.status, lines))
+}
+
+/// Starts `command` in its own process group, so it outlives us and our terminal's signals.
+pub fn detach(command: &OsStr, dir: &Path) -> io::Result<()> {
+    let mut sh = sh(command, dir);
+    sh.stdout(Stdio::null()).stderr(Stdio::null());
+    // only calls what's async-signal-safe, between fork and exec
+    unsafe {
+        sh.pre_exec(|| match libc::setpgid(0, 0) {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        });
+    }
+    let mut child = sh.spawn()?;
+    // no zombie is left while we run
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// a command run in the background ended
+#[derive(Clone, Debug)]
+pub struct Done {
+    pub command: String,
+    /// a description of the exit status, an error if it couldn't be run or failed
+    pub result: Result<String, String>,
+    /// what it printed, in [`Mode::Pager`]
+    pub output: Option<Vec<String>>,
+}
+
+/// a short description of how a command ended
+pub fn describe(status: ExitStatus) -> String {
+    match status.code() {
+        Some(0) => "done".into(),
+        Some(code) => format!("exit status {}", code),
+        None => "killed".into(),
+    }
+}
+
+#[cfg(test)]
+mod test_shell {
+    use super::*;
+    use crate::TestDir;
+
+    #[test]
+    fn test_parse() {
+        let cmd = |mode, command: &str| Some(Ok(ShellCommand { mode, command: command.into() }));
+        assert_eq!(parse("!ls -l"), cmd(Mode::Background, "ls -l"));
+        assert_eq!(parse("shell -p  du -sh %s"), cmd(Mode::Pager, "du -sh %s"));
+        assert_eq!(parse("shell -f mpv %f"), cmd(Mode::Detached, "mpv %f"));
+        assert_eq!(parse("shelly"), None);
+        assert_eq!(parse("up"), None);
+        assert!(parse("shell -pd x").unwrap().is_err());
+        assert!(parse("shell -x x").unwrap().is_err());
+        assert!(parse("!").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_expand() {
+        let macros = Macros {
+            selected: Some("/d/a b".into()),
+            targets: vec!["/d/it's".into(), "/d/-rf".into()],
+            dir: "/d".into(),
+            other: None,
+        };
+        assert_eq!(
+            expand("cp %s %d/x %f 100%% %Y", &macros).unwrap(),
+            OsStr::new(r#"cp 'it'\''s' ./-rf /d/x 'a b' 100% %Y"#)
+        );
+        assert_eq!(expand("cd %t", &macros).unwrap_err(), "%t: there's no other tab");
+        let bytes = OsStr::from_bytes(b"caf\xe9");
+        let selected = Some(Path::new("/").join(bytes));
+        let macros = Macros { selected, dir: "/".into(), ..Macros::default() };
+        assert_eq!(expand("%f", &macros).unwrap().as_bytes(), b"'caf\xe9'");
+        // the results of :find are listed as paths under the directory
+        let found = Macros {
+            selected: Some("/d/sub/x".into()),
+            targets: vec!["/d/sub/y".into(), "/elsewhere/z".into()],
+            dir: "/d".into(),
+            other: None,
+        };
+        assert_eq!(expand("rm %f %s", &found).unwrap(), OsStr::new("rm sub/x sub/y /elsewhere/z"));
+    }
+
+    #[test]
+    fn test_run() {
+        let (status, lines) = run(OsStr::new("echo a; echo b >&2; exit 3"), Path::new("/"), true)
+            .unwrap();
+        assert_eq!(describe(status), "exit status 3");
+        assert_eq!(lines, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_detach() {
+        let dir = TestDir::new("detach");
+        // its pid and process group, the same when it leads its own
+        let command = "echo $$ $(cut -d' ' -f5 /proc/$$/stat) > ids.part && mv ids.part ids";
+        detach(OsStr::new(command), &dir).unwrap();
+        let mut waited = 0;
+        while !dir.join("ids").exists() && waited < 100 {
+            thread::sleep(std::time::Duration::from_millis(50));
+            waited += 1;
+        }
+        let ids = std::fs::read_to_string(dir.join("ids")).unwrap();
+        let ids: Vec<&str> = ids.split_whitespace().collect();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0], ids[1]);
+    }
+}
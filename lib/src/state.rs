@@ -1,9 +1,24 @@
 use druid::{Data, Lens};
 
+use std::collections::{BTreeSet, HashMap};
 use std::env;
+use std::ops::{Range, RangeInclusive};
 use std::path::{Path, PathBuf};
 use std::{sync::Arc};
 
+use crate::config::Config;
+use crate::details::Column;
+use crate::dirinfo::{DirCache, DirInfo};
+use crate::dupes::{self, Group};
+use crate::entry::{self, Entry, ListOptions};
+use crate::find::{Found, Search};
+use crate::git::Repo;
+use crate::history::History;
+use crate::incsearch::IncSearch;
+use crate::popup::{Popup, PopupKind};
+use crate::preview;
+use crate::theme::{Style, Theme};
+
 /// @FIXME: decouple druid from yukari_lib
 #[derive(Clone, Data, Lens)]
 pub struct State {
@@ -12,9 +27,46 @@ pub struct State {
     #[data(same_fn = "PartialEq::eq")]
     pub current: PathBuf,
     #[data(ignore)]
-    current_content: Arc<Vec<String>>,
+    current_content: Arc<Vec<Entry>>,
     #[data(ignore)]
     parent_content: Arc<Vec<String>>,
+    /// index into `current_content`
+    pub cursor: usize,
+    /// names in `current`
+    marked: Arc<BTreeSet<String>>,
+    /// where the visual selection started, it ends at `cursor`
+    pub visual: Option<usize>,
+    /// status line shown in the footer
+    pub message: String,
+    #[data(ignore)]
+    history: Arc<History>,
+    pub popup: Option<Popup>,
+    config: Arc<Config>,
+    /// the tab's own sort and hidden files, the config's until changed
+    #[data(same_fn = "PartialEq::eq")]
+    listing: ListOptions,
+    /// only the entries of `current` whose names contain it are listed, smart case
+    #[data(same_fn = "PartialEq::eq")]
+    filter: Option<String>,
+    theme: Arc<Theme>,
+    /// of the detail view's columns, as resized
+    pub widths: Arc<Vec<f64>>,
+    /// the listed directories' counts and sizes, shared by the tabs
+    dirs: Arc<DirCache>,
+    /// listed in place of `current`'s entries, named by their paths from it
+    #[data(same_fn = "PartialEq::eq")]
+    pub search: Option<Search>,
+    /// of the search results, by name, the lines matching for searches of the contents
+    #[data(ignore)]
+    matches: Arc<HashMap<String, Vec<usize>>>,
+    /// the last `/`, its matches are highlighted
+    pub incsearch: Option<IncSearch>,
+    /// the status of the repository `current` is in, if any
+    git: Option<Arc<Repo>>,
+    /// what `preview` shows
+    #[data(same_fn = "PartialEq::eq")]
+    previewed: Option<PathBuf>,
+    pub preview: Arc<Vec<String>>,
 }
 
 impl State {
@@ -23,20 +75,563 @@ impl State {
         let current = env::current_dir()
             .or_else(|_| env::home_dir().ok_or(()))
             .unwrap_or("/".into());
+        Self::new(current)
+    }
+
+    pub fn new(current: PathBuf) -> Self {
         let parent = current.parent().map(|p| p.to_owned());
         let mut state = Self {
             current,
             parent,
             current_content: Default::default(),
             parent_content: Default::default(),
+            cursor: 0,
+            marked: Default::default(),
+            visual: None,
+            message: String::new(),
+            history: Default::default(),
+            popup: None,
+            config: Default::default(),
+            listing: Default::default(),
+            filter: None,
+            theme: Default::default(),
+            widths: Default::default(),
+            dirs: Default::default(),
+            search: None,
+            matches: Default::default(),
+            incsearch: None,
+            git: None,
+            previewed: None,
+            preview: Default::default(),
         };
+        state.widths = state.default_widths();
         state.update();
         state
     }
 
+    pub fn config(&self) -> &Arc<Config> {
+        &self.config
+    }
+
+    pub fn theme(&self) -> &Arc<Theme> {
+        &self.theme
+    }
+
+    pub fn listing(&self) -> &ListOptions {
+        &self.listing
+    }
+
+    /// re-reads the directories if `listing` changes them
+    pub fn set_listing(&mut self, listing: ListOptions) {
+        if listing != self.listing {
+            self.listing = listing;
+            self.reload();
+        }
+    }
+
+    pub fn filter(&self) -> Option<&str> {
+        self.filter.as_deref()
+    }
+
+    /// lists only the entries whose names contain `filter`, all of them if `None` or empty
+    pub fn set_filter(&mut self, filter: Option<String>) {
+        let filter = filter.filter(|f| !f.is_empty());
+        if filter != self.filter {
+            self.filter = filter;
+            self.reload();
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: Arc<Theme>) {
+        self.theme = theme;
+    }
+
+    /// how the entry is drawn, after its kind and name
+    pub fn style(&self, entry: &Entry) -> Style {
+        self.theme.ls_colors.style(entry)
+    }
+
+    pub fn set_git(&mut self, git: Option<Arc<Repo>>) {
+        self.git = git;
+    }
+
+    /// git's mark for `entry`, see [`crate::git::Status::mark`]
+    pub fn git_mark(&self, entry: &Entry) -> String {
+        let repo = self.git.as_ref();
+        repo.map(|repo| repo.status(&entry.path(&self.current)).mark()).unwrap_or_default()
+    }
+
+    /// the branch checked out and how it compares to its upstream, for the footer
+    pub fn branch(&self) -> String {
+        self.git.as_ref().map(|repo| repo.describe()).unwrap_or_default()
+    }
+
+    pub fn set_dirs(&mut self, dirs: Arc<DirCache>) {
+        self.dirs = dirs;
+    }
+
+    /// the entry's count and size, if it's a directory
+    pub fn dir_info(&self, entry: &Entry) -> DirInfo {
+        if entry.is_dir() {
+            self.dirs.info(&entry.path(&self.current))
+        } else {
+            DirInfo::default()
+        }
+    }
+
+    /// the directories listed whose entries aren't counted yet
+    pub fn uncounted(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        let dirs = self.current_content.iter().filter(|e| e.is_dir());
+        dirs.map(move |e| e.path(&self.current)).filter(move |p| !self.dirs.is_counted(p))
+    }
+
+    /// the detail view's columns
+    pub fn columns(&self) -> &[Column] {
+        &self.config.details.columns
+    }
+
+    fn default_widths(&self) -> Arc<Vec<f64>> {
+        Arc::new(self.columns().iter().map(|c| c.default_width()).collect())
+    }
+
+    /// Re-reads the directories if the listing options changed. The tab's own ones are
+    /// replaced only if the config's did.
+    pub fn set_config(&mut self, config: Arc<Config>) {
+        let new_listing = config.listing != self.config.listing;
+        let relist = new_listing || config.preview != self.config.preview;
+        let new_columns = config.details.columns != self.config.details.columns;
+        if new_listing {
+            self.listing = config.listing.clone();
+        }
+        self.config = config;
+        if new_columns {
+            self.widths = self.default_widths();
+        }
+        if relist {
+            self.update();
+        }
+    }
+
+    /// re-reads the directories, or what's still there of the search results
     pub fn update(&mut self) {
-        self.current_content = Arc::new(Self::dir_contents(&self.current));
-        self.parent_content = Arc::new(self.parent.as_ref().map_or(vec![], Self::dir_contents));
+        let listing = &self.listing;
+        let entries = match &self.search {
+            Some(search) => {
+                let current = &self.current;
+                let found = self.current_content.iter();
+                let found = found.map(|e| Entry::new(current, e.file_name.clone()));
+                let mut found: Vec<Entry> = found.filter(|e| e.metadata.is_some()).collect();
+                if search.groups.is_none() {
+                    entry::sort_entries(&mut found, listing);
+                }
+                found
+            }
+            None => {
+                let mut entries = entry::read_entries(&self.current, listing);
+                if let Some(filter) = &self.filter {
+                    entries.retain(|e| entry::contains(&e.name, filter));
+                }
+                entries
+            }
+        };
+        self.current_content = Arc::new(entries);
+        let parent = self.parent.as_ref().map(|p| entry::read_entries(p, listing));
+        let names = parent.unwrap_or_default().into_iter().map(|e| e.name);
+        self.parent_content = Arc::new(names.collect());
+        // the selected file may have changed too
+        self.previewed = None;
+        self.cursor = self.cursor.min(self.current_content.len().saturating_sub(1));
+        let content = &self.current_content;
+        let exists = |m: &String| content.iter().any(|e| e.name == *m);
+        if !self.marked.iter().all(exists) {
+            Arc::make_mut(&mut self.marked).retain(exists);
+        }
+    }
+
+    /// re-reads the directories, the cursor stays on the same entry if it's still there
+    pub fn reload(&mut self) {
+        let selected = self.current_content.get(self.cursor).map(|e| e.name.clone());
+        self.update();
+        let content = &self.current_content;
+        if let Some(i) = selected.and_then(|name| content.iter().position(|e| e.name == name)) {
+            self.cursor = i;
+        }
+    }
+
+    /// whether `changed` is listed, in the current or parent directory or the search results
+    pub fn lists(&self, changed: &Path) -> bool {
+        let dir = changed.parent();
+        let found = self.search.is_some() && changed.starts_with(&self.current);
+        found || dir == Some(&self.current) || dir.is_some() && dir == self.parent.as_deref()
+    }
+
+    /// the current directory, and the search whose results are listed or the filter
+    pub fn location(&self) -> String {
+        let dir = self.current.to_string_lossy();
+        match (&self.search, &self.filter) {
+            (Some(search), _) => format!("{} [{}]", dir, search.describe()),
+            (None, Some(filter)) => format!("{} [filter {}]", dir, filter),
+            (None, None) => dir.into_owned(),
+        }
+    }
+
+    /// lists the results of `search` under `current` in place of its entries, none yet
+    pub fn show_search(&mut self, search: Search) {
+        self.search = Some(search);
+        self.current_content = Default::default();
+        self.matches = Default::default();
+        self.marked = Default::default();
+        self.visual = None;
+        self.cursor = 0;
+        self.previewed = None;
+    }
+
+    /// Adds `found`, paths from `current`, to the results if they're the search `id`'s.
+    /// Returns whether they are.
+    pub fn add_found(&mut self, id: u64, found: &[Found], done: bool) -> bool {
+        let search = match &mut self.search {
+            Some(search) if search.id == id => search,
+            _ => return false,
+        };
+        search.found += found.len();
+        let with_lines = found.iter().filter(|f| !f.lines.is_empty());
+        let names = with_lines.map(|f| (f.path.to_string_lossy().into_owned(), f.lines.clone()));
+        Arc::make_mut(&mut self.matches).extend(names);
+        search.done = done;
+        let selected = self.current_content.get(self.cursor).map(|e| e.name.clone());
+        let (current, content) = (&self.current, Arc::make_mut(&mut self.current_content));
+        content.extend(found.iter().map(|f| Entry::new(current, f.path.clone().into())));
+        entry::sort_entries(content, &self.listing);
+        if let Some(i) = selected.and_then(|name| content.iter().position(|e| e.name == name)) {
+            self.cursor = i;
+        }
+        true
+    }
+
+    /// Lists `groups`, paths from `current`, group after group if they're the duplicates the
+    /// search `id` looked for. Returns whether they are.
+    pub fn add_duplicates(&mut self, id: u64, groups: &[Group]) -> bool {
+        let search = match &mut self.search {
+            Some(search) if search.id == id => search,
+            _ => return false,
+        };
+        let (mut content, mut by_name) = (vec![], HashMap::new());
+        for (i, group) in groups.iter().enumerate() {
+            for path in &group.paths {
+                by_name.insert(path.to_string_lossy().into_owned(), i);
+                content.push(Entry::new(&self.current, path.clone().into()));
+            }
+        }
+        search.found = content.len();
+        search.done = true;
+        search.groups = Some(Arc::new(by_name));
+        self.current_content = Arc::new(content);
+        self.cursor = 0;
+        self.previewed = None;
+        true
+    }
+
+    /// the group of `entry` if duplicates are listed, see `add_duplicates`
+    pub fn duplicate_group(&self, entry: &Entry) -> Option<usize> {
+        let groups = self.search.as_ref()?.groups.as_ref()?;
+        groups.get(&entry.name).copied()
+    }
+
+    /// the duplicates listed with their groups, in order
+    fn listed_duplicates(&self) -> Option<Vec<(&str, usize)>> {
+        let groups = self.search.as_ref()?.groups.as_ref()?;
+        let listed = self.current_content.iter();
+        Some(listed.filter_map(|e| Some((e.name.as_str(), *groups.get(&e.name)?))).collect())
+    }
+
+    /// marks the duplicates listed but the first of each group, only them
+    pub fn mark_duplicates(&mut self) {
+        let listed = match self.listed_duplicates() {
+            Some(listed) => listed,
+            None => {
+                self.message = "no duplicates listed, see :dupes".into();
+                return;
+            }
+        };
+        let doomed = dupes::all_but_first(listed.into_iter());
+        let doomed: BTreeSet<String> = doomed.into_iter().map(String::from).collect();
+        self.message = format!("marked {} duplicates", doomed.len());
+        self.marked = Arc::new(doomed);
+        self.visual = None;
+    }
+
+    /// The marked duplicates paired with the first of their group that isn't, see
+    /// `dupes::originals`, as (duplicate, original).
+    pub fn duplicate_originals(&self) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+        let listed = self.listed_duplicates().ok_or("no duplicates listed, see :dupes")?;
+        let marked = |name: &str| self.marked.contains(name);
+        let (pairs, all_marked) = dupes::originals(listed.iter().copied(), marked);
+        if all_marked > 0 {
+            return Err(format!("{} groups have all their files marked", all_marked));
+        }
+        let path = |name: &str| self.current.join(name);
+        Ok(pairs.into_iter().map(|(copy, original)| (path(copy), path(original))).collect())
+    }
+
+    /// back to listing the current directory
+    fn close_search(&mut self) {
+        self.search = None;
+        self.matches = Default::default();
+        self.marked = Default::default();
+        self.visual = None;
+        self.reload();
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.current_content
+    }
+
+    fn names(&self) -> Vec<&str> {
+        self.current_content.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    /// `/` as it's typed: highlights what `pattern` matches, with the cursor on the first match
+    /// from `from`, or there if there's none
+    pub fn incsearch(&mut self, pattern: &str, from: usize) -> Result<(), String> {
+        self.cursor = from;
+        self.move_cursor(0);
+        self.incsearch = None;
+        if pattern.is_empty() {
+            return Ok(());
+        }
+        let search = IncSearch::new(pattern, self.config.search_regex)?;
+        if let Some(i) = search.next(&self.names(), self.cursor, false, true) {
+            self.cursor = i;
+        }
+        self.incsearch = Some(search);
+        Ok(())
+    }
+
+    /// `n`, or `N` if `back`: moves to the next match of the last `/`
+    pub fn search_next(&mut self, back: bool) {
+        let search = match &self.incsearch {
+            Some(search) => search,
+            None => {
+                self.message = "no search".into();
+                return;
+            }
+        };
+        match search.next(&self.names(), self.cursor, back, false) {
+            Some(i) => self.cursor = i,
+            None => self.message = format!("not found: {}", search.pattern),
+        }
+    }
+
+    /// what the last `/` matches in `name`
+    pub fn highlight(&self, name: &str) -> Option<Range<usize>> {
+        self.incsearch.as_ref().and_then(|search| search.find(name))
+    }
+
+    pub fn move_cursor(&mut self, delta: isize) {
+        let last = self.current_content.len().saturating_sub(1) as isize;
+        self.cursor = (self.cursor as isize).saturating_add(delta).max(0).min(last) as usize;
+    }
+
+    /// puts the cursor on the entry named `name` if it's listed
+    pub fn select(&mut self, name: &str) {
+        if let Some(index) = self.current_content.iter().position(|e| e.name == name) {
+            self.cursor = index;
+        }
+    }
+
+    /// moves to the directory `path`, recording it in the history
+    pub fn cd(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        if !path.is_dir() {
+            self.message = format!("not a directory: {}", path.display());
+        } else if path != self.current || self.search.is_some() {
+            Arc::make_mut(&mut self.history).visit(self.current.clone());
+            self.goto(path);
+        }
+    }
+
+    /// enters the selected directory
+    pub fn enter(&mut self) {
+        match self.selected() {
+            Some(path) if path.is_dir() => self.cd(path),
+            _ => (),
+        }
+    }
+
+    /// goes to the parent, with the cursor on the directory we came from, or back to the
+    /// directory from its search results
+    pub fn leave(&mut self) {
+        if self.search.is_some() {
+            self.close_search();
+        } else if let (Some(parent), Some(name)) = (self.parent.clone(), self.current.file_name()) {
+            Arc::make_mut(&mut self.history).remember(&parent, &name.to_string_lossy());
+            self.cd(parent);
+        }
+    }
+
+    pub fn back(&mut self) {
+        match Arc::make_mut(&mut self.history).back(&self.current) {
+            Some(path) => self.goto(path),
+            None => self.message = "already at oldest location".into(),
+        }
+    }
+
+    pub fn forward(&mut self) {
+        match Arc::make_mut(&mut self.history).forward(&self.current) {
+            Some(path) => self.goto(path),
+            None => self.message = "already at newest location".into(),
+        }
+    }
+
+    pub fn show_history(&mut self) {
+        let (list, pos) = self.history.list(&self.current);
+        let lines = list.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+        let mut popup = Popup::new(PopupKind::History, "history", lines);
+        popup.cursor = pos;
+        self.popup = Some(popup);
+    }
+
+    /// acts on the line under the popup's cursor and closes it
+    pub fn popup_accept(&mut self) {
+        let popup = match self.popup.take() {
+            Some(popup) => popup,
+            None => return,
+        };
+        match popup.kind {
+            PopupKind::History => {
+                if let Some(path) = Arc::make_mut(&mut self.history).jump(&self.current, popup.cursor) {
+                    self.goto(path);
+                }
+            }
+            PopupKind::Jump => {
+                if let Some(path) = popup.selected_path() {
+                    self.cd(path);
+                }
+            }
+            PopupKind::Text
+            | PopupKind::Analyzer
+            | PopupKind::Finder
+            | PopupKind::Permissions
+            | PopupKind::Properties
+            | PopupKind::Compare => {}
+        }
+    }
+
+    /// moves to `path` without touching the back/forward stacks
+    fn goto(&mut self, path: PathBuf) {
+        let listed = self.current_content.get(self.cursor).filter(|_| self.search.is_none());
+        if let Some(entry) = listed {
+            Arc::make_mut(&mut self.history).remember(&self.current, &entry.name);
+        }
+        self.parent = path.parent().map(|p| p.to_owned());
+        // until the status of the new one's repository is read
+        if !matches!(&self.git, Some(repo) if path.starts_with(&repo.root)) {
+            self.git = None;
+        }
+        self.current = path;
+        self.search = None;
+        self.filter = None;
+        self.matches = Default::default();
+        self.marked = Default::default();
+        self.visual = None;
+        self.update();
+        let content = &self.current_content;
+        self.cursor = self
+            .history
+            .cursor(&self.current)
+            .and_then(|name| content.iter().position(|e| e.name == name))
+            .unwrap_or(0);
+    }
+
+    /// What to read for the preview of the selected entry if it isn't already shown or
+    /// being read, to be given to [`State::set_preview`]. The preview is emptied right away
+    /// if there's nothing to show.
+    pub fn refresh_preview(&mut self) -> Option<preview::Request> {
+        let selected = self.selected().filter(|_| self.config.preview.enabled);
+        if selected == self.previewed {
+            return None;
+        }
+        self.previewed = selected.clone();
+        let path = match selected {
+            Some(path) => path,
+            None => {
+                self.preview = Default::default();
+                return None;
+            }
+        };
+        let entry = self.current_content.get(self.cursor);
+        let config = &self.config;
+        Some(preview::Request {
+            path,
+            matched: entry.and_then(|e| self.matches.get(&e.name)).cloned(),
+            context: config.grep.context,
+            options: config.preview.clone(),
+            listing: self.listing.clone(),
+        })
+    }
+
+    /// shows the preview of `path` read for [`State::refresh_preview`], unless the cursor
+    /// moved on since
+    pub fn set_preview(&mut self, path: &Path, lines: Vec<String>) {
+        if self.previewed.as_deref() == Some(path) {
+            self.preview = Arc::new(lines);
+        }
+    }
+
+    /// entry under the cursor
+    pub fn selected(&self) -> Option<PathBuf> {
+        self.current_content.get(self.cursor).map(|e| e.path(&self.current))
+    }
+
+    pub fn toggle_mark(&mut self) {
+        if let Some(entry) = self.current_content.get(self.cursor) {
+            let marked = Arc::make_mut(&mut self.marked);
+            if !marked.remove(&entry.name) {
+                marked.insert(entry.name.clone());
+            }
+        }
+    }
+
+    pub fn is_marked(&self, name: &str) -> bool {
+        self.marked.contains(name)
+    }
+
+    /// indices between the visual anchor and the cursor
+    fn visual_range(&self) -> Option<RangeInclusive<usize>> {
+        self.visual.map(|anchor| anchor.min(self.cursor)..=anchor.max(self.cursor))
+    }
+
+    pub fn in_visual(&self, index: usize) -> bool {
+        matches!(self.visual_range(), Some(range) if range.contains(&index))
+    }
+
+    /// starts selecting from the cursor, or ends it marking what was selected
+    pub fn toggle_visual(&mut self) {
+        if self.visual.is_some() {
+            let names: Vec<String> = self.visual_names().cloned().collect();
+            Arc::make_mut(&mut self.marked).extend(names);
+            self.visual = None;
+        } else if !self.current_content.is_empty() {
+            self.visual = Some(self.cursor);
+        }
+    }
+
+    fn visual_names(&self) -> impl Iterator<Item = &String> {
+        let content = &self.current_content;
+        let range = self.visual_range().into_iter().flatten();
+        range.filter_map(move |i| content.get(i).map(|e| &e.name))
+    }
+
+    /// marked entries and the visual selection, or the selected one if there are none
+    pub fn targets(&self) -> Vec<PathBuf> {
+        let mut names: BTreeSet<&String> = self.marked.iter().collect();
+        names.extend(self.visual_names());
+        if names.is_empty() {
+            self.selected().into_iter().collect()
+        } else {
+            let entries = self.current_content.iter().filter(|e| names.contains(&e.name));
+            entries.map(|e| e.path(&self.current)).collect()
+        }
     }
 
     pub fn dir_contents(path: impl AsRef<Path>) -> Vec<String> {
@@ -49,7 +644,3 @@ impl State {
         }
     }
 }
-
-struct DirInfo {
-    num_children: u32,
-}
\ No newline at end of file
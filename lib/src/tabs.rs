@@ -0,0 +1,288 @@
+use druid::{Data, Lens};
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::cmdline::CmdLine;
+use crate::config::Config;
+use crate::dirinfo::DirCache;
+use crate::theme::Theme;
+use crate::State;
+
+#[derive(Copy, Clone, Debug, Data, PartialEq, Eq)]
+pub enum Layout {
+    /// parent and current directory of the active tab
+    Miller,
+    /// the active and the other tab side by side, commander style
+    Dual,
+}
+
+/// The app's data: every tab navigates on its own.
+#[derive(Clone, Data)]
+pub struct Tabs {
+    tabs: Arc<Vec<State>>,
+    /// index into `tabs`, always valid
+    active: usize,
+    /// previously active tab, may be out of date
+    alternate: usize,
+    pub layout: Layout,
+    /// being edited after `:`, shared by all tabs
+    pub cmdline: Option<CmdLine>,
+    /// which-key style continuations of the keys typed so far
+    pub hints: Option<Arc<Vec<String>>>,
+    config: Arc<Config>,
+    theme: Arc<Theme>,
+    dirs: Arc<DirCache>,
+}
+
+/// more tabs than this can't be opened
+pub const MAX_TABS: usize = 9;
+
+impl Tabs {
+    pub fn new(state: State) -> Self {
+        Self {
+            tabs: Arc::new(vec![state]),
+            active: 0,
+            alternate: 0,
+            layout: Layout::Miller,
+            cmdline: None,
+            hints: None,
+            config: Default::default(),
+            theme: Default::default(),
+            dirs: Default::default(),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn current(&self) -> &State {
+        &self.tabs[self.active]
+    }
+
+    pub fn current_mut(&mut self) -> &mut State {
+        &mut Arc::make_mut(&mut self.tabs)[self.active]
+    }
+
+    pub fn get(&self, index: usize) -> Option<&State> {
+        self.tabs.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut State> {
+        Arc::make_mut(&mut self.tabs).get_mut(index)
+    }
+
+    /// the previously active tab, or the one after the active one
+    pub fn other_index(&self) -> Option<usize> {
+        if self.tabs.len() < 2 {
+            None
+        } else if self.alternate != self.active && self.alternate < self.tabs.len() {
+            Some(self.alternate)
+        } else {
+            Some((self.active + 1) % self.tabs.len())
+        }
+    }
+
+    pub fn other(&self) -> Option<&State> {
+        self.other_index().map(|i| &self.tabs[i])
+    }
+
+    /// tab indices shown in the left and right panes of [`Layout::Dual`]
+    pub fn panes(&self) -> (usize, usize) {
+        let other = self.other_index().unwrap_or(self.active);
+        (self.active.min(other), self.active.max(other))
+    }
+
+    /// opens a second tab if needed for [`Layout::Dual`]
+    pub fn set_layout(&mut self, layout: Layout) {
+        if layout == Layout::Dual && self.tabs.len() < 2 {
+            let active = self.active;
+            self.open();
+            self.select(active);
+        }
+        self.layout = layout;
+    }
+
+    /// opens a tab on the current directory, right after the active one
+    pub fn open(&mut self) {
+        if self.tabs.len() >= MAX_TABS {
+            self.current_mut().message = format!("no more than {} tabs", MAX_TABS);
+            return;
+        }
+        let mut state = State::new(self.current().current.clone());
+        state.set_config(self.config.clone());
+        state.set_theme(self.theme.clone());
+        state.set_dirs(self.dirs.clone());
+        // its listing options may differ
+        let current = self.current();
+        if let Some(entry) = current.entries().get(current.cursor) {
+            state.select(&entry.name);
+        }
+        self.alternate = self.active;
+        self.active += 1;
+        Arc::make_mut(&mut self.tabs).insert(self.active, state);
+    }
+
+    /// the last tab can't be closed
+    pub fn close(&mut self) {
+        if self.tabs.len() > 1 {
+            let closed = self.active;
+            Arc::make_mut(&mut self.tabs).remove(closed);
+            if self.alternate > closed {
+                self.alternate -= 1;
+            }
+            self.active = if self.alternate < closed {
+                self.alternate
+            } else {
+                closed.min(self.tabs.len() - 1)
+            };
+            if self.tabs.len() < 2 {
+                self.layout = Layout::Miller;
+            }
+        }
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.tabs.len() && index != self.active {
+            self.alternate = self.active;
+            self.active = index;
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.select((self.active + 1) % self.tabs.len());
+    }
+
+    pub fn prev(&mut self) {
+        self.select((self.active + self.tabs.len() - 1) % self.tabs.len());
+    }
+
+    pub fn config(&self) -> &Arc<Config> {
+        &self.config
+    }
+
+    /// applies `config` to every tab, their listing options only if the config's changed, it
+    /// has the ones of new tabs
+    pub fn set_config(&mut self, config: Arc<Config>) {
+        for state in Arc::make_mut(&mut self.tabs) {
+            state.set_config(config.clone());
+        }
+        self.config = config;
+    }
+
+    pub fn theme(&self) -> &Arc<Theme> {
+        &self.theme
+    }
+
+    /// applies `theme` to every tab
+    pub fn set_theme(&mut self, theme: Arc<Theme>) {
+        for state in Arc::make_mut(&mut self.tabs) {
+            state.set_theme(theme.clone());
+        }
+        self.theme = theme;
+    }
+
+    /// the directories' counts and sizes
+    pub fn dirs(&self) -> &Arc<DirCache> {
+        &self.dirs
+    }
+
+    /// gives every tab `dirs`
+    pub fn set_dirs(&mut self, dirs: Arc<DirCache>) {
+        for state in Arc::make_mut(&mut self.tabs) {
+            state.set_dirs(dirs.clone());
+        }
+        self.dirs = dirs;
+    }
+
+    /// re-reads the directories of the tabs listing `changed`, of every tab if `None`
+    pub fn reload(&mut self, changed: Option<&Path>) {
+        for state in Arc::make_mut(&mut self.tabs) {
+            let listed = match changed {
+                Some(changed) => state.lists(changed),
+                None => true,
+            };
+            if listed {
+                state.reload();
+            }
+        }
+    }
+
+    /// re-reads every tab's directories
+    pub fn update(&mut self) {
+        for state in Arc::make_mut(&mut self.tabs) {
+            state.update();
+        }
+    }
+
+    /// `index:dirname` for every tab
+    pub fn titles(&self) -> Vec<String> {
+        self.tabs
+            .iter()
+            .enumerate()
+            .map(|(i, state)| {
+                let name = state.current.file_name().map_or_else(
+                    || state.current.to_string_lossy(),
+                    |name| name.to_string_lossy(),
+                );
+                format!("{}:{}", i + 1, name)
+            })
+            .collect()
+    }
+}
+
+/// lens from [`Tabs`] to the active tab's [`State`]
+pub struct Active;
+
+/// lens from [`Tabs`] to the [`State`] shown in a pane of [`Layout::Dual`]
+pub struct Pane {
+    pub right: bool,
+}
+
+impl Pane {
+    pub fn index(&self, tabs: &Tabs) -> usize {
+        let (left, right) = tabs.panes();
+        if self.right {
+            right
+        } else {
+            left
+        }
+    }
+}
+
+impl Lens<Tabs, State> for Pane {
+    fn with<V, F: FnOnce(&State) -> V>(&self, data: &Tabs, f: F) -> V {
+        f(&data.tabs[self.index(data)])
+    }
+
+    fn with_mut<V, F: FnOnce(&mut State) -> V>(&self, data: &mut Tabs, f: F) -> V {
+        let index = self.index(data);
+        let mut state = data.tabs[index].clone();
+        let v = f(&mut state);
+        if !state.same(&data.tabs[index]) {
+            Arc::make_mut(&mut data.tabs)[index] = state;
+        }
+        v
+    }
+}
+
+impl Lens<Tabs, State> for Active {
+    fn with<V, F: FnOnce(&State) -> V>(&self, data: &Tabs, f: F) -> V {
+        f(data.current())
+    }
+
+    fn with_mut<V, F: FnOnce(&mut State) -> V>(&self, data: &mut Tabs, f: F) -> V {
+        let mut state = data.current().clone();
+        let v = f(&mut state);
+        // only write back on change, avoids cloning the tabs
+        if !state.same(data.current()) {
+            *data.current_mut() = state;
+        }
+        v
+    }
+}
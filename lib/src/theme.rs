@@ -0,0 +1,403 @@
+use serde::Deserialize;
+use toml::Spanned;
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::PathBuf;
+
+use crate::entry::Entry;
+use crate::xdg;
+
+/// red, green, blue and alpha
+pub type Rgba = (u8, u8, u8, u8);
+
+/// named colors of a theme, and of the config's `[colors]`
+pub const COLOR_NAMES: &[&str] = &["background", "foreground", "selection", "border", "footer"];
+
+/// name of the theme used when the config doesn't say
+pub const DEFAULT: &str = "default";
+
+/// `(name, theme file)`, overridden by user themes of the same name
+const BUNDLED: &[(&str, &str)] = &[
+    // druid's own colors, and the `LS_COLORS` of the environment
+    ("default", ""),
+    (
+        "dark",
+        r##"
+ls_colors = """
+di=1;34:ln=36:or=31:ex=1;32:pi=33:so=35:bd=33:cd=33:\
+*.tar=31:*.gz=31:*.zst=31:*.zip=31:*.7z=31:\
+*.jpg=35:*.png=35:*.gif=35:*.mp4=35:*.mkv=35:*.mp3=36:*.flac=36:*.pdf=33"""
+
+[colors]
+background = "#1d1f21"
+foreground = "#c5c8c6"
+selection = "#373b41"
+border = "#81a2be"
+footer = "#282a2e"
+"##,
+    ),
+    (
+        "light",
+        r##"
+ls_colors = """
+di=1;38;5;25:ln=38;5;30:or=38;5;160:ex=1;38;5;28:pi=38;5;136:so=38;5;127:bd=38;5;136:\
+cd=38;5;136:*.tar=38;5;160:*.gz=38;5;160:*.zst=38;5;160:*.zip=38;5;160:*.7z=38;5;160:\
+*.jpg=38;5;127:*.png=38;5;127:*.gif=38;5;127:*.mp4=38;5;127:*.mkv=38;5;127:\
+*.mp3=38;5;30:*.flac=38;5;30:*.pdf=38;5;136"""
+
+[colors]
+background = "#fafafa"
+foreground = "#383a42"
+selection = "#d0d0d0"
+border = "#4078f2"
+footer = "#e5e5e6"
+"##,
+    ),
+];
+
+/// what `ls` uses without `LS_COLORS`
+const LS_COLORS_FALLBACK: &str =
+    "di=01;34:ln=01;36:pi=40;33:so=01;35:bd=40;33;01:cd=40;33;01:or=40;31;01:ex=01;32";
+
+/// `#rgb`, `#rrggbb` or `#rrggbbaa` as rgba
+pub fn parse_color(s: &str) -> Option<Rgba> {
+    let hex = s.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    match hex.len() {
+        3 => {
+            let digit = |i: usize| u8::from_str_radix(&hex[i..=i], 16).ok().map(|d| d * 17);
+            Some((digit(0)?, digit(1)?, digit(2)?, 255))
+        }
+        6 => Some((byte(0)?, byte(2)?, byte(4)?, 255)),
+        8 => Some((byte(0)?, byte(2)?, byte(4)?, byte(6)?)),
+        _ => None,
+    }
+}
+
+/// how an entry is drawn, unset parts are left to the theme
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Style {
+    pub color: Option<Rgba>,
+    pub bold: bool,
+}
+
+/// the 16 colors of the terminal, as in xterm
+const ANSI: [Rgba; 16] = [
+    (0, 0, 0, 255),
+    (205, 0, 0, 255),
+    (0, 205, 0, 255),
+    (205, 205, 0, 255),
+    (59, 120, 255, 255),
+    (205, 0, 205, 255),
+    (0, 205, 205, 255),
+    (229, 229, 229, 255),
+    (127, 127, 127, 255),
+    (255, 0, 0, 255),
+    (0, 255, 0, 255),
+    (255, 255, 0, 255),
+    (92, 92, 255, 255),
+    (255, 0, 255, 255),
+    (0, 255, 255, 255),
+    (255, 255, 255, 255),
+];
+
+/// one of the 256 colors of `38;5;N`
+fn color_256(n: u8) -> Rgba {
+    match n {
+        0..=15 => ANSI[n as usize],
+        16..=231 => {
+            let level = |i: u8| if i == 0 { 0 } else { 55 + i * 40 };
+            let n = n - 16;
+            (level(n / 36), level(n / 6 % 6), level(n % 6), 255)
+        }
+        _ => {
+            let gray = 8 + (n - 232) * 10;
+            (gray, gray, gray, 255)
+        }
+    }
+}
+
+/// the foreground color and boldness of SGR codes like `01;38;5;208`, backgrounds are ignored
+fn parse_sgr(codes: &str) -> Option<Style> {
+    let codes = codes.split(';').map(|c| if c.is_empty() { Ok(0) } else { c.parse::<u8>() });
+    let codes = codes.collect::<Result<Vec<u8>, _>>().ok()?;
+    let mut style = Style::default();
+    // the bold variant of the 8 first colors is the bright one
+    let mut basic = None;
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            0 => {
+                style = Style::default();
+                basic = None;
+            }
+            1 => style.bold = true,
+            30..=37 => basic = Some(codes[i] - 30),
+            39 => basic = None,
+            90..=97 => {
+                basic = None;
+                style.color = Some(ANSI[(codes[i] - 90 + 8) as usize]);
+            }
+            code @ 38 | code @ 48 => {
+                let color = match codes.get(i + 1..) {
+                    Some([5, n, ..]) => {
+                        i += 2;
+                        color_256(*n)
+                    }
+                    Some([2, r, g, b, ..]) => {
+                        i += 4;
+                        (*r, *g, *b, 255)
+                    }
+                    _ => return None,
+                };
+                if code == 38 {
+                    basic = None;
+                    style.color = Some(color);
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    if let Some(n) = basic {
+        style.color = Some(ANSI[(n + if style.bold { 8 } else { 0 }) as usize]);
+    }
+    Some(style)
+}
+
+/// the kind of file an `LS_COLORS` key like `di` applies to, for `entry` with `metadata`
+fn kind(entry: &Entry, metadata: &Metadata) -> &'static str {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        match entry.link.as_ref().and_then(|link| link.metadata.as_ref()) {
+            Some(_) => "ln",
+            None => "or",
+        }
+    } else if file_type.is_dir() {
+        "di"
+    } else if file_type.is_fifo() {
+        "pi"
+    } else if file_type.is_socket() {
+        "so"
+    } else if file_type.is_block_device() {
+        "bd"
+    } else if file_type.is_char_device() {
+        "cd"
+    } else if metadata.permissions().mode() & 0o111 != 0 {
+        "ex"
+    } else {
+        "fi"
+    }
+}
+
+/// Styles by file kind and name suffix, parsed from the `LS_COLORS` format. Suffixes only
+/// style regular files, like `ls` does.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LsColors {
+    kinds: HashMap<String, Style>,
+    /// lowercase suffix, including the dot for `*.ext`
+    suffixes: Vec<(String, Style)>,
+}
+
+impl LsColors {
+    /// unknown or malformed entries are skipped
+    pub fn parse(text: &str) -> Self {
+        let mut colors = Self::default();
+        for entry in text.split(':') {
+            let (key, codes) = match entry.split_once('=') {
+                Some(split) => split,
+                None => continue,
+            };
+            let style = match parse_sgr(codes) {
+                Some(style) => style,
+                None => continue,
+            };
+            match key.strip_prefix('*') {
+                Some(suffix) => colors.suffixes.push((suffix.to_lowercase(), style)),
+                None => {
+                    colors.kinds.insert(key.to_owned(), style);
+                }
+            }
+        }
+        // the longest suffix wins
+        colors.suffixes.sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.len()));
+        colors
+    }
+
+    /// `$LS_COLORS`, or what `ls` uses without it
+    pub fn from_env() -> Self {
+        Self::parse(&env::var("LS_COLORS").unwrap_or_else(|_| LS_COLORS_FALLBACK.into()))
+    }
+
+    /// the style of `entry`, nothing is read from the disk
+    pub fn style(&self, entry: &Entry) -> Style {
+        let metadata = match &entry.metadata {
+            Some(metadata) => metadata,
+            None => return Style::default(),
+        };
+        let kind = kind(entry, metadata);
+        if kind == "fi" || kind == "ex" {
+            let name = entry.name.to_lowercase();
+            if let Some((_, style)) = self.suffixes.iter().find(|(s, _)| name.ends_with(s)) {
+                return *style;
+            }
+        }
+        self.kinds.get(kind).copied().unwrap_or_default()
+    }
+}
+
+/// a theme file, in TOML
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeFile {
+    /// see [`COLOR_NAMES`]
+    colors: BTreeMap<String, Spanned<String>>,
+    /// `$LS_COLORS` if not set
+    ls_colors: Option<String>,
+}
+
+/// colors of the interface and of the entries
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Theme {
+    /// the druid theme is kept for those not set
+    pub colors: BTreeMap<String, Rgba>,
+    pub ls_colors: LsColors,
+}
+
+impl Theme {
+    /// `$XDG_CONFIG_HOME/yukari/themes`, where `NAME.toml` is the theme `NAME`
+    pub fn user_dir() -> Option<PathBuf> {
+        xdg::config_dir().map(|d| d.join("themes"))
+    }
+
+    /// names of the bundled and user themes
+    pub fn names() -> Vec<String> {
+        let mut names: Vec<String> = BUNDLED.iter().map(|(name, _)| name.to_string()).collect();
+        let files = Self::user_dir().and_then(|d| fs::read_dir(d).ok());
+        for entry in files.into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.extension() == Some("toml".as_ref()) {
+                if let Some(stem) = path.file_stem() {
+                    names.push(stem.to_string_lossy().into_owned());
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Errors are `LINE: message`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: ThemeFile = toml::from_str(text).map_err(|e| {
+            let line = e.line_col().map_or(1, |(line, _)| line + 1);
+            let msg = e.to_string();
+            let msg = msg.split(" at line ").next().unwrap_or_default().to_owned();
+            format!("{}: {}", line, msg)
+        })?;
+        let mut colors = BTreeMap::new();
+        for (name, value) in file.colors {
+            let line = text[..value.start()].matches('\n').count() + 1;
+            if !COLOR_NAMES.contains(&name.as_str()) {
+                return Err(format!("{}: unknown color {}", line, name));
+            }
+            let value = value.into_inner();
+            let color = parse_color(&value)
+                .ok_or_else(|| format!("{}: {}: expected #rrggbb, got {:?}", line, name, value))?;
+            colors.insert(name, color);
+        }
+        let ls_colors = match file.ls_colors {
+            Some(text) => LsColors::parse(&text),
+            None => LsColors::from_env(),
+        };
+        Ok(Self { colors, ls_colors })
+    }
+
+    /// The user theme `name`, or the bundled one. Errors start with the file name.
+    pub fn load(name: &str) -> Result<Self, String> {
+        if let Some(file) = Self::user_dir().map(|d| d.join(format!("{}.toml", name))) {
+            match fs::read_to_string(&file) {
+                Ok(text) => {
+                    return Self::parse(&text).map_err(|e| format!("{}:{}", file.display(), e))
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(format!("{}: {}", file.display(), e)),
+            }
+        }
+        match BUNDLED.iter().find(|(n, _)| *n == name) {
+            // bundled themes are tested
+            Some((_, text)) => Self::parse(text),
+            None => Err(format!("unknown theme {}", name)),
+        }
+    }
+
+    /// with `colors` set over the theme's
+    pub fn with_colors(mut self, colors: impl IntoIterator<Item = (String, Rgba)>) -> Self {
+        self.colors.extend(colors);
+        self
+    }
+
+    pub fn color(&self, name: &str) -> Option<Rgba> {
+        self.colors.get(name).copied()
+    }
+}
+
+#[cfg(test)]
+mod test_theme {
+    use super::*;
+    use crate::TestDir;
+
+    #[test]
+    fn test_parse_sgr() {
+        let style = |color, bold| Some(Style { color, bold });
+        assert_eq!(parse_sgr("01;34"), style(Some(ANSI[12]), true));
+        assert_eq!(parse_sgr("34"), style(Some(ANSI[4]), false));
+        assert_eq!(parse_sgr("40;33"), style(Some(ANSI[3]), false));
+        assert_eq!(parse_sgr("38;5;208"), style(Some((255, 135, 0, 255)), false));
+        assert_eq!(parse_sgr("1;38;2;1;2;3"), style(Some((1, 2, 3, 255)), true));
+        assert_eq!(parse_sgr("48;5;1;4"), style(None, false));
+        assert_eq!(parse_sgr("0"), style(None, false));
+        assert_eq!(parse_sgr("38;5"), None);
+        assert_eq!(parse_sgr("x"), None);
+    }
+
+    #[test]
+    fn test_ls_colors() {
+        let dir = TestDir::new("theme");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.TAR.gz"), "").unwrap();
+        fs::write(dir.join("b.txt"), "").unwrap();
+        std::os::unix::fs::symlink(dir.join("nowhere"), dir.join("broken")).unwrap();
+        let colors = LsColors::parse("di=34:or=31:*.gz=32:*.tar.gz=35:bogus:ex=1;x");
+        let style = |name: &str| colors.style(&Entry::new(&dir, name.into())).color;
+        assert_eq!(style("sub"), Some(ANSI[4]));
+        assert_eq!(style("a.TAR.gz"), Some(ANSI[5]));
+        assert_eq!(style("b.txt"), None);
+        assert_eq!(style("broken"), Some(ANSI[1]));
+    }
+
+    #[test]
+    fn test_bundled() {
+        for (name, text) in BUNDLED {
+            assert!(Theme::parse(text).is_ok(), "{}", name);
+        }
+        let dark = Theme::parse(BUNDLED[1].1).unwrap();
+        assert_eq!(dark.color("background"), Some((0x1d, 0x1f, 0x21, 255)));
+        let bold_blue = Style { color: Some(ANSI[12]), bold: true };
+        assert_eq!(dark.ls_colors.kinds.get("di"), Some(&bold_blue));
+        assert_eq!(
+            Theme::parse("[colors]\nborder = \"blue\"\n").unwrap_err(),
+            "2: border: expected #rrggbb, got \"blue\""
+        );
+        let unknown = Theme::parse("[colors]\n\nborders = \"#fff\"\n").unwrap_err();
+        assert_eq!(unknown, "3: unknown color borders");
+        assert!(Theme::parse("colours = 1").unwrap_err().starts_with("1: unknown field"));
+    }
+}
@@ -0,0 +1,102 @@
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
+
+/// changes closer than this are reported once
+const DELAY: Duration = Duration::from_millis(300);
+/// directories watched at most by a [`DirWatcher`], each one takes an inotify watch
+const MAX_DIRS: usize = 256;
+
+fn touches(event: &DebouncedEvent, file: &Path) -> bool {
+    match event {
+        DebouncedEvent::Create(p) | DebouncedEvent::Write(p) | DebouncedEvent::Remove(p) => {
+            p == file
+        }
+        // editors often save by renaming a temporary file over the original
+        DebouncedEvent::Rename(from, to) => from == file || to == file,
+        DebouncedEvent::Rescan => true,
+        _ => false,
+    }
+}
+
+/// Calls `on_change` from a background thread whenever `file` is written, created, replaced
+/// or removed. Its directory has to exist.
+pub fn watch_file(file: PathBuf, on_change: impl Fn() + Send + 'static) -> notify::Result<()> {
+    let dir = file.parent().unwrap_or_else(|| Path::new("/")).to_owned();
+    let (tx, rx) = channel();
+    let mut watcher = notify::watcher(tx, DELAY)?;
+    // the whole directory, as the file may be replaced or not exist yet
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    thread::spawn(move || {
+        // the watcher stops when dropped
+        let _watcher = watcher;
+        for event in rx {
+            if touches(&event, &file) {
+                on_change();
+            }
+        }
+    });
+    Ok(())
+}
+
+/// What an event changed: `Some(path)` for an entry created, removed, renamed or written,
+/// `None` if events were missed and anything may have.
+fn changed(event: DebouncedEvent) -> Vec<Option<PathBuf>> {
+    match event {
+        DebouncedEvent::Create(p)
+        | DebouncedEvent::Write(p)
+        | DebouncedEvent::Chmod(p)
+        | DebouncedEvent::Remove(p) => vec![Some(p)],
+        DebouncedEvent::Rename(from, to) => vec![Some(from), Some(to)],
+        DebouncedEvent::Rescan => vec![None],
+        _ => vec![],
+    }
+}
+
+/// Watches the entries of a set of directories, not their subdirectories.
+pub struct DirWatcher {
+    watcher: RecommendedWatcher,
+    watched: BTreeSet<PathBuf>,
+}
+
+impl DirWatcher {
+    /// `on_change` is called from a background thread with what changed, see [`changed`]
+    pub fn new(on_change: impl Fn(Option<PathBuf>) + Send + 'static) -> notify::Result<Self> {
+        let (tx, rx) = channel();
+        let watcher = notify::watcher(tx, DELAY)?;
+        thread::spawn(move || {
+            for event in rx {
+                changed(event).into_iter().for_each(&on_change);
+            }
+        });
+        Ok(Self { watcher, watched: BTreeSet::new() })
+    }
+
+    /// Watches `dirs` and only them, the first ones if there are too many. The ones that
+    /// can't be watched, that don't exist say, are left out.
+    pub fn set(&mut self, dirs: impl IntoIterator<Item = PathBuf>) {
+        let mut wanted = BTreeSet::new();
+        for dir in dirs {
+            if wanted.len() == MAX_DIRS {
+                break;
+            }
+            wanted.insert(dir);
+        }
+        for dir in self.watched.difference(&wanted) {
+            let _ = self.watcher.unwatch(dir);
+        }
+        let mut watched = BTreeSet::new();
+        for dir in wanted {
+            if self.watched.contains(&dir)
+                || self.watcher.watch(&dir, RecursiveMode::NonRecursive).is_ok()
+            {
+                watched.insert(dir);
+            }
+        }
+        self.watched = watched;
+    }
+}
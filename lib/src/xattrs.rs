@@ -0,0 +1,182 @@
+//! `:xattr`: the extended attributes of entries like `getfattr` and `setfattr` have them,
+//! POSIX ACLs shown like `getfacl` does, and keeping them when copying.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::details;
+use crate::perms;
+
+/// the attributes holding the access and default POSIX ACLs
+const ACLS: [&str; 2] = ["system.posix_acl_access", "system.posix_acl_default"];
+
+/// the names and values of the extended attributes of the entry at `path`, not following
+/// it if it's a symlink
+pub fn list(path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut attributes = vec![];
+    for name in xattr::list(path)? {
+        // removed since listed
+        if let Some(value) = xattr::get(path, &name)? {
+            attributes.push((name.to_string_lossy().into_owned(), value));
+        }
+    }
+    Ok(attributes)
+}
+
+/// `user::rw-,user:alice:r--,group::r--,mask::r--,other::r--`, if `value` is an ACL as the
+/// kernel stores it: a version, then (tag, permissions, id) entries
+fn acl(value: &[u8]) -> Option<String> {
+    let (version, entries) = (value.get(..4)?, &value[4..]);
+    if version != [2, 0, 0, 0] || entries.len() % 8 != 0 {
+        return None;
+    }
+    let mut shown = vec![];
+    for entry in entries.chunks(8) {
+        let tag = u16::from_le_bytes([entry[0], entry[1]]);
+        let perm = u16::from_le_bytes([entry[2], entry[3]]);
+        let id = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
+        let who = match tag {
+            0x01 => "user:".to_owned(),
+            0x02 => format!("user:{}", details::user_name(id)),
+            0x04 => "group:".to_owned(),
+            0x08 => format!("group:{}", details::group_name(id)),
+            0x10 => "mask:".to_owned(),
+            0x20 => "other:".to_owned(),
+            _ => return None,
+        };
+        let bits = [(4, 'r'), (2, 'w'), (1, 'x')];
+        let rwx: String =
+            bits.iter().map(|&(bit, c)| if perm & bit != 0 { c } else { '-' }).collect();
+        shown.push(format!("{}:{}", who, rwx));
+    }
+    Some(shown.join(","))
+}
+
+/// `name="text"`, `name=0x...` if the value isn't printable, ACLs as text
+pub fn show(name: &str, value: &[u8]) -> String {
+    if ACLS.contains(&name) {
+        if let Some(acl) = acl(value) {
+            return format!("{}={}", name, acl);
+        }
+    }
+    match std::str::from_utf8(value) {
+        Ok(text) if !text.chars().any(char::is_control) => format!("{}=\"{}\"", name, text),
+        _ => {
+            let hex: String = value.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("{}=0x{}", name, hex)
+        }
+    }
+}
+
+/// The name and the value as typed of an attribute as [`show`] shows it, to edit it, text
+/// still in its quotes. ACLs are read-only, `setfacl` edits them.
+pub fn unshow(shown: &str) -> Option<(&str, &str)> {
+    let (name, value) = shown.split_once('=')?;
+    if ACLS.contains(&name) {
+        return None;
+    }
+    Some((name, value))
+}
+
+/// the bytes of a value as typed: text in double quotes, `0x` and hex digits, or else text
+pub fn parse_value(text: &str) -> Result<Vec<u8>, String> {
+    if let Some(quoted) = text.strip_prefix('"').and_then(|text| text.strip_suffix('"')) {
+        return Ok(quoted.as_bytes().to_vec());
+    }
+    let hex = match text.strip_prefix("0x") {
+        Some(hex) => hex,
+        None => return Ok(text.as_bytes().to_vec()),
+    };
+    let digits: Option<Vec<u8>> = hex.chars().map(|c| c.to_digit(16).map(|d| d as u8)).collect();
+    match digits {
+        Some(digits) if digits.len() % 2 == 0 => {
+            Ok(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+        }
+        _ => Err(format!("invalid hex value: {}", text)),
+    }
+}
+
+/// `:xattr set`: sets the attribute `name` of each of `paths`
+pub fn set(paths: &[PathBuf], name: &str, value: &[u8]) -> (usize, Vec<String>) {
+    perms::change_all(paths, false, &mut |path, _| xattr::set(path, name, value))
+}
+
+/// `:xattr rm`: removes the attribute `name` of each of `paths`
+pub fn remove(paths: &[PathBuf], name: &str) -> (usize, Vec<String>) {
+    perms::change_all(paths, false, &mut |path, _| xattr::remove(path, name))
+}
+
+/// Gives `dest` the extended attributes of `src`. The ones `dest` can't have, like
+/// `security.*` ones for a user, or any on a filesystem without them, are left out.
+pub fn copy(src: &Path, dest: &Path) -> io::Result<()> {
+    let attributes = match list(src) {
+        Ok(attributes) => attributes,
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for (name, value) in attributes {
+        match xattr::set(dest, &name, &value) {
+            Err(e) if matches!(e.raw_os_error(), Some(libc::ENOTSUP) | Some(libc::EPERM)) => {}
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_xattrs {
+    use super::*;
+    use crate::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_show() {
+        assert_eq!(show("user.tag", b"blue"), "user.tag=\"blue\"");
+        assert_eq!(show("user.bin", &[0, 0xff]), "user.bin=0x00ff");
+        assert_eq!(unshow("user.tag=\"a=\"b\""), Some(("user.tag", "\"a=\"b\"")));
+        assert_eq!(unshow("user.bin=0x00ff"), Some(("user.bin", "0x00ff")));
+        assert_eq!(parse_value("0x00ff"), Ok(vec![0, 0xff]));
+        assert_eq!(parse_value("blue"), Ok(b"blue".to_vec()));
+        assert_eq!(parse_value("\"a=\"b\""), Ok(b"a=\"b".to_vec()));
+        assert_eq!(parse_value("\""), Ok(b"\"".to_vec()));
+        // what's shown reads back as it was
+        for value in &[&b"0xyz"[..], b"0x00", b"\"quoted\"", &[0, 1]] {
+            let shown = show("user.tag", value);
+            let (_, typed) = unshow(&shown).unwrap();
+            assert_eq!(parse_value(typed).as_deref(), Ok(*value));
+        }
+        assert!(parse_value("0x0").is_err());
+        assert!(parse_value("0xzz").is_err());
+        let mut value = vec![2, 0, 0, 0];
+        for &(tag, perm, id) in &[(0x01u16, 6u16, u32::MAX), (0x08, 4, 0), (0x20, 0, u32::MAX)] {
+            value.extend_from_slice(&tag.to_le_bytes());
+            value.extend_from_slice(&perm.to_le_bytes());
+            value.extend_from_slice(&id.to_le_bytes());
+        }
+        let acl = format!("user::rw-,group:{}:r--,other::---", details::group_name(0));
+        assert_eq!(show(ACLS[0], &value), format!("{}={}", ACLS[0], acl));
+        assert_eq!(unshow(&show(ACLS[0], &value)), None);
+        assert_eq!(show(ACLS[0], &value[..6]), format!("{}=0x{}", ACLS[0], "020000000100"));
+    }
+
+    #[test]
+    fn test_set() {
+        let dir = TestDir::new("xattrs");
+        let (src, dest) = (dir.join("src"), dir.join("dest"));
+        fs::write(&src, "").unwrap();
+        fs::write(&dest, "").unwrap();
+        let (changed, errors) = set(std::slice::from_ref(&src), "user.tag", b"blue");
+        // not every filesystem has user attributes
+        if !errors.is_empty() {
+            assert_eq!(changed, 0);
+            return;
+        }
+        assert_eq!(list(&src).unwrap(), vec![("user.tag".to_owned(), b"blue".to_vec())]);
+        copy(&src, &dest).unwrap();
+        assert_eq!(list(&dest).unwrap(), list(&src).unwrap());
+        assert_eq!(remove(&[src.clone(), dest], "user.tag"), (2, vec![]));
+        assert_eq!(list(&src).unwrap(), vec![]);
+        let (changed, errors) = remove(&[src], "user.tag");
+        assert_eq!((changed, errors.len()), (0, 1));
+    }
+}
@@ -0,0 +1,41 @@
+use std::env;
+use std::path::PathBuf;
+
+fn dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+}
+
+/// `$XDG_DATA_HOME/yukari`
+pub fn data_dir() -> Option<PathBuf> {
+    dir("XDG_DATA_HOME", ".local/share").map(|d| d.join("yukari"))
+}
+
+/// `$XDG_DATA_HOME/Trash`, the home trash of the freedesktop.org trash spec
+pub fn trash_dir() -> Option<PathBuf> {
+    dir("XDG_DATA_HOME", ".local/share").map(|d| d.join("Trash"))
+}
+
+/// `$XDG_CONFIG_HOME`, shared with other programs
+pub fn config_home() -> Option<PathBuf> {
+    dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_CONFIG_HOME/yukari`
+pub fn config_dir() -> Option<PathBuf> {
+    config_home().map(|d| d.join("yukari"))
+}
+
+/// `path` with a leading `~` standing for `$HOME`
+pub fn expand_home(path: &str) -> PathBuf {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest.trim_start_matches('/'),
+        _ => return PathBuf::from(path),
+    };
+    match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join(rest),
+        None => PathBuf::from(path),
+    }
+}
@@ -0,0 +1,295 @@
+//! Named commands, as typed on the command line and bound to keys.
+
+use druid::Command;
+
+use yukari_lib::find::Syntax;
+use yukari_lib::tabs::Layout;
+
+use crate::commands;
+
+/// every command name, for completion
+pub const NAMES: &[&str] = &[
+    "accept",
+    "analyze",
+    "analyze_delete",
+    "back",
+    "bookmark_delete",
+    "bookmark_go",
+    "bookmark_set",
+    "bookmarks",
+    "bottom",
+    "cd",
+    "chmod",
+    "chown",
+    "close",
+    "cmdline",
+    "compare",
+    "compress",
+    "console",
+    "copy_other",
+    "cut",
+    "delete",
+    "down",
+    "du",
+    "dupes",
+    "dupes_link",
+    "dupes_mark",
+    "enter",
+    "extract",
+    "filter",
+    "find",
+    "finder",
+    "focus_other",
+    "forward",
+    "grep",
+    "history",
+    "layout",
+    "leave",
+    "mark",
+    "move_other",
+    "paste",
+    "permissions",
+    "properties",
+    "quit",
+    "ranger_import",
+    "search",
+    "search_next",
+    "search_prev",
+    "set",
+    "shell",
+    "sync",
+    "tab",
+    "tabclose",
+    "tabcopy",
+    "tabmove",
+    "tabnew",
+    "tabnext",
+    "tabprev",
+    "theme",
+    "top",
+    "trash",
+    "up",
+    "visual",
+    "xattr",
+    "yank",
+    "z",
+    "zi",
+];
+
+/// what `cmdline OP` does to the command line
+pub const CMDLINE_OPS: &[&str] = &[
+    "run",
+    "cancel",
+    "complete",
+    "complete_back",
+    "older",
+    "newer",
+    "left",
+    "right",
+    "home",
+    "end",
+    "backspace",
+    "delete",
+    "kill_word",
+    "kill_line",
+];
+
+fn count(arg: &str) -> Result<isize, String> {
+    arg.parse().map_err(|_| format!("not a count: {}", arg))
+}
+
+fn key(arg: &str) -> Result<char, String> {
+    let mut chars = arg.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(format!("not a single key: {}", arg)),
+    }
+}
+
+/// 1-based on the command line, like the tab titles
+fn tab(arg: &str) -> Result<usize, String> {
+    match arg.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(format!("not a tab number: {}", arg)),
+    }
+}
+
+/// `analyze [-x] [-f] [DIR]`
+fn analyze(args: &[&str]) -> Result<Command, String> {
+    let (mut one_filesystem, mut fresh, mut dir) = (false, false, None);
+    for &arg in args {
+        match arg {
+            "-x" => one_filesystem = true,
+            "-f" => fresh = true,
+            _ if arg.starts_with('-') => return Err(format!("analyze: unknown flag {}", arg)),
+            _ if dir.is_none() => dir = Some(arg.to_string()),
+            _ => return Err("analyze: wrong number of arguments".into()),
+        }
+    }
+    Ok(commands::ANALYZE.with((one_filesystem, fresh, dir)))
+}
+
+/// `sync [-a] left|right`
+fn sync(args: &[&str]) -> Result<Command, String> {
+    let (replace, side) = match args {
+        ["-a", side] => (true, *side),
+        [side] => (false, *side),
+        _ => return Err("sync: wrong number of arguments".into()),
+    };
+    match side {
+        "left" => Ok(commands::SYNC.with((false, replace))),
+        "right" => Ok(commands::SYNC.with((true, replace))),
+        _ => Err(format!("sync: expected left or right, not {}", side)),
+    }
+}
+
+/// `find [-r|-f] [-a] PATTERN`
+fn find(args: &[&str]) -> Result<Command, String> {
+    let (mut syntax, mut all, mut pattern) = (Syntax::Glob, false, None);
+    for &arg in args {
+        match arg {
+            "-r" => syntax = Syntax::Regex,
+            "-f" => syntax = Syntax::Fuzzy,
+            "-a" => all = true,
+            _ if arg.starts_with('-') => return Err(format!("find: unknown flag {}", arg)),
+            _ if pattern.is_none() => pattern = Some(arg.to_string()),
+            _ => return Err("find: wrong number of arguments".into()),
+        }
+    }
+    match pattern {
+        Some(pattern) => Ok(commands::FIND.with((syntax, all, pattern))),
+        None => Err("find: no pattern".into()),
+    }
+}
+
+/// `grep [-F] [-i] [-a] PATTERN`
+fn grep(args: &[&str]) -> Result<Command, String> {
+    let (mut literal, mut ignore_case, mut all, mut pattern) = (false, false, false, None);
+    for &arg in args {
+        match arg {
+            "-F" => literal = true,
+            "-i" => ignore_case = true,
+            "-a" => all = true,
+            _ if arg.starts_with('-') => return Err(format!("grep: unknown flag {}", arg)),
+            _ if pattern.is_none() => pattern = Some(arg.to_string()),
+            _ => return Err("grep: wrong number of arguments".into()),
+        }
+    }
+    match pattern {
+        Some(pattern) => Ok(commands::GREP.with((literal, ignore_case, all, pattern))),
+        None => Err("grep: no pattern".into()),
+    }
+}
+
+/// `chmod [-R] MODE` or `chown [-R] OWNER`, modes like `-w` aren't flags
+fn recursive(name: &str, args: &[&str]) -> Result<(bool, String), String> {
+    match args {
+        ["-R", arg] => Ok((true, arg.to_string())),
+        [arg] => Ok((false, arg.to_string())),
+        _ => Err(format!("{}: wrong number of arguments", name)),
+    }
+}
+
+/// the command for already parsed arguments, the first being its name, `shell` and `!` are
+/// handled before parsing by `shell::parse`
+pub fn command(args: &[String]) -> Result<Command, String> {
+    let (name, args) = match args.split_first() {
+        Some(split) => split,
+        None => return Err("empty command".into()),
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let cmd = match (name.as_str(), args.as_slice()) {
+        ("down", []) => commands::MOVE_CURSOR.with(1),
+        ("down", [n]) => commands::MOVE_CURSOR.with(count(n)?),
+        ("up", []) => commands::MOVE_CURSOR.with(-1),
+        ("up", [n]) => commands::MOVE_CURSOR.with(-count(n)?),
+        ("top", []) => commands::MOVE_CURSOR.with(isize::MIN),
+        ("bottom", []) => commands::MOVE_CURSOR.with(isize::MAX),
+        ("enter", []) => commands::ENTER.into(),
+        ("leave", []) => commands::LEAVE.into(),
+        ("cd", []) => commands::CD.with("~".into()),
+        ("cd", [path]) => commands::CD.with(path.to_string()),
+        ("back", []) => commands::BACK.into(),
+        ("forward", []) => commands::FORWARD.into(),
+        ("history", []) => commands::HISTORY.into(),
+        ("mark", []) => commands::TOGGLE_MARK.into(),
+        ("visual", []) => commands::VISUAL.into(),
+        ("yank", []) => commands::YANK.with(true),
+        ("cut", []) => commands::YANK.with(false),
+        ("paste", []) => commands::PASTE.into(),
+        ("accept", []) => commands::ACCEPT.into(),
+        ("close", []) => commands::CLOSE_POPUP.into(),
+        ("search", []) => commands::SEARCH.with(None),
+        ("search", pattern) => commands::SEARCH.with(Some(pattern.join(" "))),
+        ("search_next", []) => commands::SEARCH_NEXT.with(false),
+        ("search_prev", []) => commands::SEARCH_NEXT.with(true),
+        ("console", text) => commands::CONSOLE.with(text.join(" ")),
+        ("cmdline", [op]) if CMDLINE_OPS.contains(op) => commands::CMDLINE.with(op.to_string()),
+        ("cmdline", [op]) => return Err(format!("cmdline: unknown operation {}", op)),
+        ("bookmarks", []) => commands::BOOKMARKS.into(),
+        ("bookmark_set", [k]) => commands::SET_BOOKMARK.with(key(k)?),
+        ("bookmark_go", [k]) => commands::GOTO_BOOKMARK.with(key(k)?),
+        ("bookmark_delete", [k]) => commands::DELETE_BOOKMARK.with(key(k)?),
+        ("compress", [name]) => commands::COMPRESS.with(name.to_string()),
+        ("extract", []) => commands::EXTRACT.into(),
+        ("z", query) => commands::Z.with(query.join(" ")),
+        ("zi", query) => commands::ZI.with(query.join(" ")),
+        ("tabnew", []) => commands::TAB_NEW.into(),
+        ("tabclose", []) => commands::TAB_CLOSE.into(),
+        ("tabnext", []) => commands::TAB_NEXT.into(),
+        ("tabprev", []) => commands::TAB_PREV.into(),
+        ("tab", [n]) => commands::TAB_SELECT.with(tab(n)?),
+        ("tabmove", [n]) => commands::MOVE_TO_TAB.with(tab(n)?),
+        ("tabcopy", [n]) => commands::COPY_TO_TAB.with(tab(n)?),
+        ("copy_other", []) => commands::COPY_TO_OTHER.into(),
+        ("move_other", []) => commands::MOVE_TO_OTHER.into(),
+        ("focus_other", []) => commands::FOCUS_OTHER.into(),
+        ("layout", []) => commands::TOGGLE_LAYOUT.into(),
+        ("layout", ["miller"]) => commands::SET_LAYOUT.with(Layout::Miller),
+        ("layout", ["dual"]) => commands::SET_LAYOUT.with(Layout::Dual),
+        ("layout", [other]) => return Err(format!("unknown layout: {}", other)),
+        ("set", [name, value]) => commands::SET.with((name.to_string(), value.to_string())),
+        ("filter", []) => commands::FILTER.with(None),
+        ("filter", text) => commands::FILTER.with(Some(text.join(" "))),
+        ("theme", []) => commands::THEME.with(None),
+        ("theme", [name]) => commands::THEME.with(Some(name.to_string())),
+        ("du", []) => commands::DU.with(false),
+        ("du", ["-x"]) => commands::DU.with(true),
+        ("du", [flag]) => return Err(format!("du: unknown flag {}", flag)),
+        ("analyze", args) => analyze(args)?,
+        ("find", args) => find(args)?,
+        ("chmod", args) => commands::CHMOD.with(recursive("chmod", args)?),
+        ("chown", args) => commands::CHOWN.with(recursive("chown", args)?),
+        ("permissions", []) => commands::PERMISSIONS.into(),
+        ("properties", []) => commands::PROPERTIES.into(),
+        ("xattr", []) => commands::XATTRS.into(),
+        ("xattr", ["get", name]) => commands::XATTR_GET.with(name.to_string()),
+        ("xattr", ["set", name, value]) => {
+            commands::XATTR_SET.with((name.to_string(), value.to_string()))
+        }
+        ("xattr", ["rm", name]) => commands::XATTR_REMOVE.with(name.to_string()),
+        ("xattr", [op, ..]) if !["get", "set", "rm"].contains(op) => {
+            return Err(format!("xattr: unknown operation {}", op))
+        }
+        ("finder", []) => commands::FINDER.into(),
+        ("grep", args) => grep(args)?,
+        ("dupes", []) => commands::DUPES.into(),
+        ("dupes_mark", []) => commands::MARK_DUPLICATES.into(),
+        ("dupes_link", []) => commands::LINK_DUPLICATES.into(),
+        ("delete", []) => commands::DELETE.into(),
+        ("trash", []) => commands::TRASH.into(),
+        ("compare", []) => commands::COMPARE.with(false),
+        ("compare", ["-c"]) => commands::COMPARE.with(true),
+        ("compare", [flag]) => return Err(format!("compare: unknown flag {}", flag)),
+        ("sync", args) => sync(args)?,
+        ("analyze_delete", []) => commands::ANALYZE_DELETE.into(),
+        ("ranger_import", []) => commands::RANGER_IMPORT.with(None),
+        ("ranger_import", [file]) => commands::RANGER_IMPORT.with(Some(file.to_string())),
+        ("quit", []) => druid::commands::QUIT_APP.into(),
+        (name, _) if NAMES.contains(&name) => {
+            return Err(format!("{}: wrong number of arguments", name))
+        }
+        (name, _) => return Err(format!("unknown command: {}", name)),
+    };
+    Ok(cmd)
+}
@@ -0,0 +1,198 @@
+use druid::{Selector, SingleUse};
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use yukari_lib::analyzer::Scan;
+use yukari_lib::compare::Comparison;
+use yukari_lib::dirinfo::Du;
+use yukari_lib::dupes::Group;
+use yukari_lib::find::{Found, Syntax};
+use yukari_lib::fuzzy::Ranked;
+use yukari_lib::git::Repo;
+use yukari_lib::job::JobEvent;
+use yukari_lib::props::Algorithm;
+use yukari_lib::shell;
+use yukari_lib::tabs::Layout;
+
+/// move the cursor (of the popup if one is shown) by that many lines
+pub const MOVE_CURSOR: Selector<isize> = Selector::new("yukari.move-cursor");
+pub const ENTER: Selector = Selector::new("yukari.enter");
+pub const LEAVE: Selector = Selector::new("yukari.leave");
+/// toggle the mark on the selected entry and move down
+pub const TOGGLE_MARK: Selector = Selector::new("yukari.toggle-mark");
+/// start or end selecting a range of entries, see `State::toggle_visual`
+pub const VISUAL: Selector = Selector::new("yukari.visual");
+/// remember the marked entries (or the selected one) for `PASTE`, to copy if true, else move
+pub const YANK: Selector<bool> = Selector::new("yukari.yank");
+/// copy or move the yanked entries into the current directory
+pub const PASTE: Selector = Selector::new("yukari.paste");
+/// act on the popup's selected line and close it
+pub const ACCEPT: Selector = Selector::new("yukari.accept");
+pub const CLOSE_POPUP: Selector = Selector::new("yukari.close-popup");
+/// `search [PATTERN]`: move to the first entry matching PATTERN and highlight its matches,
+/// see `State::incsearch`, or open the `/` line to type it if `None`
+pub const SEARCH: Selector<Option<String>> = Selector::new("yukari.search");
+/// move to the next match of the last search, the previous one if true
+pub const SEARCH_NEXT: Selector<bool> = Selector::new("yukari.search-next");
+/// open the command line with the given text
+pub const CONSOLE: Selector<String> = Selector::new("yukari.console");
+/// edit or run the open command line, one of `actions::CMDLINE_OPS`
+pub const CMDLINE: Selector<String> = Selector::new("yukari.cmdline");
+
+/// archive the marked entries (or the selected one) under the given name
+pub const COMPRESS: Selector<String> = Selector::new("yukari.compress");
+/// extract the selected archive into a new sibling directory
+pub const EXTRACT: Selector = Selector::new("yukari.extract");
+
+/// relative to the current directory, `~` is home
+pub const CD: Selector<String> = Selector::new("yukari.cd");
+pub const BACK: Selector = Selector::new("yukari.back");
+pub const FORWARD: Selector = Selector::new("yukari.forward");
+/// show the jump list
+pub const HISTORY: Selector = Selector::new("yukari.history");
+/// show the bookmarks
+pub const BOOKMARKS: Selector = Selector::new("yukari.bookmarks");
+/// bookmark the current directory under the given key
+pub const SET_BOOKMARK: Selector<char> = Selector::new("yukari.set-bookmark");
+pub const GOTO_BOOKMARK: Selector<char> = Selector::new("yukari.goto-bookmark");
+/// forget the bookmark under the given key
+pub const DELETE_BOOKMARK: Selector<char> = Selector::new("yukari.delete-bookmark");
+/// jump to the best frecency match for the query
+pub const Z: Selector<String> = Selector::new("yukari.z");
+/// pick among the frecency matches for the query
+pub const ZI: Selector<String> = Selector::new("yukari.zi");
+
+pub const TAB_NEW: Selector = Selector::new("yukari.tab-new");
+pub const TAB_CLOSE: Selector = Selector::new("yukari.tab-close");
+pub const TAB_NEXT: Selector = Selector::new("yukari.tab-next");
+pub const TAB_PREV: Selector = Selector::new("yukari.tab-prev");
+/// zero-based
+pub const TAB_SELECT: Selector<usize> = Selector::new("yukari.tab-select");
+/// move the marked entries (or the selected one) into the directory of the given tab
+pub const MOVE_TO_TAB: Selector<usize> = Selector::new("yukari.move-to-tab");
+/// copy the marked entries (or the selected one) into the directory of the given tab
+pub const COPY_TO_TAB: Selector<usize> = Selector::new("yukari.copy-to-tab");
+/// copy the marked entries (or the selected one) into the directory of the other tab
+pub const COPY_TO_OTHER: Selector = Selector::new("yukari.copy-to-other");
+/// move the marked entries (or the selected one) into the directory of the other tab
+pub const MOVE_TO_OTHER: Selector = Selector::new("yukari.move-to-other");
+/// make the other tab active, the other pane in the dual pane layout
+pub const FOCUS_OTHER: Selector = Selector::new("yukari.focus-other");
+pub const SET_LAYOUT: Selector<Layout> = Selector::new("yukari.set-layout");
+/// switch between the Miller and dual pane layouts
+pub const TOGGLE_LAYOUT: Selector = Selector::new("yukari.toggle-layout");
+
+/// `set NAME VALUE`, see `Config::set`, the listing options only for the active tab
+pub const SET: Selector<(String, String)> = Selector::new("yukari.set");
+/// `filter [TEXT]`: lists only the entries whose names contain `TEXT`, smart case, until
+/// leaving the directory, all of them without it
+pub const FILTER: Selector<Option<String>> = Selector::new("yukari.filter");
+/// switch to the named theme for the session, list the themes if `None`
+pub const THEME: Selector<Option<String>> = Selector::new("yukari.theme");
+/// import ranger's `rc.conf`, the default one if `None`
+pub const RANGER_IMPORT: Selector<Option<String>> = Selector::new("yukari.ranger-import");
+/// a detail view header was clicked: sort by that column, reverse the order if sorted by it
+pub const SORT_BY_COLUMN: Selector<usize> = Selector::new("yukari.sort-by-column");
+/// `du [-x]`: the recursive sizes of the selected or marked directories, with `-x` (`true`)
+/// staying on their filesystems
+pub const DU: Selector<bool> = Selector::new("yukari.du");
+/// sent by the thread computing a directory's size
+pub const DU_DONE: Selector<(PathBuf, Result<Du, String>)> = Selector::new("yukari.du-done");
+/// sent by the thread counting directories' entries
+pub const DIRS_COUNTED: Selector<Vec<(PathBuf, Option<u32>)>> =
+    Selector::new("yukari.dirs-counted");
+/// sent by the thread reading previews, as (the entry previewed, its lines)
+pub const PREVIEW_READ: Selector<(PathBuf, Vec<String>)> = Selector::new("yukari.preview-read");
+/// `chmod [-R] MODE`: change the mode of the marked or selected entries, and of what's in
+/// them if true, see `ModeChange::parse`
+pub const CHMOD: Selector<(bool, String)> = Selector::new("yukari.chmod");
+/// `chown [-R] [USER][:GROUP]`: change their owner or group, or both
+pub const CHOWN: Selector<(bool, String)> = Selector::new("yukari.chown");
+/// `permissions`: show the mode and owner of the marked or selected entries to edit them
+pub const PERMISSIONS: Selector = Selector::new("yukari.permissions");
+/// `properties`: show everything known of the selected entry, see `props::lines`
+pub const PROPERTIES: Selector = Selector::new("yukari.properties");
+/// sent by the thread computing a checksum for the properties popup, as (the file, the
+/// algorithm, the checksum)
+pub const CHECKSUM_DONE: Selector<(PathBuf, Algorithm, Result<String, String>)> =
+    Selector::new("yukari.checksum-done");
+/// `xattr`: list the extended attributes of the selected entry
+pub const XATTRS: Selector = Selector::new("yukari.xattrs");
+/// `xattr get NAME`: show the value of an extended attribute of the selected entry
+pub const XATTR_GET: Selector<String> = Selector::new("yukari.xattr-get");
+/// `xattr set NAME VALUE`: set an extended attribute of the marked or selected entries to
+/// `"text"` or `0x` and hex digits, see `xattrs::parse_value`. POSIX ACLs are read-only here,
+/// `setfacl` edits them.
+pub const XATTR_SET: Selector<(String, String)> = Selector::new("yukari.xattr-set");
+/// `xattr rm NAME`: remove an extended attribute of the marked or selected entries
+pub const XATTR_REMOVE: Selector<String> = Selector::new("yukari.xattr-remove");
+/// sent by a job before it fails, as (what it did, an error per entry it failed on)
+pub const FILE_ERRORS: Selector<(String, Vec<String>)> = Selector::new("yukari.file-errors");
+/// sent by the watcher of the listed directories, see `DirWatcher`
+pub const DIR_CHANGED: Selector<Option<PathBuf>> = Selector::new("yukari.dir-changed");
+/// sent by the thread reading the status of the repository a directory is in, as (the
+/// generation it was read for, see `Delegate::forget_git`, the directory, its repository)
+pub const GIT_STATUS: Selector<(u64, PathBuf, Option<Arc<Repo>>)> =
+    Selector::new("yukari.git-status");
+/// `analyze [-x] [-f] [DIR]` as (`-x`, `-f`, DIR): scans DIR, the current directory if
+/// `None`, for the analyzer. A cancelled scan is resumed unless `-f` or it was started with
+/// a different `-x`, which stays on its filesystem.
+pub const ANALYZE: Selector<(bool, bool, Option<String>)> = Selector::new("yukari.analyze");
+/// delete the entry selected in the analyzer, once confirmed by doing it twice
+pub const ANALYZE_DELETE: Selector = Selector::new("yukari.analyze-delete");
+/// sent by the scanning thread now and then, with the scan's summary
+pub const ANALYZE_PROGRESS: Selector<String> = Selector::new("yukari.analyze-progress");
+/// sent by the scanning thread once done or cancelled
+pub const ANALYZE_DONE: Selector<SingleUse<Scan>> = Selector::new("yukari.analyze-done");
+/// `find [-r|-f] [-a] PATTERN` as (syntax, `-a`, PATTERN): lists the entries under the current
+/// directory matching PATTERN, a glob unless `-r` (regex) or `-f` (fuzzy). `-a` looks into
+/// what `.gitignore` and `.ignore` files ignore.
+pub const FIND: Selector<(Syntax, bool, String)> = Selector::new("yukari.find");
+/// `-F`, `-i`, `-a` and PATTERN of `grep`
+pub type GrepArgs = (bool, bool, bool, String);
+/// `grep [-F] [-i] [-a] PATTERN`: lists the files under the current directory with lines
+/// matching PATTERN, a regex unless `-F`, ignoring case with `-i`. `-a` looks into what
+/// `.gitignore` and `.ignore` files ignore. Binary files and big ones are skipped, see
+/// `GrepOptions`.
+pub const GREP: Selector<GrepArgs> = Selector::new("yukari.grep");
+/// sent by the searching thread as (search id, what it found, whether it's done)
+pub const FOUND: Selector<(u64, Vec<Found>, bool)> = Selector::new("yukari.found");
+/// `dupes`: lists the files under the current directory with the same contents, group after
+/// group, see `dupes::scan`
+pub const DUPES: Selector = Selector::new("yukari.dupes");
+/// sent by the scanning thread now and then as (search id, what it's done)
+pub const DUPES_PROGRESS: Selector<(u64, String)> = Selector::new("yukari.dupes-progress");
+/// sent by the scanning thread once done, as (search id, the groups of duplicates)
+pub const DUPES_FOUND: Selector<(u64, Vec<Group>)> = Selector::new("yukari.dupes-found");
+/// `dupes_mark`: mark the duplicates listed but the first of each group
+pub const MARK_DUPLICATES: Selector = Selector::new("yukari.mark-duplicates");
+/// `dupes_link`: replace the marked duplicates with hardlinks of one of their group that
+/// isn't marked
+pub const LINK_DUPLICATES: Selector = Selector::new("yukari.link-duplicates");
+/// delete the marked entries (or the selected one), once confirmed by doing it twice
+pub const DELETE: Selector = Selector::new("yukari.delete");
+/// move the marked entries (or the selected one) to the trash, see `fileops::trash`
+pub const TRASH: Selector = Selector::new("yukari.trash");
+/// `compare [-c]`: lists what differs between the directories of the two panes, files by
+/// contents with `-c` rather than by size and modification time, see `Comparison`
+pub const COMPARE: Selector<bool> = Selector::new("yukari.compare");
+/// sent by the comparing thread once done, `None` if cancelled
+pub const COMPARED: Selector<SingleUse<Result<Option<Comparison>, String>>> =
+    Selector::new("yukari.compared");
+/// `sync [-a] left|right` as (whether to the right, `-a`): copies what the last comparison
+/// found missing on that side from the other, and what differs with `-a` once sent twice,
+/// but never older files over newer ones nor anything over a directory
+pub const SYNC: Selector<(bool, bool)> = Selector::new("yukari.sync");
+/// `finder`: type to rank the paths under the current directory fzf style, see `fuzzy::Finder`
+pub const FINDER: Selector = Selector::new("yukari.finder");
+/// sent by the finder's walk as (its id, the best matches so far)
+pub const FINDER_RANKED: Selector<(u64, Ranked)> = Selector::new("yukari.finder-ranked");
+/// sent by the watcher of the config file
+pub const CONFIG_CHANGED: Selector = Selector::new("yukari.config-changed");
+
+/// sent by the thread waiting for a shell command
+pub const SHELL_DONE: Selector<shell::Done> = Selector::new("yukari.shell-done");
+
+/// sent from the job queue's worker thread
+pub const JOB_EVENT: Selector<JobEvent> = Selector::new("yukari.job-event");
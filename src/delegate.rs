@@ -0,0 +1,2179 @@
+use druid::{
+    AppDelegate, Command, DelegateCtx, Env, Event, ExtEventSink, Handled, KbKey, KeyEvent,
+    SingleUse, Target, WindowId,
+};
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use yukari_lib::analyzer::{NodeState, Scan};
+use yukari_lib::archive::{self, Format};
+use yukari_lib::bookmarks::{self, Bookmarks};
+use yukari_lib::cmdline::{self, CmdHistory, CmdLine};
+use yukari_lib::compare::{Comparison, Status};
+use yukari_lib::config::Config;
+use yukari_lib::details;
+use yukari_lib::dirinfo::{self, Counter, DirCache, Du};
+use yukari_lib::dupes::{self, Group};
+use yukari_lib::entry;
+use yukari_lib::fileops;
+use yukari_lib::find::{self, Found, Pattern, Query, Search, Syntax};
+use yukari_lib::fmt;
+use yukari_lib::frecency::{self, Frecency};
+use yukari_lib::fuzzy::{Finder, Ranked};
+use yukari_lib::git::Repo;
+use yukari_lib::grep;
+use yukari_lib::job::{JobEvent, JobId, Jobs};
+use yukari_lib::keymap::{self, Feed, Keymap, Mode, Pending};
+use yukari_lib::opener;
+use yukari_lib::perms::{self, ModeChange};
+use yukari_lib::popup::{Popup, PopupKind};
+use yukari_lib::preview::Previewer;
+use yukari_lib::props::{self, Algorithm};
+use yukari_lib::tabs::Layout;
+use yukari_lib::theme::Theme;
+use yukari_lib::ranger;
+use yukari_lib::shell::{self, Macros, ShellCommand};
+use yukari_lib::watch::{self, DirWatcher};
+use yukari_lib::xattrs;
+use yukari_lib::xdg;
+use yukari_lib::{State, Tabs};
+
+use crate::commands::GrepArgs;
+use crate::{actions, commands};
+
+/// re-reading a repository that changed waits this long for more changes
+const GIT_SETTLE: Duration = Duration::from_millis(500);
+
+/// the commands that delete or replace once asked twice
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Doom {
+    Delete,
+    AnalyzeDelete,
+    /// `:sync -a`, to the right or not
+    Sync(bool),
+}
+
+pub struct Delegate {
+    sink: ExtEventSink,
+    jobs: Jobs,
+    /// name of the running job
+    job: String,
+    bookmarks: Bookmarks,
+    keymap: Keymap,
+    /// typed towards a binding of `keymap`
+    keys: Pending,
+    /// entries to paste, and whether to copy rather than move them
+    yanked: Option<(Vec<PathBuf>, bool)>,
+    /// of the command line
+    history: CmdHistory,
+    frecency: Frecency,
+    /// last directory recorded in `frecency`
+    visited: PathBuf,
+    counter: Counter,
+    /// sent to `counter`, not counted yet
+    counting: HashSet<PathBuf>,
+    previewer: Previewer,
+    /// of the listed directories, `None` if it couldn't be started
+    watcher: Option<DirWatcher>,
+    /// the analyzer's scans by root, complete or cancelled, the running one is missing
+    scans: HashMap<PathBuf, Scan>,
+    /// root of the running scan, and how to cancel it
+    scanning: Option<(PathBuf, Arc<AtomicBool>)>,
+    /// entries `analyze_delete` is deleting by job, taken out of their scan once deleted
+    analyze_deleting: HashMap<JobId, PathBuf>,
+    /// the comparison `:compare` shows, what `:sync` copies
+    compared: Option<Comparison>,
+    /// how to cancel the running comparison
+    comparing: Option<Arc<AtomicBool>>,
+    /// the file checksums are computed for, and how to cancel them once its properties are
+    /// closed
+    checksumming: Option<(PathBuf, Arc<AtomicBool>)>,
+    /// to be deleted if the command that asked is run again
+    doomed: Option<(Doom, Vec<PathBuf>)>,
+    /// searches started so far, the last one's id
+    searches: u64,
+    /// id of the running search, and how to cancel it
+    finding: Option<(u64, Arc<AtomicBool>)>,
+    /// while it's open
+    finder: Option<Finder>,
+    /// the repositories the listed directories are in, by directory
+    repos: HashMap<PathBuf, Option<Arc<Repo>>>,
+    /// the directories whose repository is being read
+    reading_git: HashSet<PathBuf>,
+    /// the directories whose repository changed, read again once the changes settle
+    stale_git: HashSet<PathBuf>,
+    /// bumped when some of `repos` is forgotten
+    git_generation: u64,
+    /// what changed at each generation while repositories are being read, those it's in
+    /// aren't kept if they were read before
+    git_changes: Vec<(u64, Option<PathBuf>)>,
+}
+
+impl Delegate {
+    /// reports errors in the config and key bindings to the footer of `tabs`
+    pub fn new(sink: ExtEventSink, tabs: &mut Tabs) -> Self {
+        // the watcher needs the directory, it's fine to miss its creation
+        let watched =
+            Config::default_file().filter(|f| matches!(f.parent(), Some(d) if d.is_dir()));
+        if let Some(file) = watched {
+            let sink = sink.clone();
+            let _ = watch::watch_file(file, move || {
+                let _ = sink.submit_command(commands::CONFIG_CHANGED, (), Target::Auto);
+            });
+        }
+        let job_sink = sink.clone();
+        let jobs = Jobs::new(move |event| {
+            // only fails if the app is shutting down
+            let _ = job_sink.submit_command(commands::JOB_EVENT, event, Target::Auto);
+        });
+        let count_sink = sink.clone();
+        let counter = Counter::new(move |counted| {
+            let _ = count_sink.submit_command(commands::DIRS_COUNTED, counted, Target::Auto);
+        });
+        let preview_sink = sink.clone();
+        let previewer = Previewer::new(move |path, lines| {
+            let read = (path, lines);
+            let _ = preview_sink.submit_command(commands::PREVIEW_READ, read, Target::Auto);
+        });
+        let watch_sink = sink.clone();
+        let watcher = DirWatcher::new(move |changed| {
+            let _ = watch_sink.submit_command(commands::DIR_CHANGED, changed, Target::Auto);
+        });
+        let bookmarks = Bookmarks::default_file().map_or_else(Bookmarks::default, Bookmarks::load);
+        let frecency = Frecency::default_file().map_or_else(Frecency::default, Frecency::load);
+        let history = CmdHistory::default_file().map_or_else(CmdHistory::default, CmdHistory::load);
+        let mut errors = vec![];
+        let config = Self::read_config().unwrap_or_else(|e| {
+            errors.extend(e);
+            Config::default()
+        });
+        let keymap = Self::configure(tabs, config, &mut errors);
+        if !errors.is_empty() {
+            tabs.current_mut().message = format!("error: {}", errors.join("; "));
+        }
+        let mut delegate = Self {
+            sink,
+            jobs,
+            job: String::new(),
+            bookmarks,
+            keymap,
+            keys: Pending::default(),
+            yanked: None,
+            history,
+            frecency,
+            visited: PathBuf::new(),
+            counter,
+            counting: HashSet::new(),
+            previewer,
+            watcher: watcher.ok(),
+            scans: HashMap::new(),
+            scanning: None,
+            analyze_deleting: HashMap::new(),
+            compared: None,
+            comparing: None,
+            checksumming: None,
+            doomed: None,
+            searches: 0,
+            finding: None,
+            finder: None,
+            repos: HashMap::new(),
+            reading_git: HashSet::new(),
+            stale_git: HashSet::new(),
+            git_generation: 0,
+            git_changes: vec![],
+        };
+        delegate.refresh_git(tabs);
+        delegate
+    }
+
+    /// `config.toml`, the defaults if there's none
+    fn read_config() -> Result<Config, Vec<String>> {
+        Config::default_file().map_or_else(|| Ok(Config::default()), |f| Config::load(&f))
+    }
+
+    /// Applies `config` with the ranger `rc.conf` it names and its theme. Returns the key
+    /// bindings: ranger's, then `keys.conf`, then the config's `[keymap]`, over the defaults.
+    fn configure(tabs: &mut Tabs, mut config: Config, errors: &mut Vec<String>) -> Keymap {
+        let mut keymap = Keymap::default();
+        if let Some(file) = &config.ranger_rc {
+            let file = xdg::expand_home(file);
+            match ranger::import_file(&file, &mut keymap, &mut config) {
+                Ok(unsupported) if !unsupported.is_empty() => errors.push(format!(
+                    "{}: {} lines not imported, see :ranger_import",
+                    file.display(),
+                    unsupported.len()
+                )),
+                Ok(_) => (),
+                Err(e) => errors.push(format!("{}: {}", file.display(), e)),
+            }
+        }
+        if let Some(file) = Keymap::default_file() {
+            errors.extend(keymap.apply_file(&file));
+        }
+        config.apply_keymap(&mut keymap);
+        tabs.set_config(Arc::new(config));
+        let theme = tabs.config().theme.clone();
+        if let Err(e) = Self::set_theme(tabs, &theme) {
+            errors.push(e);
+        }
+        keymap
+    }
+
+    /// the config file changed, a broken one is reported and ignored
+    fn reload_config(&mut self, tabs: &mut Tabs) {
+        let mut errors = vec![];
+        match Self::read_config() {
+            Ok(config) => self.keymap = Self::configure(tabs, config, &mut errors),
+            Err(e) => errors = e,
+        }
+        tabs.current_mut().message = if errors.is_empty() {
+            "config reloaded".into()
+        } else {
+            format!("error: {}", errors.join("; "))
+        };
+    }
+
+    /// `:ranger_import`, for the session only, shows what wasn't imported
+    fn ranger_import(&mut self, tabs: &mut Tabs, file: Option<&str>) {
+        let file = match file.map(xdg::expand_home).or_else(ranger::default_file) {
+            Some(file) => tabs.current().current.join(file),
+            None => return,
+        };
+        let mut config = Config::clone(tabs.config());
+        let data = tabs.current_mut();
+        match ranger::import_file(&file, &mut self.keymap, &mut config) {
+            Ok(unsupported) if unsupported.is_empty() => {
+                data.message = format!("imported {}", file.display());
+            }
+            Ok(unsupported) => {
+                let title = format!("{}: {} lines not imported", file.display(), unsupported.len());
+                data.popup = Some(Popup::new(PopupKind::Text, title, unsupported));
+            }
+            Err(e) => {
+                data.message = format!("error: {}: {}", file.display(), e);
+                return;
+            }
+        }
+        tabs.set_config(Arc::new(config));
+    }
+
+    /// The theme `name` with the config's colors over it. Errors leave the theme as it was.
+    fn set_theme(tabs: &mut Tabs, name: &str) -> Result<(), String> {
+        let theme = Theme::load(name)?.with_colors(tabs.config().colors());
+        tabs.set_theme(Arc::new(theme));
+        Ok(())
+    }
+
+    /// `set NAME VALUE`, for the session only, a listing option for the active tab only
+    fn set_option(&mut self, tabs: &mut Tabs, name: &str, value: &str) {
+        let data = tabs.current_mut();
+        if entry::OPTIONS.contains(&name) {
+            let mut listing = data.listing().clone();
+            match listing.set(name, value) {
+                Ok(()) => data.set_listing(listing),
+                Err(e) => data.message = format!("error: {}", e),
+            }
+            return;
+        }
+        let mut config = Config::clone(tabs.config());
+        if let Err(e) = config.set(name, value) {
+            tabs.current_mut().message = format!("error: {}", e);
+            return;
+        }
+        let theme = config.theme.clone();
+        let new_theme = theme != tabs.config().theme;
+        tabs.set_config(Arc::new(config));
+        if new_theme {
+            if let Err(e) = Self::set_theme(tabs, &theme) {
+                tabs.current_mut().message = format!("error: {}", e);
+            }
+        }
+    }
+
+    /// sorts the active tab by the detail view's `column`, in reverse if it's already sorted
+    /// by it
+    fn sort_by_column(data: &mut State, column: usize) {
+        let column = match data.columns().get(column) {
+            Some(column) => *column,
+            None => return,
+        };
+        let mut listing = data.listing().clone();
+        match column.sort_key() {
+            Some(key) if key == listing.sort => listing.sort_reverse = !listing.sort_reverse,
+            Some(key) => {
+                listing.sort = key;
+                listing.sort_reverse = false;
+            }
+            None => {
+                let title = column.title().to_lowercase();
+                data.message = format!("can't sort by {}", title);
+                return;
+            }
+        }
+        data.set_listing(listing);
+    }
+
+    /// enters the selected directory, or opens the selected file
+    fn enter(&mut self, data: &mut State) {
+        match data.selected() {
+            Some(path) if !path.is_dir() => {
+                let command = opener::command_for(&data.config().opener, &path);
+                if let Err(e) = opener::spawn(&command, &data.current) {
+                    data.message = format!("error: {}: {}", command.to_string_lossy(), e);
+                }
+            }
+            _ => data.enter(),
+        }
+    }
+
+    /// records the current directory if it changed since last time
+    fn visit(&mut self, data: &State) {
+        if data.current != self.visited {
+            self.visited = data.current.clone();
+            self.frecency.visit(&data.current, frecency::now());
+            let _ = self.frecency.save_soon(frecency::now());
+        }
+    }
+
+    /// `:z`, jumps to the best match
+    fn z(&mut self, data: &mut State, query: &str) {
+        match self.frecency.query(query, &data.current, frecency::now()).into_iter().next() {
+            Some(path) => self.jump(data, path),
+            None => data.message = format!("z: no match for {:?}", query),
+        }
+    }
+
+    /// `:zi`, lets the user pick among the matches
+    fn zi(&mut self, data: &mut State, query: &str) {
+        let paths = self.frecency.query(query, &data.current, frecency::now());
+        if paths.is_empty() {
+            data.message = format!("z: no match for {:?}", query);
+            return;
+        }
+        let lines = paths.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+        data.popup = Some(Popup::jump(format!("z {}", query), lines, paths));
+    }
+
+    /// `cd` remembering where we came from for `''`
+    fn jump(&mut self, data: &mut State, path: PathBuf) {
+        let from = data.current.clone();
+        data.cd(path);
+        if data.current != from {
+            self.bookmarks.set(bookmarks::LAST, from);
+            let _ = self.bookmarks.save();
+        }
+    }
+
+    fn set_bookmark(&mut self, data: &mut State, key: char) {
+        if !self.bookmarks.set(key, data.current.clone()) {
+            data.message = format!("can't use {:?} as a bookmark", key);
+            return;
+        }
+        data.message = match self.bookmarks.save() {
+            Ok(()) => format!("bookmark {} set", key),
+            Err(e) => format!("error: saving bookmarks: {}", e),
+        };
+    }
+
+    fn goto_bookmark(&mut self, data: &mut State, key: char) {
+        match self.bookmarks.get(key) {
+            Some(path) => {
+                let path = path.to_owned();
+                self.jump(data, path);
+            }
+            None => data.message = format!("no bookmark {}", key),
+        }
+    }
+
+    fn delete_bookmark(&mut self, data: &mut State, key: char) {
+        if self.bookmarks.remove(key).is_none() {
+            data.message = format!("no bookmark {}", key);
+            return;
+        }
+        data.message = match self.bookmarks.save() {
+            Ok(()) => format!("bookmark {} deleted", key),
+            Err(e) => format!("error: saving bookmarks: {}", e),
+        };
+    }
+
+    fn show_bookmarks(&mut self, data: &mut State) {
+        let (lines, paths) = self
+            .bookmarks
+            .iter()
+            .map(|(key, path)| {
+                let missing = if path.is_dir() { "" } else { "  (missing)" };
+                (format!("{}  {}{}", key, path.display(), missing), path.to_owned())
+            })
+            .unzip();
+        data.popup = Some(Popup::jump("bookmarks", lines, paths));
+    }
+
+    fn popup_accept(&mut self, data: &mut State) {
+        match &data.popup {
+            Some(popup) if popup.kind == PopupKind::Jump => {
+                let path = popup.selected_path().map(|p| p.to_owned());
+                data.popup = None;
+                if let Some(path) = path {
+                    self.jump(data, path);
+                }
+            }
+            Some(popup) if popup.kind == PopupKind::Analyzer => self.analyzer_enter(data),
+            Some(popup) if popup.kind == PopupKind::Permissions => self.permissions_accept(data),
+            Some(popup) if popup.kind == PopupKind::Properties => self.properties_accept(data),
+            Some(popup) if popup.kind == PopupKind::Compare => self.compare_accept(data),
+            _ => data.popup_accept(),
+        }
+    }
+
+    /// `:chmod` of `targets` in the background, the errors are listed if there are any
+    fn chmod(&mut self, data: &mut State, targets: Vec<PathBuf>, recursive: bool, spec: &str) {
+        let mode = match ModeChange::parse(spec) {
+            Ok(mode) => mode,
+            Err(e) => {
+                data.message = format!("error: chmod: {}", e);
+                return;
+            }
+        };
+        let (what, sink) = (format!("chmod {}", spec), self.sink.clone());
+        self.jobs.push(what.clone(), move |_| {
+            let (changed, errors) = perms::chmod(&targets, &mode, recursive);
+            Self::changed(&sink, what, changed, errors)
+        });
+    }
+
+    /// `:chown` of the marked or selected entries
+    fn chown(&mut self, data: &mut State, recursive: bool, spec: &str) {
+        let (uid, gid) = match perms::parse_owner(spec) {
+            Ok(owner) => owner,
+            Err(e) => {
+                data.message = format!("error: chown: {}", e);
+                return;
+            }
+        };
+        let targets = data.targets();
+        let (what, sink) = (format!("chown {}", spec), self.sink.clone());
+        self.jobs.push(what.clone(), move |_| {
+            let (changed, errors) = perms::chown(&targets, uid, gid, recursive);
+            Self::changed(&sink, what, changed, errors)
+        });
+    }
+
+    /// the summary of a job changing entries, the errors are sent to be listed
+    fn changed(
+        sink: &ExtEventSink,
+        what: String,
+        changed: usize,
+        errors: Vec<String>,
+    ) -> Result<String, String> {
+        if errors.is_empty() {
+            return Ok(format!("{}: {} entries changed", what, changed));
+        }
+        let summary = format!("{}: {} entries changed, {} failed", what, changed, errors.len());
+        let _ = sink.submit_command(commands::FILE_ERRORS, (what, errors), Target::Auto);
+        Err(summary)
+    }
+
+    /// the permissions popup for `paths`, showing the first's
+    fn show_permissions(data: &mut State, paths: Vec<PathBuf>, cursor: usize) {
+        let first = match paths.first() {
+            Some(first) => first,
+            None => {
+                data.message = "permissions: nothing selected".into();
+                return;
+            }
+        };
+        let meta = match fs::symlink_metadata(first) {
+            Ok(meta) => meta,
+            Err(e) => {
+                data.message = format!("error: {}: {}", first.display(), e);
+                return;
+            }
+        };
+        let name = first.file_name().unwrap_or(first.as_os_str()).to_string_lossy();
+        let title = match paths.len() {
+            1 => format!("permissions of {}", name),
+            n => format!("permissions of {} entries, as {}", n, name),
+        };
+        let mut popup = Popup::new(PopupKind::Permissions, title, perms::lines(&meta));
+        popup.paths = Arc::new(paths);
+        popup.move_cursor(cursor as isize);
+        data.popup = Some(popup);
+    }
+
+    /// toggles the bit of the line under the cursor, or edits the mode or owner on the
+    /// command line
+    fn permissions_accept(&mut self, data: &mut State) {
+        let (paths, cursor) = match &data.popup {
+            Some(popup) => (popup.paths.to_vec(), popup.cursor),
+            None => return,
+        };
+        let meta = match paths.first().map(fs::symlink_metadata) {
+            Some(Ok(meta)) => meta,
+            _ => return,
+        };
+        let owner = perms::BITS.len() + 1;
+        let line = match cursor {
+            0 => format!("chmod {:04o}", meta.mode() & 0o7777),
+            _ if cursor == owner => format!("chown {}", details::user_name(meta.uid())),
+            _ if cursor == owner + 1 => format!("chown :{}", details::group_name(meta.gid())),
+            _ => match perms::toggle(cursor, meta.mode()) {
+                Some(clause) => return self.chmod(data, paths, false, &clause),
+                None => return,
+            },
+        };
+        let _ = self.sink.submit_command(commands::CONSOLE, line, Target::Auto);
+    }
+
+    /// the properties popup for the entry at `path`, with the checksums of the one it replaces
+    /// if it's of the same file and the file didn't change
+    fn show_properties(data: &mut State, path: PathBuf, cursor: usize) {
+        match props::lines(&path) {
+            Ok(mut lines) => {
+                if let Some(popup) = &data.popup {
+                    if popup.kind == PopupKind::Properties && popup.paths.first() == Some(&path) {
+                        props::keep_checksums(&popup.lines, &mut lines);
+                    }
+                }
+                let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                let title = format!("properties of {}", name);
+                let mut popup = Popup::new(PopupKind::Properties, title, lines);
+                popup.paths = Arc::new(vec![path]);
+                popup.move_cursor(cursor as isize);
+                data.popup = Some(popup);
+            }
+            Err(e) => data.message = format!("error: {}: {}", path.display(), e),
+        }
+    }
+
+    /// computes the checksum of the line under the cursor in the background, edits the
+    /// extended attribute on the command line, or copies the line's value to the clipboard
+    fn properties_accept(&mut self, data: &mut State) {
+        let popup = match &mut data.popup {
+            Some(popup) => popup,
+            None => return,
+        };
+        let (label, value) = match popup.lines.get(popup.cursor) {
+            Some(line) => props::split(line),
+            None => return,
+        };
+        let (label, value) = (label.to_owned(), value.to_owned());
+        let attribute = if label == "xattr" { xattrs::unshow(&value) } else { None };
+        match (Algorithm::from_name(&label), attribute) {
+            (Some(algorithm), _) if value == props::PENDING => {
+                let path = match popup.paths.first() {
+                    Some(path) => path.clone(),
+                    None => return,
+                };
+                Arc::make_mut(&mut popup.lines)[popup.cursor] =
+                    props::line(&label, props::COMPUTING);
+                let cancel = match &self.checksumming {
+                    Some((file, cancel)) if *file == path => cancel.clone(),
+                    _ => Arc::new(AtomicBool::new(false)),
+                };
+                self.checksumming = Some((path.clone(), cancel.clone()));
+                let sink = self.sink.clone();
+                thread::spawn(move || {
+                    let checksum = match props::checksum(&path, algorithm, &cancel) {
+                        Ok(Some(checksum)) => Ok(checksum),
+                        // the popup is closed
+                        Ok(None) => return,
+                        Err(e) => Err(e.to_string()),
+                    };
+                    let done = (path, algorithm, checksum);
+                    let _ = sink.submit_command(commands::CHECKSUM_DONE, done, Target::Auto);
+                });
+            }
+            (Some(_), _) if value == props::COMPUTING => {}
+            (_, Some((name, typed))) => {
+                let line = format!("xattr set {} {}", cmdline::quote(name), cmdline::quote(typed));
+                let _ = self.sink.submit_command(commands::CONSOLE, line, Target::Auto);
+            }
+            _ => {
+                druid::Application::global().clipboard().put_string(&value);
+                data.message = format!("copied {}", label);
+            }
+        }
+    }
+
+    /// shows a computed checksum in the tabs still showing the properties of its file
+    fn checksum_done(tabs: &mut Tabs, path: &Path, algorithm: Algorithm, checksum: &str) {
+        let shows = |state: &State| match &state.popup {
+            Some(popup) => {
+                popup.kind == PopupKind::Properties
+                    && popup.paths.first().map(PathBuf::as_path) == Some(path)
+            }
+            None => false,
+        };
+        let showing: Vec<usize> =
+            (0..tabs.count()).filter(|&i| matches!(tabs.get(i), Some(s) if shows(s))).collect();
+        for i in showing {
+            let popup = match tabs.get_mut(i).and_then(|data| data.popup.as_mut()) {
+                Some(popup) => popup,
+                None => continue,
+            };
+            // it's pending again if the file changed meanwhile
+            let name = algorithm.name();
+            let computing = |line: &String| props::split(line) == (name, props::COMPUTING);
+            if let Some(line) = popup.lines.iter().position(computing) {
+                Arc::make_mut(&mut popup.lines)[line] = props::line(name, checksum);
+            }
+        }
+    }
+
+    /// cancels the checksums being computed if no tab shows the properties of their file
+    fn cancel_checksums(&mut self, tabs: &Tabs) {
+        let file = match &self.checksumming {
+            Some((file, _)) => file,
+            None => return,
+        };
+        let shows = |state: &State| match &state.popup {
+            Some(popup) => popup.kind == PopupKind::Properties && popup.paths.first() == Some(file),
+            None => false,
+        };
+        if !(0..tabs.count()).any(|i| matches!(tabs.get(i), Some(state) if shows(state))) {
+            if let Some((_, cancel)) = self.checksumming.take() {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// `:xattr`, the extended attributes of the selected entry in a popup
+    fn show_xattrs(data: &mut State) {
+        let path = match data.selected() {
+            Some(path) => path,
+            None => {
+                data.message = "xattr: nothing selected".into();
+                return;
+            }
+        };
+        let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+        match xattrs::list(&path) {
+            Ok(attributes) if attributes.is_empty() => {
+                data.message = format!("no extended attributes: {}", name);
+            }
+            Ok(attributes) => {
+                let lines = attributes.iter().map(|(name, value)| xattrs::show(name, value));
+                let title = format!("extended attributes of {}", name);
+                data.popup = Some(Popup::new(PopupKind::Text, title, lines.collect()));
+            }
+            Err(e) => data.message = format!("error: {}: {}", path.display(), e),
+        }
+    }
+
+    /// `:xattr get`, the value of an extended attribute of the selected entry as a message
+    fn xattr_get(data: &mut State, name: &str) {
+        let path = match data.selected() {
+            Some(path) => path,
+            None => {
+                data.message = "xattr: nothing selected".into();
+                return;
+            }
+        };
+        data.message = match xattrs::list(&path) {
+            Ok(attributes) => match attributes.iter().find(|(n, _)| n == name) {
+                Some((name, value)) => xattrs::show(name, value),
+                None => format!("error: xattr: no attribute {}", name),
+            },
+            Err(e) => format!("error: {}: {}", path.display(), e),
+        };
+    }
+
+    /// `:xattr set`, or `:xattr rm` if `value` is `None`, of the marked or selected entries
+    fn xattr_change(&mut self, data: &mut State, name: &str, value: Option<&str>) {
+        let value = match value.map(xattrs::parse_value).transpose() {
+            Ok(value) => value,
+            Err(e) => {
+                data.message = format!("error: xattr: {}", e);
+                return;
+            }
+        };
+        let (targets, name, sink) = (data.targets(), name.to_owned(), self.sink.clone());
+        let verb = if value.is_some() { "set" } else { "rm" };
+        let what = format!("xattr {} {}", verb, name);
+        self.jobs.push(what.clone(), move |_| {
+            let (changed, errors) = match &value {
+                Some(value) => xattrs::set(&targets, &name, value),
+                None => xattrs::remove(&targets, &name),
+            };
+            Self::changed(&sink, what, changed, errors)
+        });
+    }
+
+    fn compress(&mut self, data: &mut State, name: &str) {
+        let sources = data.targets();
+        if sources.is_empty() {
+            data.message = "nothing to compress".into();
+            return;
+        }
+        // not written anywhere but the current directory
+        let mut components = Path::new(name).components();
+        let single = matches!(components.next(), Some(Component::Normal(_)));
+        if name.contains('/') || !single || components.next().is_some() {
+            data.message = format!("error: compress: not a file name: {}", name);
+            return;
+        }
+        let mut dest = data.current.join(name);
+        if Format::from_path(&dest).is_none() {
+            dest = data.current.join(format!("{}.{}", name, Format::Zip.extension()));
+        }
+        self.jobs.push(format!("compress {}", name), move |progress| {
+            archive::compress(&sources, &dest, |done, total| progress.report(done, total))
+                .map(|links| match links {
+                    0 => format!("created {}", dest.display()),
+                    _ => format!("created {}, {} symlinks left out", dest.display(), links),
+                })
+                .map_err(|e| format!("{}: {}", dest.display(), e))
+        });
+    }
+
+    /// copies or moves the targets of the active tab into the directory of tab `index`
+    fn transfer_to_tab(&mut self, tabs: &mut Tabs, index: Option<usize>, keep: bool) {
+        let dir = match index.and_then(|i| tabs.get(i)) {
+            Some(tab) => tab.current.clone(),
+            None => {
+                tabs.current_mut().message = "no such tab".into();
+                return;
+            }
+        };
+        let sources = tabs.current().targets();
+        self.transfer(sources, dir, keep, tabs.config().preserve_xattrs);
+    }
+
+    /// copies (or moves, unless `keep`) `sources` into `dir` in a job, with their extended
+    /// attributes if `xattrs`
+    fn transfer(&mut self, sources: Vec<PathBuf>, dir: PathBuf, keep: bool, xattrs: bool) {
+        if sources.is_empty() {
+            return;
+        }
+        let verb = if keep { "copy" } else { "move" };
+        self.jobs.push(format!("{} to {}", verb, dir.display()), move |progress| {
+            let report = |done, total| progress.report(done, total);
+            let done = if keep {
+                fileops::copy_into(&sources, &dir, xattrs, report)
+            } else {
+                fileops::move_into(&sources, &dir, xattrs, report)
+            };
+            done.map(|done| format!("{}: {} entries to {}", verb, done.len(), dir.display()))
+                .map_err(|e| e.to_string())
+        });
+    }
+
+    fn extract(&mut self, data: &mut State) {
+        let archive = match data.selected() {
+            Some(path) if Format::from_path(&path).is_some() => path,
+            _ => {
+                data.message = "not an archive".into();
+                return;
+            }
+        };
+        let name = archive.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        self.jobs.push(format!("extract {}", name), move |progress| {
+            archive::extract(&archive, |done, total| progress.report(done, total))
+                .map(|dest| format!("extracted to {}", dest.display()))
+                .map_err(|e| format!("{}: {}", archive.display(), e))
+        });
+    }
+
+    /// `:shell` and `!`, expanding the macros for the active tab
+    fn shell(&mut self, tabs: &mut Tabs, shell: ShellCommand) {
+        let data = tabs.current();
+        let macros = Macros {
+            selected: data.selected(),
+            targets: data.targets(),
+            dir: data.current.clone(),
+            other: tabs.other_index().and_then(|i| tabs.get(i)).map(|t| t.current.clone()),
+        };
+        let command = match shell::expand(&shell.command, &macros) {
+            Ok(command) => command,
+            Err(e) => {
+                tabs.current_mut().message = format!("error: {}", e);
+                return;
+            }
+        };
+        let dir = macros.dir;
+        let name = command.to_string_lossy().into_owned();
+        if shell.mode == shell::Mode::Detached {
+            tabs.current_mut().message = match shell::detach(&command, &dir) {
+                Ok(()) => format!("started {}", name),
+                Err(e) => format!("error: {}: {}", name, e),
+            };
+            return;
+        }
+        tabs.current_mut().message = format!("running {}", name);
+        let capture = shell.mode == shell::Mode::Pager;
+        let sink = self.sink.clone();
+        thread::spawn(move || {
+            let (result, output) = match shell::run(&command, &dir, capture) {
+                Ok((status, lines)) if status.success() => (Ok(shell::describe(status)), lines),
+                Ok((status, lines)) => (Err(shell::describe(status)), lines),
+                Err(e) => (Err(e.to_string()), vec![]),
+            };
+            let output = if capture { Some(output) } else { None };
+            let done = shell::Done { command: name, result, output };
+            let _ = sink.submit_command(commands::SHELL_DONE, done, Target::Auto);
+        });
+    }
+
+    /// Watches the listed directories and the ones they list, and counts what these have
+    /// if they aren't yet.
+    fn refresh_dirs(&mut self, tabs: &Tabs) {
+        let states = (0..tabs.count()).filter_map(|i| tabs.get(i));
+        let mut listed = vec![];
+        let mut uncounted = vec![];
+        for state in states {
+            listed.push(state.current.clone());
+            listed.extend(state.parent.clone());
+            uncounted.extend(state.uncounted().filter(|dir| !self.counting.contains(dir)));
+        }
+        if let Some(watcher) = &mut self.watcher {
+            let subdirs = (0..tabs.count()).filter_map(|i| tabs.get(i)).flat_map(|state| {
+                let dirs = state.entries().iter().filter(|e| e.is_dir());
+                dirs.map(move |e| e.path(&state.current))
+            });
+            watcher.set(listed.into_iter().chain(subdirs));
+        }
+        if !uncounted.is_empty() {
+            self.counting.extend(uncounted.iter().cloned());
+            self.counter.count(uncounted);
+        }
+    }
+
+    /// reads the preview of the selected entry of `data` off the UI thread, if it isn't
+    /// shown yet
+    fn refresh_preview(&self, data: &mut State) {
+        if let Some(request) = data.refresh_preview() {
+            self.previewer.read(request);
+        }
+    }
+
+    fn dirs_counted(&mut self, tabs: &mut Tabs, counted: &[(PathBuf, Option<u32>)]) {
+        let mut dirs = DirCache::clone(tabs.dirs());
+        for (dir, count) in counted {
+            self.counting.remove(dir);
+            dirs.set_count(dir.clone(), *count);
+        }
+        tabs.set_dirs(Arc::new(dirs));
+    }
+
+    /// Gives the tabs the status of the repository they're in, and reads it off the UI
+    /// thread if it isn't known yet.
+    fn refresh_git(&mut self, tabs: &mut Tabs) {
+        for i in 0..tabs.count() {
+            let state = match tabs.get_mut(i) {
+                Some(state) => state,
+                None => continue,
+            };
+            if let Some(repo) = self.repos.get(&state.current) {
+                state.set_git(repo.clone());
+                continue;
+            }
+            let dir = state.current.clone();
+            if !self.reading_git.insert(dir.clone()) {
+                continue;
+            }
+            let (sink, generation) = (self.sink.clone(), self.git_generation);
+            let stale = self.stale_git.remove(&dir);
+            thread::spawn(move || {
+                if stale {
+                    thread::sleep(GIT_SETTLE);
+                }
+                // a broken repository is left undecorated
+                let repo = Repo::open(&dir).ok().flatten().map(Arc::new);
+                let read = (generation, dir, repo);
+                let _ = sink.submit_command(commands::GIT_STATUS, read, Target::Auto);
+            });
+        }
+    }
+
+    fn git_read(
+        &mut self,
+        tabs: &mut Tabs,
+        generation: u64,
+        dir: &Path,
+        repo: &Option<Arc<Repo>>,
+    ) {
+        self.reading_git.remove(dir);
+        let mut changes = self.git_changes.iter().filter(|(changed, _)| *changed > generation);
+        // shown until it's read again
+        if changes.any(|(_, changed)| in_repo(changed, dir, repo)) {
+            self.stale_git.insert(dir.to_owned());
+        } else {
+            self.repos.insert(dir.to_owned(), repo.clone());
+        }
+        if self.reading_git.is_empty() {
+            self.git_changes.clear();
+        }
+        for i in 0..tabs.count() {
+            match tabs.get_mut(i) {
+                Some(state) if state.current == dir => state.set_git(repo.clone()),
+                _ => (),
+            }
+        }
+    }
+
+    /// the status of the repositories `changed` is in, or of all if `None`, is read again
+    /// once the changes settle
+    fn forget_git(&mut self, changed: Option<&Path>) {
+        let changed = changed.map(Path::to_owned);
+        let repos = self.repos.iter().filter(|(dir, repo)| in_repo(&changed, dir, repo));
+        let forgotten: Vec<PathBuf> = repos.map(|(dir, _)| dir.clone()).collect();
+        for dir in forgotten {
+            self.repos.remove(&dir);
+            self.stale_git.insert(dir);
+        }
+        self.git_generation += 1;
+        if !self.reading_git.is_empty() {
+            self.git_changes.push((self.git_generation, changed));
+        }
+    }
+
+    /// forgets the status of the repositories the tabs are in, after a command or a job
+    /// that may have changed anything there
+    fn forget_shown_git(&mut self, tabs: &Tabs) {
+        for i in 0..tabs.count() {
+            if let Some(state) = tabs.get(i) {
+                self.forget_git(Some(&state.current));
+            }
+        }
+    }
+
+    /// forgets what's known of what changed, and lists it again
+    fn dir_changed(&mut self, tabs: &mut Tabs, changed: Option<&Path>) {
+        self.forget_git(changed);
+        let mut dirs = DirCache::clone(tabs.dirs());
+        dirs.invalidate(changed);
+        tabs.set_dirs(Arc::new(dirs));
+        tabs.reload(changed);
+    }
+
+    /// `:du`, computes the sizes of the selected or marked directories one after the other,
+    /// off the UI thread
+    fn du(&mut self, data: &mut State, one_filesystem: bool) {
+        let is_dir = |p: &PathBuf| matches!(p.symlink_metadata(), Ok(m) if m.is_dir());
+        let dirs: Vec<PathBuf> = data.targets().into_iter().filter(is_dir).collect();
+        if dirs.is_empty() {
+            data.message = "du: no directory selected".into();
+            return;
+        }
+        data.message = format!("du: computing {} directories", dirs.len());
+        let sink = self.sink.clone();
+        thread::spawn(move || {
+            for dir in dirs {
+                let du = dirinfo::du(&dir, one_filesystem).map_err(|e| e.to_string());
+                let _ = sink.submit_command(commands::DU_DONE, (dir, du), Target::Auto);
+            }
+        });
+    }
+
+    fn du_done(&mut self, tabs: &mut Tabs, dir: &Path, du: &Result<Du, String>) {
+        let name = dir.file_name().unwrap_or(dir.as_os_str()).to_string_lossy();
+        tabs.current_mut().message = match du {
+            Ok(du) => {
+                let mut dirs = DirCache::clone(tabs.dirs());
+                dirs.set_du(dir.to_owned(), *du);
+                tabs.set_dirs(Arc::new(dirs));
+                format!("{}: {}", name, du.describe())
+            }
+            Err(e) => format!("error: du: {}: {}", name, e),
+        };
+    }
+
+    /// `:analyze`, shows the scan of `dir`, scanning it first if it isn't complete
+    fn analyze(&mut self, data: &mut State, one_filesystem: bool, fresh: bool, dir: Option<&str>) {
+        let root = match dir {
+            Some(dir) => data.current.join(xdg::expand_home(dir)),
+            None => data.current.clone(),
+        };
+        if let Some((scanning, _)) = &self.scanning {
+            data.message = format!("analyze: already scanning {}", scanning.display());
+            return;
+        }
+        // a scan with or without `-x` isn't the one asked for
+        let cached = self.scans.remove(&root).filter(|s| s.one_filesystem() == one_filesystem);
+        let scan = match cached {
+            Some(scan) if !fresh && scan.is_complete() => {
+                self.scans.insert(root.clone(), scan);
+                return self.show_scan(data, &root, None);
+            }
+            Some(scan) if !fresh => Ok(scan),
+            _ => Scan::new(root.clone(), one_filesystem),
+        };
+        let mut scan = match scan {
+            Ok(scan) => scan,
+            Err(e) => {
+                data.message = format!("error: analyze: {}: {}", root.display(), e);
+                return;
+            }
+        };
+        let title = format!("scanning {}, close to cancel", root.display());
+        data.popup = Some(Popup::new(PopupKind::Analyzer, title, vec![]));
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.scanning = Some((root, cancel.clone()));
+        let sink = self.sink.clone();
+        thread::spawn(move || {
+            scan.run(&cancel, |scan| {
+                let summary = scan.summary();
+                let _ = sink.submit_command(commands::ANALYZE_PROGRESS, summary, Target::Auto);
+            });
+            let done = SingleUse::new(scan);
+            let _ = sink.submit_command(commands::ANALYZE_DONE, done, Target::Auto);
+        });
+    }
+
+    /// the scan `path` is in, the deepest one if they're nested
+    fn scan_of(&self, path: &Path) -> Option<&Scan> {
+        let scans = self.scans.values().filter(|scan| path.starts_with(&scan.root));
+        scans.max_by_key(|scan| scan.root.components().count())
+    }
+
+    /// the analyzer on `dir`, with the cursor on `selected`
+    fn show_scan(&self, data: &mut State, dir: &Path, selected: Option<&Path>) {
+        let (scan, node) = match self.scan_of(dir).and_then(|s| Some((s, s.find(dir)?))) {
+            Some(found) => found,
+            None => {
+                data.popup = None;
+                return;
+            }
+        };
+        let children = scan.children(node);
+        let mut lines = vec!["/..".to_owned()];
+        lines.extend(children.iter().map(|&child| scan.line(child)));
+        let mut paths = vec![dir.to_owned()];
+        paths.extend(children.iter().map(|&child| scan.path(child)));
+        let size = fmt::human_size(scan.node(node).bytes);
+        let title = format!("{}: {} ({})", dir.display(), size, scan.summary());
+        let mut popup = Popup::new(PopupKind::Analyzer, title, lines);
+        popup.cursor = selected.and_then(|s| paths.iter().rposition(|p| p == s)).unwrap_or(0);
+        popup.paths = Arc::new(paths);
+        data.popup = Some(popup);
+    }
+
+    /// the tab whose analyzer waits for the running scan, there's only one scan at a time
+    fn scan_tab(tabs: &mut Tabs) -> Option<&mut State> {
+        let waiting = |state: &State| match &state.popup {
+            Some(popup) => popup.kind == PopupKind::Analyzer && popup.paths.is_empty(),
+            None => false,
+        };
+        let tab = (0..tabs.count()).find(|&i| matches!(tabs.get(i), Some(s) if waiting(s)))?;
+        tabs.get_mut(tab)
+    }
+
+    fn analyze_progress(&mut self, tabs: &mut Tabs, summary: &str) {
+        if let Some(data) = Self::scan_tab(tabs) {
+            data.message = format!("scanning {}", summary);
+        }
+    }
+
+    /// keeps the scan, shows it in the tab waiting for it if any
+    fn analyze_done(&mut self, tabs: &mut Tabs, scan: Option<Scan>) {
+        self.scanning = None;
+        let scan = match scan {
+            Some(scan) => scan,
+            None => return,
+        };
+        let root = scan.root.clone();
+        let message = if scan.is_complete() {
+            format!("scanned {}", scan.summary())
+        } else {
+            format!("cancelled, :analyze resumes {}", scan.summary())
+        };
+        self.scans.insert(root.clone(), scan);
+        if let Some(data) = Self::scan_tab(tabs) {
+            data.message = message;
+            self.show_scan(data, &root, None);
+        }
+    }
+
+    /// drills down into the selected directory, or up from `/..`
+    fn analyzer_enter(&mut self, data: &mut State) {
+        let popup = match &data.popup {
+            Some(popup) => popup,
+            None => return,
+        };
+        if popup.cursor == 0 {
+            return self.analyzer_leave(data);
+        }
+        let path = match popup.selected_path() {
+            Some(path) => path.to_owned(),
+            None => return,
+        };
+        let scan = self.scan_of(&path);
+        let node = scan.and_then(|scan| Some(scan.node(scan.find(&path)?)));
+        if matches!(node, Some(node) if node.is_dir && node.state != NodeState::Unreadable) {
+            self.show_scan(data, &path, None);
+        }
+    }
+
+    /// goes up to the parent directory, closes the analyzer from the scan's root
+    fn analyzer_leave(&mut self, data: &mut State) {
+        let dir = match data.popup.as_ref().and_then(|p| p.paths.first()) {
+            Some(dir) => dir.clone(),
+            None => return,
+        };
+        match (self.scan_of(&dir), dir.parent()) {
+            (Some(scan), Some(parent)) if dir != scan.root => {
+                self.show_scan(data, parent, Some(&dir))
+            }
+            _ => data.popup = None,
+        }
+    }
+
+    /// deletes the selected entry from the disk, the second time it's asked
+    fn analyze_delete(&mut self, data: &mut State) {
+        let popup = match &data.popup {
+            Some(popup) if popup.kind == PopupKind::Analyzer && popup.cursor > 0 => popup,
+            _ => {
+                data.message = "analyze_delete: no entry of the analyzer selected".into();
+                return;
+            }
+        };
+        let path = match popup.selected_path() {
+            Some(path) => path.to_owned(),
+            None => return,
+        };
+        let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned();
+        let doomed = (Doom::AnalyzeDelete, vec![path.clone()]);
+        if self.doomed.as_ref() != Some(&doomed) {
+            data.message = format!("delete {}? press d again to confirm", name);
+            self.doomed = Some(doomed);
+            return;
+        }
+        self.doomed = None;
+        let deleted = path.clone();
+        let job = self.jobs.push(format!("delete {}", name), move |_| {
+            fileops::remove_all(&path)
+                .map(|()| format!("deleted {}", name))
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        });
+        self.analyze_deleting.insert(job, deleted);
+    }
+
+    /// takes what the job `id` deleted out of its scan, or what's left of it if it failed
+    fn analyze_deleted(&mut self, id: JobId, deleted: bool) {
+        let path = match self.analyze_deleting.remove(&id) {
+            Some(path) => path,
+            None => return,
+        };
+        let root = self.scan_of(&path).map(|scan| scan.root.clone());
+        let scan = match root.and_then(|root| self.scans.get_mut(&root)) {
+            Some(scan) => scan,
+            None => return,
+        };
+        match scan.find(&path) {
+            Some(node) if deleted => scan.remove(node),
+            Some(node) => scan.forget(node),
+            None => {}
+        }
+    }
+
+    /// `:find`, lists the matches under the current directory as they're found
+    fn find(&mut self, data: &mut State, syntax: Syntax, all: bool, pattern: &str) {
+        let query = match Pattern::new(syntax, pattern) {
+            Ok(pattern) => {
+                Query { pattern, hidden: data.listing().show_hidden, ignored: all }
+            }
+            Err(e) => {
+                data.message = format!("error: find: {}", e);
+                return;
+            }
+        };
+        self.search(data, format!("find {}", pattern), move |root, cancel, found| {
+            find::find(root, &query, cancel, found)
+        });
+    }
+
+    /// `:grep`, lists the files under the current directory with lines matching, the
+    /// preview shows them
+    fn grep(&mut self, data: &mut State, (literal, ignore_case, all, pattern): &GrepArgs) {
+        let query = match grep::regex(pattern, *literal, *ignore_case) {
+            Ok(regex) => grep::Query {
+                regex,
+                hidden: data.listing().show_hidden,
+                ignored: *all,
+                max_size: data.config().grep.max_size,
+            },
+            Err(e) => {
+                data.message = format!("error: grep: {}", e);
+                return;
+            }
+        };
+        self.search(data, format!("grep {}", pattern), move |root, cancel, found| {
+            grep::grep(root, &query, cancel, found)
+        });
+    }
+
+    /// Lists the results of a new search in place of the current directory's entries, none
+    /// yet, and cancels the running one. Returns its id and how to cancel it.
+    fn start_search(&mut self, data: &mut State, query: String) -> (u64, Arc<AtomicBool>) {
+        if let Some((_, cancel)) = self.finding.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        self.searches += 1;
+        let id = self.searches;
+        data.show_search(Search::new(id, query));
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.finding = Some((id, cancel.clone()));
+        (id, cancel)
+    }
+
+    /// Lists what `run` finds under the current directory in place of its entries, as it's
+    /// found. `run` is cancelled if another search starts.
+    fn search(
+        &mut self,
+        data: &mut State,
+        query: String,
+        run: impl FnOnce(&Path, &AtomicBool, &mut dyn FnMut(Vec<Found>)) -> bool + Send + 'static,
+    ) {
+        let (id, cancel) = self.start_search(data, query);
+        let (root, sink) = (data.current.clone(), self.sink.clone());
+        thread::spawn(move || {
+            let send = |found, done| {
+                let _ = sink.submit_command(commands::FOUND, (id, found, done), Target::Auto);
+            };
+            if run(&root, &cancel, &mut |found| send(found, false)) {
+                send(vec![], true);
+            }
+        });
+    }
+
+    /// the tab listing the results of the search `id`
+    fn search_tab(tabs: &mut Tabs, id: u64) -> Option<&mut State> {
+        let lists = |state: &State| matches!(&state.search, Some(search) if search.id == id);
+        let tab = (0..tabs.count()).find(|&i| matches!(tabs.get(i), Some(s) if lists(s)))?;
+        tabs.get_mut(tab)
+    }
+
+    /// cancels the search `id` if it's running and done, or no tab lists its results anymore
+    fn search_ended(&mut self, id: u64, done: bool, listed: bool) {
+        let running = matches!(&self.finding, Some((running, _)) if *running == id);
+        if running && (done || !listed) {
+            if let Some((_, cancel)) = self.finding.take() {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// adds to the results of the search `id`, cancels it if no tab lists them anymore
+    fn found(&mut self, tabs: &mut Tabs, id: u64, found: &[Found], done: bool) {
+        let tab = Self::search_tab(tabs, id);
+        let listed = tab.is_some();
+        if let Some(state) = tab {
+            state.add_found(id, found, done);
+        }
+        self.search_ended(id, done, listed);
+    }
+
+    /// `:dupes`, lists the duplicates under the current directory once they're all found
+    fn dupes(&mut self, data: &mut State) {
+        let (id, cancel) = self.start_search(data, "dupes".into());
+        let (root, sink) = (data.current.clone(), self.sink.clone());
+        let hidden = data.listing().show_hidden;
+        thread::spawn(move || {
+            let progress = |done| {
+                let _ = sink.submit_command(commands::DUPES_PROGRESS, (id, done), Target::Auto);
+            };
+            if let Some(groups) = dupes::scan(&root, hidden, &cancel, progress) {
+                let _ = sink.submit_command(commands::DUPES_FOUND, (id, groups), Target::Auto);
+            }
+        });
+    }
+
+    /// shows how far the search for duplicates `id` got, cancels it if no tab lists it
+    fn dupes_progress(&mut self, tabs: &mut Tabs, id: u64, done: &str) {
+        let tab = Self::search_tab(tabs, id);
+        let listed = tab.is_some();
+        if let Some(state) = tab {
+            state.message = format!("dupes: {}", done);
+        }
+        self.search_ended(id, false, listed);
+    }
+
+    fn dupes_found(&mut self, tabs: &mut Tabs, id: u64, groups: &[Group]) {
+        if let Some(state) = Self::search_tab(tabs, id) {
+            state.add_duplicates(id, groups);
+            state.message = format!("dupes: {}", dupes::summary(groups));
+        }
+        self.search_ended(id, true, true);
+    }
+
+    /// `:dupes_link`, replaces the marked duplicates with hardlinks in a job
+    fn link_duplicates(&mut self, data: &mut State) {
+        let pairs = match data.duplicate_originals() {
+            Ok(pairs) if pairs.is_empty() => {
+                data.message = "dupes_link: no duplicates marked".into();
+                return;
+            }
+            Ok(pairs) => pairs,
+            Err(e) => {
+                data.message = format!("error: dupes_link: {}", e);
+                return;
+            }
+        };
+        let (what, sink) = ("link duplicates".to_owned(), self.sink.clone());
+        self.jobs.push(what.clone(), move |progress| {
+            let (mut changed, mut errors) = (0, vec![]);
+            for (i, (duplicate, original)) in pairs.iter().enumerate() {
+                match dupes::hardlink(original, duplicate) {
+                    Ok(()) => changed += 1,
+                    Err(e) => errors.push(format!("{}: {}", duplicate.display(), e)),
+                }
+                progress.report(i as u64 + 1, pairs.len() as u64);
+            }
+            Self::changed(&sink, what, changed, errors)
+        });
+    }
+
+    /// `:delete`, the marked or selected entries once asked twice for the same ones
+    fn delete(&mut self, data: &mut State) {
+        let targets = data.targets();
+        if targets.is_empty() {
+            data.message = "delete: nothing selected".into();
+            return;
+        }
+        let doomed = (Doom::Delete, targets);
+        if self.doomed.as_ref() != Some(&doomed) {
+            let count = doomed.1.len();
+            data.message = format!("delete {} entries? delete again to confirm", count);
+            self.doomed = Some(doomed);
+            return;
+        }
+        self.doomed = None;
+        let targets = doomed.1;
+        let (what, sink) = (format!("delete {} entries", targets.len()), self.sink.clone());
+        self.jobs.push(what.clone(), move |progress| {
+            let (mut changed, mut errors) = (0, vec![]);
+            for (i, path) in targets.iter().enumerate() {
+                match fileops::remove_all(path) {
+                    Ok(()) => changed += 1,
+                    Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+                }
+                progress.report(i as u64 + 1, targets.len() as u64);
+            }
+            Self::changed(&sink, what, changed, errors)
+        });
+    }
+
+    /// `:compare`, of the directories of the two panes
+    fn compare(&mut self, tabs: &mut Tabs, content: bool) {
+        if tabs.count() < 2 {
+            tabs.current_mut().message = "compare: needs a second tab".into();
+            return;
+        }
+        let (left, right) = tabs.panes();
+        let dir = |i| tabs.get(i).map(|tab: &State| tab.current.clone()).unwrap_or_default();
+        let (left, right) = (dir(left), dir(right));
+        let data = tabs.current_mut();
+        if self.comparing.is_none() {
+            self.compared = None;
+            let title =
+                format!("comparing {} and {}, close to cancel", left.display(), right.display());
+            data.popup = Some(Popup::new(PopupKind::Compare, title, vec![]));
+        }
+        self.start_compare(data, left, right, content);
+    }
+
+    /// compares `left` and `right` in a thread
+    fn start_compare(&mut self, data: &mut State, left: PathBuf, right: PathBuf, content: bool) {
+        if self.comparing.is_some() {
+            data.message = "compare: already comparing".into();
+            return;
+        }
+        let hidden = data.listing().show_hidden;
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.comparing = Some(cancel.clone());
+        let sink = self.sink.clone();
+        thread::spawn(move || {
+            let compared = Comparison::new(&left, &right, content, hidden, &cancel);
+            let done = SingleUse::new(compared.map_err(|e| e.to_string()));
+            let _ = sink.submit_command(commands::COMPARED, done, Target::Auto);
+        });
+    }
+
+    /// compares again what's shown, once a job may have changed it
+    fn recompare(&mut self, data: &mut State) {
+        if let (Some(shown), None) = (&self.compared, &self.comparing) {
+            let (left, right) = (shown.left.clone(), shown.right.clone());
+            self.start_compare(data, left, right, shown.content);
+        }
+    }
+
+    /// the tab whose comparison waits for the running one, else one showing a comparison,
+    /// else the active one, there's only one comparison at a time
+    fn compare_tab(tabs: &Tabs) -> usize {
+        let shows = |i, waiting: bool| match tabs.get(i).and_then(|s| s.popup.as_ref()) {
+            Some(popup) => popup.kind == PopupKind::Compare && (!waiting || popup.lines.is_empty()),
+            None => false,
+        };
+        let find = |waiting| (0..tabs.count()).find(|&i| shows(i, waiting));
+        find(true).or_else(|| find(false)).unwrap_or_else(|| tabs.active())
+    }
+
+    fn compared(&mut self, data: &mut State, compared: Option<Result<Option<Comparison>, String>>) {
+        self.comparing = None;
+        let shown = matches!(&data.popup, Some(p) if p.kind == PopupKind::Compare);
+        let comparison = match compared {
+            Some(Ok(Some(comparison))) => comparison,
+            Some(Err(e)) => {
+                data.message = format!("error: compare: {}", e);
+                if shown {
+                    data.popup = None;
+                }
+                return;
+            }
+            // cancelled
+            _ => return,
+        };
+        let summary = comparison.summary();
+        if shown {
+            let (left, right) = (comparison.left.display(), comparison.right.display());
+            let title = format!("{} \u{21c4} {}: {}", left, right, summary);
+            let cursor = data.popup.as_ref().map_or(0, |p| p.cursor);
+            let mut popup = Popup::new(PopupKind::Compare, title, comparison.lines());
+            popup.cursor = cursor.min(comparison.diffs.len().saturating_sub(1));
+            data.popup = Some(popup);
+        }
+        data.message = format!("compare: {}", summary);
+        self.compared = Some(comparison);
+    }
+
+    /// copies the selected entry of the comparison to the side it's missing on
+    fn compare_accept(&mut self, data: &mut State) {
+        let cursor = data.popup.as_ref().map_or(0, |p| p.cursor);
+        let diff = match self.compared.as_ref().and_then(|c| c.diffs.get(cursor)) {
+            Some(diff) => diff.clone(),
+            None => return,
+        };
+        let to_right = match diff.status {
+            Status::OnlyLeft => true,
+            Status::OnlyRight => false,
+            Status::Differ | Status::Same => {
+                let both = diff.path.display();
+                data.message = format!("{} is on both sides, :sync -a replaces it", both);
+                return;
+            }
+            Status::Unreadable => {
+                data.message = format!("{} couldn't be read", diff.path.display());
+                return;
+            }
+        };
+        self.copy_compared(data, vec![diff.path], to_right);
+    }
+
+    /// `:sync`, copies what the comparison found missing on one side, and what differs if
+    /// `replace` once asked twice for the same entries
+    fn sync(&mut self, data: &mut State, to_right: bool, replace: bool) {
+        let paths = match &self.compared {
+            Some(comparison) => comparison.to_copy(to_right, replace),
+            None => {
+                data.message = "sync: nothing compared, :compare first".into();
+                return;
+            }
+        };
+        if paths.is_empty() {
+            data.message = "sync: nothing to copy".into();
+            return;
+        }
+        let doomed = (Doom::Sync(to_right), paths);
+        if replace && self.doomed.as_ref() != Some(&doomed) {
+            let side = if to_right { "right" } else { "left" };
+            data.message = format!("replace what differs on the {}? sync again to confirm", side);
+            self.doomed = Some(doomed);
+            return;
+        }
+        self.doomed = None;
+        self.copy_compared(data, doomed.1, to_right);
+    }
+
+    /// copies `paths` of the comparison to the right if `to_right`, else to the left, in a job
+    fn copy_compared(&mut self, data: &mut State, paths: Vec<PathBuf>, to_right: bool) {
+        let comparison = match &self.compared {
+            Some(comparison) => comparison.clone(),
+            None => return,
+        };
+        let xattrs = data.config().preserve_xattrs;
+        let to = if to_right { &comparison.right } else { &comparison.left };
+        let (what, sink) = (format!("sync to {}", to.display()), self.sink.clone());
+        self.jobs.push(what.clone(), move |progress| {
+            let report = |done, total| progress.report(done, total);
+            let (copied, errors) = comparison.copy(&paths, to_right, xattrs, report);
+            Self::changed(&sink, what, copied, errors)
+        });
+    }
+
+    /// `:trash`, moves the marked or selected entries to the home trash
+    fn trash(&mut self, data: &mut State) {
+        let (targets, trash) = match (data.targets(), xdg::trash_dir()) {
+            (targets, _) if targets.is_empty() => {
+                data.message = "trash: nothing selected".into();
+                return;
+            }
+            (targets, Some(trash)) => (targets, trash),
+            (_, None) => {
+                data.message = "error: trash: no home directory".into();
+                return;
+            }
+        };
+        let (what, sink) = (format!("trash {} entries", targets.len()), self.sink.clone());
+        self.jobs.push(what.clone(), move |progress| {
+            let (mut changed, mut errors) = (0, vec![]);
+            for (i, path) in targets.iter().enumerate() {
+                if let Err(e) = fileops::trash(path, &trash) {
+                    errors.push(format!("{}: {}", path.display(), e));
+                } else {
+                    changed += 1;
+                }
+                progress.report(i as u64 + 1, targets.len() as u64);
+            }
+            Self::changed(&sink, what, changed, errors)
+        });
+    }
+
+    /// opens the finder over the paths under the current directory, listed as they're found
+    fn finder(&mut self, tabs: &mut Tabs) {
+        self.close_finder(tabs);
+        self.searches += 1;
+        let id = self.searches;
+        let data = tabs.current_mut();
+        let (hidden, sink) = (data.listing().show_hidden, self.sink.clone());
+        let finder = Finder::new(id, data.current.clone(), hidden, move |ranked| {
+            let _ = sink.submit_command(commands::FINDER_RANKED, (id, ranked), Target::Auto);
+        });
+        data.popup = Some(finder.popup(0));
+        self.finder = Some(finder);
+        tabs.cmdline = Some(CmdLine::finder());
+    }
+
+    /// shows the best matches of the finder `id` if it's still open
+    fn finder_ranked(&mut self, tabs: &mut Tabs, id: u64, ranked: &Ranked) {
+        let finder = match &mut self.finder {
+            Some(finder) if finder.id == id => finder,
+            _ => return,
+        };
+        // the cursor stays on the same line while paths are found
+        let same = finder.set_ranked(ranked.clone());
+        let data = tabs.current_mut();
+        let cursor = match &data.popup {
+            Some(popup) if same && popup.kind == PopupKind::Finder => popup.cursor,
+            _ => 0,
+        };
+        data.popup = Some(finder.popup(cursor));
+    }
+
+    /// has the finder's walk rank the paths again if its line changed
+    fn finder_typed(&mut self, tabs: &mut Tabs) {
+        if let (Some(finder), Some(CmdLine { finder: true, text, .. })) =
+            (&mut self.finder, &tabs.cmdline)
+        {
+            finder.set_pattern(text);
+        }
+    }
+
+    fn close_finder(&mut self, tabs: &mut Tabs) {
+        // dropping it stops its walk
+        if self.finder.take().is_some() {
+            tabs.cmdline = None;
+            tabs.current_mut().popup = None;
+        }
+    }
+
+    /// goes to the directory of the selected match, with the cursor on it
+    fn finder_accept(&mut self, tabs: &mut Tabs) {
+        let selected = tabs.current().popup.as_ref().and_then(|p| p.selected_path());
+        let selected = selected.map(|p| p.to_owned());
+        self.close_finder(tabs);
+        let data = tabs.current_mut();
+        match selected.as_ref().and_then(|p| Some((p.parent()?, p.file_name()?))) {
+            Some((dir, name)) => {
+                self.jump(data, dir.to_owned());
+                data.select(&name.to_string_lossy());
+            }
+            None => data.message = "finder: no match".into(),
+        }
+    }
+
+    fn shell_done(&mut self, tabs: &mut Tabs, done: &shell::Done) {
+        // the command may have changed the files shown, and what git says of them
+        tabs.update();
+        self.forget_shown_git(tabs);
+        let data = tabs.current_mut();
+        data.message = match &done.result {
+            Ok(status) => format!("{}: {}", done.command, status),
+            Err(e) => format!("error: {}: {}", done.command, e),
+        };
+        if let Some(output) = &done.output {
+            data.popup = Some(Popup::new(PopupKind::Text, done.command.clone(), output.clone()));
+        }
+    }
+
+    /// parses and runs a command line, errors end up in the footer
+    fn run(&mut self, ctx: &mut DelegateCtx, tabs: &mut Tabs, env: &Env, line: &str) {
+        // the rest of the line goes to the shell as is
+        if let Some(shell) = shell::parse(line) {
+            match shell {
+                Ok(shell) => self.shell(tabs, shell),
+                Err(e) => tabs.current_mut().message = format!("error: {}", e),
+            }
+            return;
+        }
+        let cmd = cmdline::parse(line)
+            .map_err(|e| e.to_string())
+            .and_then(|args| actions::command(&args));
+        match cmd {
+            Ok(cmd) => {
+                // druid's own commands, like quitting, go through the app
+                if !self.command(ctx, Target::Auto, &cmd, tabs, env).is_handled() {
+                    ctx.submit_command(cmd);
+                }
+            }
+            Err(e) => tabs.current_mut().message = format!("error: {}", e),
+        }
+    }
+
+    fn mode(tabs: &Tabs) -> Mode {
+        if tabs.cmdline.is_some() {
+            Mode::Command
+        } else if tabs.current().popup.is_some() {
+            Mode::Prompt
+        } else if tabs.current().visual.is_some() {
+            Mode::Visual
+        } else {
+            Mode::Normal
+        }
+    }
+
+    /// returns whether the key was used
+    fn key_down(
+        &mut self,
+        ctx: &mut DelegateCtx,
+        tabs: &mut Tabs,
+        key: &KeyEvent,
+        env: &Env,
+    ) -> bool {
+        let name = match key_name(key) {
+            Some(name) => name,
+            None => return false,
+        };
+        let mode = Self::mode(tabs);
+        let was_pending = !self.keys.is_empty();
+        let feed = self.keymap.feed(mode, &mut self.keys, &name);
+        tabs.hints = match feed {
+            Feed::Pending => Some(Arc::new(self.hints(mode))),
+            _ => None,
+        };
+        match feed {
+            Feed::Count | Feed::Pending => (),
+            Feed::Run { line, times } => {
+                for _ in 0..times {
+                    self.run(ctx, tabs, env, &line);
+                }
+            }
+            Feed::Unbound(keys) => match (&mut tabs.cmdline, keys.as_slice()) {
+                // plain characters are typed into the command line
+                (Some(line), [c]) if c == "<" || !c.starts_with('<') => line.insert(c),
+                (Some(line), [c]) if c == "<Space>" => line.insert(" "),
+                (Some(_), _) => (),
+                _ => return was_pending,
+            },
+        }
+        Self::search_typed(tabs);
+        self.finder_typed(tabs);
+        true
+    }
+
+    /// which-key style lines for what can follow the pending keys
+    fn hints(&self, mode: Mode) -> Vec<String> {
+        let mut lines = vec![];
+        for (keys, command) in self.keymap.hints(mode, &self.keys) {
+            let name = command.split_whitespace().next().unwrap_or_default();
+            if keys == keymap::ANY && name.starts_with("bookmark_") {
+                // the keys that mean something are the bookmarks
+                for (key, path) in self.bookmarks.iter() {
+                    lines.push(format!("{:<8}{} {}", key, name, path.display()));
+                }
+            } else {
+                lines.push(format!("{:<8}{}", keys, command));
+            }
+        }
+        lines
+    }
+
+    /// one of `actions::CMDLINE_OPS` on the open command line
+    fn cmdline_edit(&mut self, ctx: &mut DelegateCtx, tabs: &mut Tabs, env: &Env, op: &str) {
+        let cwd = tabs.current().current.clone();
+        let line = match &mut tabs.cmdline {
+            Some(line) => line,
+            None => return,
+        };
+        let (search, finder) = (line.search, line.finder);
+        match op {
+            "cancel" if finder => self.close_finder(tabs),
+            "backspace" if finder && line.text.is_empty() => self.close_finder(tabs),
+            "run" if finder => self.finder_accept(tabs),
+            "older" | "complete_back" | "newer" | "complete" if finder => {
+                let delta = if op == "older" || op == "complete_back" { -1 } else { 1 };
+                if let Some(popup) = &mut tabs.current_mut().popup {
+                    popup.move_cursor(delta);
+                }
+            }
+            "cancel" => Self::close_cmdline(tabs, search),
+            "backspace" if line.text.is_empty() => Self::close_cmdline(tabs, search),
+            "run" if search.is_some() => {
+                let text = line.text.clone();
+                tabs.cmdline = None;
+                Self::search_entered(tabs.current_mut(), &text);
+            }
+            "complete" | "complete_back" | "older" | "newer" if search.is_some() => (),
+            "run" => {
+                let text = line.text.clone();
+                tabs.cmdline = None;
+                if !text.trim().is_empty() {
+                    self.history.push(&text);
+                    if let Err(e) = self.history.save() {
+                        tabs.current_mut().message = format!("error: saving history: {}", e);
+                    }
+                    self.run(ctx, tabs, env, &text);
+                }
+            }
+            "complete" => line.complete(false, actions::NAMES, &cwd),
+            "complete_back" => line.complete(true, actions::NAMES, &cwd),
+            "older" => line.browse(true, &self.history),
+            "newer" => line.browse(false, &self.history),
+            "left" => line.left(),
+            "right" => line.right(),
+            "home" => line.home(),
+            "end" => line.end(),
+            "backspace" => line.backspace(),
+            "delete" => line.delete(),
+            "kill_word" => line.delete_word(),
+            "kill_line" => line.delete_to_start(),
+            _ => (),
+        }
+        Self::search_typed(tabs);
+        self.finder_typed(tabs);
+    }
+
+    /// without running it, the cursor goes back to where a search started
+    fn close_cmdline(tabs: &mut Tabs, search: Option<usize>) {
+        tabs.cmdline = None;
+        if let Some(from) = search {
+            let _ = tabs.current_mut().incsearch("", from);
+        }
+    }
+
+    /// follows the `/` line being typed
+    fn search_typed(tabs: &mut Tabs) {
+        if let Some(CmdLine { search: Some(from), text, .. }) = &tabs.cmdline {
+            let (text, from) = (text.clone(), *from);
+            // the pattern may be incomplete, it's reported once entered
+            let _ = tabs.current_mut().incsearch(&text, from);
+        }
+    }
+
+    /// `/` entered or `:search`, from the cursor
+    fn search_entered(data: &mut State, pattern: &str) {
+        if let Err(e) = data.incsearch(pattern, data.cursor) {
+            data.message = format!("error: search: {}", e);
+            return;
+        }
+        let selected = data.entries().get(data.cursor);
+        let found = matches!(selected, Some(entry) if data.highlight(&entry.name).is_some());
+        if !found && !pattern.is_empty() {
+            data.message = format!("not found: {}", pattern);
+        }
+    }
+
+    /// copies or moves the yanked entries into the current directory
+    fn paste(&mut self, data: &mut State) {
+        let (sources, keep) = match &self.yanked {
+            Some(yanked) => yanked.clone(),
+            None => {
+                data.message = "nothing yanked".into();
+                return;
+            }
+        };
+        // moved entries are gone from where they were yanked
+        if !keep {
+            self.yanked = None;
+        }
+        self.transfer(sources, data.current.clone(), keep, data.config().preserve_xattrs);
+    }
+
+    fn job_event(&mut self, tabs: &mut Tabs, event: &JobEvent) {
+        let data = tabs.current_mut();
+        match event {
+            JobEvent::Started { name, .. } => {
+                self.job = name.clone();
+                data.message = name.clone();
+            }
+            JobEvent::Progress { done, total, .. } => {
+                data.message = if *total == 0 {
+                    format!("{}: {}", self.job, done)
+                } else {
+                    format!("{}: {}%", self.job, done * 100 / total)
+                };
+            }
+            JobEvent::Finished { id, result } => {
+                data.message = match result {
+                    Ok(msg) => msg.clone(),
+                    Err(err) => format!("error: {}", err),
+                };
+                self.analyze_deleted(*id, result.is_ok());
+                tabs.update();
+                self.forget_shown_git(tabs);
+                let data = tabs.current_mut();
+                let (kind, paths, cursor) = match &data.popup {
+                    Some(p) => (p.kind, p.paths.to_vec(), p.cursor),
+                    None => return,
+                };
+                match (kind, paths.first()) {
+                    (PopupKind::Permissions, _) => Self::show_permissions(data, paths, cursor),
+                    (PopupKind::Properties, Some(path)) => {
+                        Self::show_properties(data, path.clone(), cursor)
+                    }
+                    (PopupKind::Compare, _) => self.recompare(data),
+                    (PopupKind::Analyzer, Some(dir)) => {
+                        self.show_scan(data, dir, None);
+                        if let Some(popup) = &mut data.popup {
+                            popup.cursor = cursor.min(popup.lines.len().saturating_sub(1));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl AppDelegate<Tabs> for Delegate {
+    fn event(
+        &mut self,
+        ctx: &mut DelegateCtx,
+        _window_id: WindowId,
+        event: Event,
+        tabs: &mut Tabs,
+        env: &Env,
+    ) -> Option<Event> {
+        let event = match &event {
+            Event::KeyDown(key) if self.key_down(ctx, tabs, key, env) => None,
+            _ => Some(event),
+        };
+        self.visit(tabs.current());
+        self.refresh_preview(tabs.current_mut());
+        self.cancel_checksums(tabs);
+        event
+    }
+
+    fn command(
+        &mut self,
+        ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        tabs: &mut Tabs,
+        env: &Env,
+    ) -> Handled {
+        if cmd.is(commands::TAB_NEW) {
+            tabs.open();
+        } else if cmd.is(commands::TAB_CLOSE) {
+            tabs.close();
+        } else if cmd.is(commands::TAB_NEXT) {
+            tabs.next();
+        } else if cmd.is(commands::TAB_PREV) {
+            tabs.prev();
+        } else if let Some(index) = cmd.get(commands::TAB_SELECT) {
+            tabs.select(*index);
+        } else if let Some(index) = cmd.get(commands::MOVE_TO_TAB) {
+            self.transfer_to_tab(tabs, Some(*index), false);
+        } else if let Some(index) = cmd.get(commands::COPY_TO_TAB) {
+            self.transfer_to_tab(tabs, Some(*index), true);
+        } else if cmd.is(commands::COPY_TO_OTHER) {
+            self.transfer_to_tab(tabs, tabs.other_index(), true);
+        } else if cmd.is(commands::MOVE_TO_OTHER) {
+            self.transfer_to_tab(tabs, tabs.other_index(), false);
+        } else if cmd.is(commands::FOCUS_OTHER) {
+            if let Some(other) = tabs.other_index() {
+                tabs.select(other);
+            }
+        } else if let Some(text) = cmd.get(commands::CONSOLE) {
+            tabs.cmdline = Some(CmdLine::new(text.clone()));
+        } else if let Some(None) = cmd.get(commands::SEARCH) {
+            tabs.cmdline = Some(CmdLine::search(tabs.current().cursor));
+        } else if cmd.is(commands::FINDER) {
+            self.finder(tabs);
+        } else if let Some((id, ranked)) = cmd.get(commands::FINDER_RANKED) {
+            self.finder_ranked(tabs, *id, ranked);
+        } else if let Some(op) = cmd.get(commands::CMDLINE) {
+            self.cmdline_edit(ctx, tabs, env, op);
+        } else if let Some(layout) = cmd.get(commands::SET_LAYOUT) {
+            tabs.set_layout(*layout);
+        } else if cmd.is(commands::TOGGLE_LAYOUT) {
+            tabs.set_layout(match tabs.layout {
+                Layout::Miller => Layout::Dual,
+                Layout::Dual => Layout::Miller,
+            });
+        } else if let Some((name, value)) = cmd.get(commands::SET) {
+            self.set_option(tabs, name, value);
+        } else if let Some(name) = cmd.get(commands::THEME) {
+            let message = match name {
+                Some(name) => Self::set_theme(tabs, name).err().map(|e| format!("error: {}", e)),
+                None => Some(format!("themes: {}", Theme::names().join(", "))),
+            };
+            if let Some(message) = message {
+                tabs.current_mut().message = message;
+            }
+        } else if let Some(column) = cmd.get(commands::SORT_BY_COLUMN) {
+            Self::sort_by_column(tabs.current_mut(), *column);
+        } else if let Some(file) = cmd.get(commands::RANGER_IMPORT) {
+            self.ranger_import(tabs, file.as_deref());
+        } else if cmd.is(commands::CONFIG_CHANGED) {
+            self.reload_config(tabs);
+        } else if let Some(done) = cmd.get(commands::SHELL_DONE) {
+            self.shell_done(tabs, done);
+        } else if let Some(event) = cmd.get(commands::JOB_EVENT) {
+            self.job_event(tabs, event);
+        } else if let Some((path, lines)) = cmd.get(commands::PREVIEW_READ) {
+            for i in 0..tabs.count() {
+                if let Some(state) = tabs.get_mut(i) {
+                    state.set_preview(path, lines.clone());
+                }
+            }
+        } else if let Some(counted) = cmd.get(commands::DIRS_COUNTED) {
+            self.dirs_counted(tabs, counted);
+        } else if let Some(changed) = cmd.get(commands::DIR_CHANGED) {
+            self.dir_changed(tabs, changed.as_deref());
+        } else if let Some((what, errors)) = cmd.get(commands::FILE_ERRORS) {
+            let title = format!("{}: errors", what);
+            tabs.current_mut().popup = Some(Popup::new(PopupKind::Text, title, errors.clone()));
+        } else if let Some((path, algorithm, checksum)) = cmd.get(commands::CHECKSUM_DONE) {
+            let checksum = checksum.clone().unwrap_or_else(|e| format!("({})", e));
+            Self::checksum_done(tabs, path, *algorithm, &checksum);
+        } else if let Some((generation, dir, repo)) = cmd.get(commands::GIT_STATUS) {
+            self.git_read(tabs, *generation, dir, repo);
+        } else if let Some((id, found, done)) = cmd.get(commands::FOUND) {
+            self.found(tabs, *id, found, *done);
+        } else if let Some((id, done)) = cmd.get(commands::DUPES_PROGRESS) {
+            self.dupes_progress(tabs, *id, done);
+        } else if let Some((id, groups)) = cmd.get(commands::DUPES_FOUND) {
+            self.dupes_found(tabs, *id, groups);
+        } else if let Some(content) = cmd.get(commands::COMPARE) {
+            self.compare(tabs, *content);
+        } else if let Some(compared) = cmd.get(commands::COMPARED) {
+            if let Some(data) = tabs.get_mut(Self::compare_tab(tabs)) {
+                self.compared(data, compared.take());
+            }
+        } else if let Some((dir, du)) = cmd.get(commands::DU_DONE) {
+            self.du_done(tabs, dir, du);
+        } else if let Some(summary) = cmd.get(commands::ANALYZE_PROGRESS) {
+            self.analyze_progress(tabs, summary);
+        } else if let Some(scan) = cmd.get(commands::ANALYZE_DONE) {
+            self.analyze_done(tabs, scan.take());
+        } else if !self.tab_command(cmd, tabs.current_mut()).is_handled() {
+            return Handled::No;
+        }
+        self.visit(tabs.current());
+        self.refresh_preview(tabs.current_mut());
+        self.cancel_checksums(tabs);
+        self.refresh_dirs(tabs);
+        self.refresh_git(tabs);
+        Handled::Yes
+    }
+}
+
+impl Delegate {
+    /// commands acting on the active tab only
+    fn tab_command(&mut self, cmd: &Command, data: &mut State) -> Handled {
+        let confirming = cmd.is(commands::ANALYZE_DELETE) || cmd.is(commands::DELETE);
+        if !confirming && !cmd.is(commands::SYNC) {
+            self.doomed = None;
+        }
+        if let Some(delta) = cmd.get(commands::MOVE_CURSOR) {
+            match &mut data.popup {
+                Some(popup) => popup.move_cursor(*delta),
+                None => data.move_cursor(*delta),
+            }
+        } else if cmd.is(commands::ENTER) && data.popup.is_some() {
+            self.popup_accept(data);
+        } else if cmd.is(commands::ENTER) {
+            self.enter(data);
+        } else if cmd.is(commands::LEAVE) && data.popup.is_some() {
+            match &data.popup {
+                Some(popup) if popup.kind == PopupKind::Analyzer => self.analyzer_leave(data),
+                _ => data.popup = None,
+            }
+        } else if cmd.is(commands::LEAVE) {
+            data.leave();
+        } else if cmd.is(commands::TOGGLE_MARK) {
+            data.toggle_mark();
+            data.move_cursor(1);
+        } else if cmd.is(commands::VISUAL) {
+            data.toggle_visual();
+        } else if let Some(keep) = cmd.get(commands::YANK) {
+            let targets = data.targets();
+            let verb = if *keep { "yanked" } else { "cut" };
+            data.message = format!("{} {} entries", verb, targets.len());
+            self.yanked = Some((targets, *keep));
+        } else if cmd.is(commands::PASTE) {
+            self.paste(data);
+        } else if cmd.is(commands::ACCEPT) {
+            self.popup_accept(data);
+        } else if cmd.is(commands::CLOSE_POPUP) {
+            // waiting for the scan
+            let waiting = matches!(&data.popup,
+                Some(p) if p.kind == PopupKind::Analyzer && p.paths.is_empty());
+            let comparing = matches!(&data.popup, Some(p) if p.kind == PopupKind::Compare);
+            data.popup = None;
+            if let (true, Some((root, cancel))) = (waiting, &self.scanning) {
+                cancel.store(true, Ordering::Relaxed);
+                data.message = format!("cancelled, :analyze resumes {}", root.display());
+            }
+            if let (true, Some(cancel)) = (comparing, &self.comparing) {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        } else if let Some((one_filesystem, fresh, dir)) = cmd.get(commands::ANALYZE) {
+            self.analyze(data, *one_filesystem, *fresh, dir.as_deref());
+        } else if cmd.is(commands::ANALYZE_DELETE) {
+            self.analyze_delete(data);
+        } else if let Some((to_right, replace)) = cmd.get(commands::SYNC) {
+            self.sync(data, *to_right, *replace);
+        } else if let Some((syntax, all, pattern)) = cmd.get(commands::FIND) {
+            self.find(data, *syntax, *all, pattern);
+        } else if let Some((recursive, spec)) = cmd.get(commands::CHMOD) {
+            self.chmod(data, data.targets(), *recursive, spec);
+        } else if let Some((recursive, spec)) = cmd.get(commands::CHOWN) {
+            self.chown(data, *recursive, spec);
+        } else if cmd.is(commands::PERMISSIONS) {
+            Self::show_permissions(data, data.targets(), 0);
+        } else if cmd.is(commands::PROPERTIES) {
+            match data.selected() {
+                Some(path) => Self::show_properties(data, path, 0),
+                None => data.message = "properties: nothing selected".into(),
+            }
+        } else if cmd.is(commands::XATTRS) {
+            Self::show_xattrs(data);
+        } else if let Some(name) = cmd.get(commands::XATTR_GET) {
+            Self::xattr_get(data, name);
+        } else if let Some(filter) = cmd.get(commands::FILTER) {
+            data.set_filter(filter.clone());
+        } else if let Some((name, value)) = cmd.get(commands::XATTR_SET) {
+            self.xattr_change(data, name, Some(value));
+        } else if let Some(name) = cmd.get(commands::XATTR_REMOVE) {
+            self.xattr_change(data, name, None);
+        } else if let Some(args) = cmd.get(commands::GREP) {
+            self.grep(data, args);
+        } else if cmd.is(commands::DUPES) {
+            self.dupes(data);
+        } else if cmd.is(commands::MARK_DUPLICATES) {
+            data.mark_duplicates();
+        } else if cmd.is(commands::LINK_DUPLICATES) {
+            self.link_duplicates(data);
+        } else if cmd.is(commands::DELETE) {
+            self.delete(data);
+        } else if cmd.is(commands::TRASH) {
+            self.trash(data);
+        } else if let Some(key) = cmd.get(commands::SET_BOOKMARK) {
+            self.set_bookmark(data, *key);
+        } else if let Some(key) = cmd.get(commands::GOTO_BOOKMARK) {
+            self.goto_bookmark(data, *key);
+        } else if let Some(key) = cmd.get(commands::DELETE_BOOKMARK) {
+            self.delete_bookmark(data, *key);
+        } else if let Some(name) = cmd.get(commands::COMPRESS) {
+            self.compress(data, name);
+        } else if cmd.is(commands::EXTRACT) {
+            self.extract(data);
+        } else if cmd.is(commands::BACK) {
+            data.back();
+        } else if cmd.is(commands::FORWARD) {
+            data.forward();
+        } else if cmd.is(commands::HISTORY) {
+            data.show_history();
+        } else if cmd.is(commands::BOOKMARKS) {
+            self.show_bookmarks(data);
+        } else if let Some(path) = cmd.get(commands::CD) {
+            let path = data.current.join(xdg::expand_home(path));
+            self.jump(data, path);
+        } else if let Some(Some(pattern)) = cmd.get(commands::SEARCH) {
+            Self::search_entered(data, pattern);
+        } else if let Some(back) = cmd.get(commands::SEARCH_NEXT) {
+            data.search_next(*back);
+        } else if let Some(query) = cmd.get(commands::Z) {
+            self.z(data, query);
+        } else if let Some(query) = cmd.get(commands::ZI) {
+            self.zi(data, query);
+        } else if let Some(one_filesystem) = cmd.get(commands::DU) {
+            self.du(data, *one_filesystem);
+        } else {
+            return Handled::No;
+        }
+        self.visit(data);
+        self.refresh_preview(data);
+        Handled::Yes
+    }
+}
+
+/// `key` as written in key bindings, see `keymap::parse_keys`
+fn key_name(key: &KeyEvent) -> Option<String> {
+    let name = match &key.key {
+        KbKey::Character(c) if c == " " => "Space".to_owned(),
+        KbKey::Character(c) => c.clone(),
+        KbKey::Enter => "Enter".into(),
+        KbKey::Escape => "Esc".into(),
+        KbKey::Tab => "Tab".into(),
+        KbKey::Backspace => "BS".into(),
+        KbKey::Delete => "Del".into(),
+        KbKey::Insert => "Insert".into(),
+        KbKey::ArrowUp => "Up".into(),
+        KbKey::ArrowDown => "Down".into(),
+        KbKey::ArrowLeft => "Left".into(),
+        KbKey::ArrowRight => "Right".into(),
+        KbKey::Home => "Home".into(),
+        KbKey::End => "End".into(),
+        KbKey::PageUp => "PageUp".into(),
+        KbKey::PageDown => "PageDown".into(),
+        KbKey::F1 => "F1".into(),
+        KbKey::F2 => "F2".into(),
+        KbKey::F3 => "F3".into(),
+        KbKey::F4 => "F4".into(),
+        KbKey::F5 => "F5".into(),
+        KbKey::F6 => "F6".into(),
+        KbKey::F7 => "F7".into(),
+        KbKey::F8 => "F8".into(),
+        KbKey::F9 => "F9".into(),
+        KbKey::F10 => "F10".into(),
+        KbKey::F11 => "F11".into(),
+        KbKey::F12 => "F12".into(),
+        _ => return None,
+    };
+    let character = matches!(&key.key, KbKey::Character(c) if c != " ");
+    let mut mods = String::new();
+    if key.mods.ctrl() {
+        mods.push_str("C-");
+    }
+    if key.mods.alt() {
+        mods.push_str("A-");
+    }
+    // shift is already part of characters
+    if key.mods.shift() && !character {
+        mods.push_str("S-");
+    }
+    if mods.is_empty() && character {
+        Some(name)
+    } else {
+        Some(format!("<{}{}>", mods, name))
+    }
+}
+
+/// whether `changed` is in the repository read for `dir`, anything is if `None`, and
+/// whether it may have made one if there was none
+fn in_repo(changed: &Option<PathBuf>, dir: &Path, repo: &Option<Arc<Repo>>) -> bool {
+    match (changed, repo) {
+        (None, _) => true,
+        (Some(changed), Some(repo)) => changed.starts_with(&repo.root),
+        (Some(changed), None) => changed.starts_with(dir),
+    }
+}
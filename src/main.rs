@@ -1,111 +1,99 @@
 use druid::{
-    AppDelegate, AppLauncher, Code, Data, Event, PlatformError, Size, Widget,
-    WidgetExt, WindowDesc, MenuDesc, LocalizedString, SysMods, platform_menus
+    AppLauncher, Data, PlatformError, Size, Widget,
+    WidgetExt, WindowDesc, MenuDesc, LocalizedString, platform_menus
 };
-use druid::widget::{Container, Flex, Label, List, Split};
+use druid::widget::{Container, Either, EnvScope, Flex, Label, List};
 
+use yukari_lib::tabs::{self, Layout, Tabs};
 use yukari_lib::State;
-use yukari_widgets::Footer;
+use yukari_widgets::{Columns, Footer};
+
+mod actions;
+mod commands;
+mod delegate;
+mod view;
+use delegate::Delegate;
 
 fn build_menu<T: Data>() -> MenuDesc<T> {
     MenuDesc::empty()
     .append(MenuDesc::new(LocalizedString::new("common-menu-file-menu"))
-            .append(platform_menus::win::file::close().hotkey(SysMods::None, "q")))
+            .append(platform_menus::win::file::close()))
 }
-fn build_footer(body: impl Widget<State> + 'static) -> impl Widget<State> {
+fn build_footer(body: impl Widget<Tabs> + 'static) -> impl Widget<Tabs> {
+    let status = Flex::row()
+        .with_flex_child(
+            Label::dynamic(|data: &State, _| data.location()),
+            2.,
+        )
+        .with_child(Label::dynamic(|data: &State, _| data.branch()))
+        .with_flex_child(Label::dynamic(|data: &State, _| data.message.clone()), 1.)
+        .must_fill_main_axis(true)
+        .lens(tabs::Active);
     Footer::new(
         body,
-        Flex::row()
-            .with_flex_child(
-                Label::dynamic(|data: &State, _| data.current.to_string_lossy().into_owned()),
-                2.,
-            )
-            .must_fill_main_axis(true),
+        Either::new(|data: &Tabs, _| data.cmdline.is_some(), view::cmdline(), status)
+            .background(view::FOOTER),
     )
     .fill_body(true)
-    .border(druid::Color::RED, 1.)
+    .border(view::BORDER, 1.)
+}
+fn build_main() -> impl Widget<Tabs> {
+    Either::new(
+        |data: &Tabs, _| data.layout == Layout::Dual,
+        view::dual(),
+        build_miller().lens(tabs::Active),
+    )
 }
-fn build_main() -> impl Widget<State> {
-    Split::columns(
-        List::new(|| Label::dynamic(|d: &String, _| d.clone()))
-            .lens(State::parent_content),
-        List::new(|| Label::dynamic(|d: &String, _| d.clone()))
-            .scroll()
-            .vertical()
-            .lens(State::current_content),
+fn build_miller() -> impl Widget<State> {
+    Columns::new(|data: &State| {
+        let config = data.config();
+        let mut ratios = config.column_ratios.clone();
+        if !config.preview.enabled {
+            ratios.truncate(2);
+        }
+        ratios
+    })
+    .with_child(
+        List::new(|| Label::dynamic(|d: &String, _| d.clone())).lens(State::parent_content),
     )
-    .draggable(true)
-    .solid_bar(true)
-    .min_size(150., 300.)
+    .with_child(view::listing())
+    .with_child(view::preview())
 }
 
-fn build_ui() -> impl Widget<State> {
-    Container::new(build_footer(build_main()))
+fn build_ui() -> impl Widget<Tabs> {
+    let ui = Flex::column()
+        .with_child(view::tab_bar().expand_width())
+        .with_flex_child(
+            Container::new(build_footer(
+                Flex::column()
+                    .with_flex_child(
+                        Either::new(
+                            |data: &Tabs, _| data.current().popup.is_some(),
+                            view::popup().lens(tabs::Active),
+                            build_main(),
+                        ),
+                        1.,
+                    )
+                    .with_child(view::hints().expand_width()),
+            ))
+            .expand(),
+            1.,
+        );
+    // the window's own background doesn't see the scope's env
+    EnvScope::new(view::apply_theme, ui.background(druid::theme::WINDOW_BACKGROUND_COLOR))
 }
 
 fn main() -> Result<(), PlatformError> {
-    fn update_title(data: &State, _: &druid::Env) -> String {
-        data.current.to_string_lossy().into_owned()
+    fn update_title(data: &Tabs, _: &druid::Env) -> String {
+        data.current().current.to_string_lossy().into_owned()
     }
-    AppLauncher::with_window(
+    let launcher = AppLauncher::with_window(
         WindowDesc::new(build_ui())
             .title(update_title)
             .menu(build_menu())
             .with_min_size(Size::new(300., 300.)),
-    )
-    .launch(State::cwd())
-}
-
-struct QExist {}
-
-impl<T: Data> AppDelegate<T> for QExist {
-    fn event(
-        &mut self,
-        _ctx: &mut druid::DelegateCtx,
-        _window_id: druid::WindowId,
-        event: Event,
-        _data: &mut T,
-        _env: &druid::Env,
-    ) -> Option<Event> {
-        match &event {
-            Event::KeyUp(k) => {
-                println!("{}", k.code);
-                if k.code == Code::KeyQ {
-                    std::process::exit(1)
-                } else {
-                    Some(event)
-                }
-            }
-            _ => Some(event),
-        }
-    }
-
-    fn command(
-        &mut self,
-        _ctx: &mut druid::DelegateCtx,
-        _target: druid::Target,
-        _cmd: &druid::Command,
-        _data: &mut T,
-        _env: &druid::Env,
-    ) -> druid::Handled {
-        druid::Handled::No
-    }
-
-    fn window_added(
-        &mut self,
-        _id: druid::WindowId,
-        _data: &mut T,
-        _env: &druid::Env,
-        _ctx: &mut druid::DelegateCtx,
-    ) {
-    }
-
-    fn window_removed(
-        &mut self,
-        _id: druid::WindowId,
-        _data: &mut T,
-        _env: &druid::Env,
-        _ctx: &mut druid::DelegateCtx,
-    ) {
-    }
+    );
+    let mut data = Tabs::new(State::cwd());
+    let delegate = Delegate::new(launcher.get_external_handle(), &mut data);
+    launcher.delegate(delegate).launch(data)
 }
@@ -0,0 +1,333 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use druid::text::RichText;
+use druid::widget::{CrossAxisAlignment, Either, Flex, Label, List, Painter, SizedBox, Split};
+use druid::widget::{EnvScope, RawLabel};
+use druid::{
+    lens, theme, Color, Data, Env, FontFamily, FontWeight, Key, Lens, PaintCtx, RenderContext,
+    Widget, WidgetExt,
+};
+
+use yukari_lib::cmdline::CmdLine;
+use yukari_lib::details::Column;
+use yukari_lib::entry::Entry;
+use yukari_lib::popup::PopupKind;
+use yukari_lib::tabs::Pane;
+use yukari_lib::theme::{Rgba, Style};
+use yukari_lib::{State, Tabs};
+use yukari_widgets::{emphasized, CellRow, Cells, Header};
+
+use crate::commands;
+
+/// of the line above the footer
+pub const BORDER: Key<Color> = Key::new("yukari.border");
+/// behind the footer
+pub const FOOTER: Key<Color> = Key::new("yukari.footer");
+
+fn color((r, g, b, a): Rgba) -> Color {
+    Color::rgba8(r, g, b, a)
+}
+
+/// the theme's colors, over druid's
+pub fn apply_theme(env: &mut Env, tabs: &Tabs) {
+    let theme = tabs.theme();
+    for &(name, key) in &[
+        ("background", theme::WINDOW_BACKGROUND_COLOR),
+        ("foreground", theme::LABEL_COLOR),
+        ("selection", theme::SELECTION_COLOR),
+    ] {
+        if let Some(c) = theme.color(name) {
+            env.set(key, color(c));
+        }
+    }
+    env.set(BORDER, theme.color("border").map_or(Color::RED, color));
+    let footer = theme.color("footer").map(color);
+    env.set(FOOTER, footer.unwrap_or_else(|| env.get(theme::WINDOW_BACKGROUND_COLOR)));
+}
+
+/// a line of a listing, as displayed
+#[derive(Clone, Data, Lens)]
+pub struct Row {
+    pub text: String,
+    /// right aligned, after `text`: a directory's size or number of entries, or the group of
+    /// a duplicate
+    pub info: String,
+    /// the detail view's columns, `text` and `info` are shown if empty
+    pub cells: Cells,
+    /// the entry's status in its repository, see `State::git_mark`
+    pub git: String,
+    /// bytes of `text` the search matched
+    pub matched: Arc<Vec<Range<usize>>>,
+    pub selected: bool,
+    pub marked: bool,
+    /// of `text`, from the theme's `ls_colors`
+    pub color: Option<Rgba>,
+    pub bold: bool,
+}
+
+impl Row {
+    fn new(text: String, selected: bool) -> Self {
+        let style = Style::default();
+        Self {
+            text,
+            info: String::new(),
+            git: String::new(),
+            cells: Cells::default(),
+            matched: Default::default(),
+            selected,
+            marked: false,
+            color: style.color,
+            bold: style.bold,
+        }
+    }
+}
+
+/// before the names of marked entries
+const MARK: &str = "* ";
+
+fn marked(text: &str) -> String {
+    format!("{}{}", MARK, text)
+}
+
+/// the row's name, marked, with what the search matched emphasized
+fn name(row: &Row) -> RichText {
+    let offset = if row.marked { MARK.len() } else { 0 };
+    let text = if row.marked { marked(&row.text) } else { row.text.clone() };
+    emphasized(&text, row.matched.iter().map(|range| range.start + offset..range.end + offset))
+}
+
+/// which of the detail view's columns are aligned to the right
+fn alignments(data: &State) -> Arc<Vec<bool>> {
+    Arc::new(data.columns().iter().map(|c| c.right_aligned()).collect())
+}
+
+/// the detail view's columns for `entry`
+fn cells(data: &State, entry: &Entry, marked: bool, right: &Arc<Vec<bool>>) -> Cells {
+    let info = data.dir_info(entry);
+    let offset = if marked { MARK.len() } else { 0 };
+    let column = data.columns().iter().position(|&c| c == Column::Name);
+    let highlight = column.and_then(|column| {
+        let range = data.highlight(&entry.name)?;
+        Some((column, range.start + offset, range.end + offset))
+    });
+    let texts = data.columns().iter().map(|column| {
+        let text = column.cell(entry, &info);
+        match column {
+            Column::Name if marked => self::marked(&text),
+            Column::Git => data.git_mark(entry),
+            _ => text,
+        }
+    });
+    let (widths, right) = (data.widths.clone(), right.clone());
+    Cells { texts: Arc::new(texts.collect()), widths, right, highlight }
+}
+
+/// `#2` for the duplicates of the second group, the summary of a directory's info else
+fn info(data: &State, entry: &Entry) -> String {
+    match data.duplicate_group(entry) {
+        Some(group) => format!("#{}", group + 1),
+        None => data.dir_info(entry).summary(),
+    }
+}
+
+fn rows(data: &State, details: bool) -> Arc<Vec<Row>> {
+    let right = alignments(data);
+    let rows = data.entries().iter().enumerate().map(|(i, entry)| {
+        let style = data.style(entry);
+        let marked = data.is_marked(&entry.name) || data.in_visual(i);
+        Row {
+            text: entry.name.clone(),
+            info: if details { String::new() } else { info(data, entry) },
+            git: if details { String::new() } else { data.git_mark(entry) },
+            cells: if details { cells(data, entry, marked, &right) } else { Cells::default() },
+            matched: Arc::new(data.highlight(&entry.name).into_iter().collect()),
+            selected: i == data.cursor,
+            marked,
+            color: style.color,
+            bold: style.bold,
+        }
+    });
+    Arc::new(rows.collect())
+}
+
+/// the detail view's titles, the one sorted by with an arrow
+fn header_cells(data: &State) -> Cells {
+    let listing = data.listing();
+    let titles = data.columns().iter().map(|column| {
+        match (column.sort_key() == Some(listing.sort), listing.sort_reverse) {
+            (true, false) => format!("{} \u{25b4}", column.title()),
+            (true, true) => format!("{} \u{25be}", column.title()),
+            (false, _) => column.title().to_owned(),
+        }
+    });
+    let texts = Arc::new(titles.collect());
+    Cells { texts, widths: data.widths.clone(), right: alignments(data), highlight: None }
+}
+
+fn popup_rows(data: &State) -> Arc<Vec<Row>> {
+    let popup = match &data.popup {
+        Some(popup) => popup,
+        None => return Default::default(),
+    };
+    let rows = popup.lines.iter().enumerate().map(|(i, line)| Row {
+        matched: Arc::new(popup.matched.get(i).cloned().unwrap_or_default()),
+        ..Row::new(line.clone(), i == popup.cursor)
+    });
+    Arc::new(rows.collect())
+}
+
+fn tab_rows(tabs: &Tabs) -> Arc<Vec<Row>> {
+    let rows = tabs.titles().into_iter().enumerate().map(|(i, title)| Row::new(title, i == tabs.active()));
+    Arc::new(rows.collect())
+}
+
+fn highlight(ctx: &mut PaintCtx, row: &Row, env: &Env) {
+    if row.selected {
+        let rect = ctx.size().to_rect();
+        ctx.fill(rect, &env.get(theme::SELECTION_COLOR));
+    }
+}
+
+/// the row's own style over the theme
+fn style_row(env: &mut Env, row: &Row) {
+    if let Some(c) = row.color {
+        env.set(theme::LABEL_COLOR, color(c));
+    }
+    if row.bold {
+        let font = env.get(theme::UI_FONT).with_weight(FontWeight::BOLD);
+        env.set(theme::UI_FONT, font);
+    }
+}
+
+fn row() -> impl Widget<Row> {
+    let name = RawLabel::new().lens(lens::Map::new(name, |_: &mut Row, _| ()));
+    let git = Label::dynamic(|row: &Row, _| row.git.clone());
+    let info = Label::dynamic(|row: &Row, _| row.info.clone());
+    let line = Either::new(
+        |row: &Row, _| row.cells.texts.is_empty(),
+        Flex::row().with_flex_child(name.expand_width(), 1.).with_child(git).with_child(info),
+        CellRow::new().lens(Row::cells),
+    );
+    EnvScope::new(style_row, line).background(Painter::new(highlight))
+}
+
+/// the detail view's titles, dragged to resize the columns and clicked to sort by them
+fn header() -> impl Widget<State> {
+    Header::new()
+        .on_click(|ctx, column| ctx.submit_command(commands::SORT_BY_COLUMN.with(column)))
+        .lens(lens::Map::new(header_cells, |data: &mut State, cells: Cells| {
+            data.widths = cells.widths
+        }))
+}
+
+/// the listing, as a table with a header when `details`
+fn table(details: fn(&State) -> bool) -> impl Widget<State> {
+    let lens = lens::Map::new(move |data: &State| rows(data, details(data)), |_: &mut State, _| ());
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Either::new(move |data: &State, _| details(data), header(), SizedBox::empty()))
+        .with_flex_child(List::new(row).scroll().vertical().lens(lens), 1.)
+}
+
+/// the current directory's listing, with cursor and marks, as a table in the detail view
+pub fn listing() -> impl Widget<State> {
+    table(|data| data.config().details.enabled)
+}
+
+/// like [`listing`], always as a table
+pub fn detail_listing() -> impl Widget<State> {
+    table(|_| true)
+}
+
+/// the selected entry's first lines or contents
+pub fn preview() -> impl Widget<State> {
+    List::new(|| Label::dynamic(|line: &String, _| line.clone()))
+        .scroll()
+        .vertical()
+        .lens(State::preview)
+}
+
+fn pane(right: bool) -> impl Widget<Tabs> {
+    Flex::column()
+        .with_child(Label::dynamic(|data: &State, _| data.location()))
+        .with_flex_child(detail_listing(), 1.)
+        .lens(Pane { right })
+        .padding(2.)
+        .background(Painter::new(move |ctx, tabs: &Tabs, env| {
+            // frame the focused pane
+            if Pane { right }.index(tabs) == tabs.active() {
+                let rect = ctx.size().to_rect().inset(-1.);
+                ctx.stroke(rect, &env.get(theme::SELECTION_COLOR), 2.);
+            }
+        }))
+}
+
+/// commander style layout, see `Layout::Dual`
+pub fn dual() -> impl Widget<Tabs> {
+    Split::columns(pane(false), pane(true)).draggable(true).solid_bar(true)
+}
+
+/// shown in place of the columns while `State::popup` is set
+pub fn popup() -> impl Widget<State> {
+    Flex::column()
+        .with_child(Label::dynamic(|data: &State, _| {
+            data.popup.as_ref().map_or_else(String::new, |p| p.title.clone())
+        }))
+        .with_flex_child(
+            EnvScope::new(
+                monospace,
+                List::new(row)
+                    .scroll()
+                    .vertical()
+                    .lens(lens::Map::new(popup_rows, |_: &mut State, _| ())),
+            ),
+            1.,
+        )
+}
+
+/// so the analyzer's sizes and bars, the values of the properties and the paths compared
+/// line up
+fn monospace(env: &mut Env, data: &State) {
+    let aligned = [PopupKind::Analyzer, PopupKind::Properties, PopupKind::Compare];
+    if matches!(&data.popup, Some(p) if aligned.contains(&p.kind)) {
+        let mut font = env.get(theme::UI_FONT);
+        font.family = FontFamily::MONOSPACE;
+        env.set(theme::UI_FONT, font);
+    }
+}
+
+/// `:`, `/` or the finder's `>` and the line being edited, replaces the footer while
+/// `Tabs::cmdline` is set
+pub fn cmdline() -> impl Widget<Tabs> {
+    Label::dynamic(|tabs: &Tabs, _| match &tabs.cmdline {
+        Some(line) => {
+            let (before, after) = line.text.split_at(line.cursor);
+            let prompt = match line {
+                CmdLine { search: Some(_), .. } => '/',
+                CmdLine { finder: true, .. } => '>',
+                _ => ':',
+            };
+            format!("{}{}\u{258f}{}", prompt, before, after)
+        }
+        None => String::new(),
+    })
+}
+
+/// which-key style list of what can follow the keys typed so far
+pub fn hints() -> impl Widget<Tabs> {
+    List::new(|| Label::dynamic(|line: &String, _| line.clone())).lens(lens::Map::new(
+        |tabs: &Tabs| tabs.hints.clone().unwrap_or_default(),
+        |_: &mut Tabs, _| (),
+    ))
+}
+
+/// one label per tab, above the columns
+pub fn tab_bar() -> impl Widget<Tabs> {
+    List::new(|| {
+        Label::dynamic(|row: &Row, _| row.text.clone()).background(Painter::new(highlight))
+    })
+    .horizontal()
+    .with_spacing(8.)
+    .lens(lens::Map::new(tab_rows, |_: &mut Tabs, _| ()))
+}
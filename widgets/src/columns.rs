@@ -0,0 +1,173 @@
+use druid::kurbo::Line;
+use druid::{
+    theme, BoxConstraints, Cursor, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget, WidgetPod,
+};
+
+/// how close to the edge between two children dragging resizes them
+const GRIP: f64 = 4.;
+/// children can't be dragged narrower than this
+const MIN_WIDTH: f64 = 50.;
+
+/// dragging the edge between two children
+struct Drag {
+    left: usize,
+    right: usize,
+    /// where it was grabbed
+    x: f64,
+    /// the widths of all the children then
+    widths: Vec<f64>,
+}
+
+/// Children side by side, each as wide as its share of the ratios given by the data.
+/// Children with a zero (or missing) ratio are hidden. Dragging the edge between two
+/// children resizes them until the data's ratios change.
+pub struct Columns<T> {
+    children: Vec<WidgetPod<T, Box<dyn Widget<T>>>>,
+    ratios: Box<dyn Fn(&T) -> Vec<f64>>,
+    /// the ratios dragged to, over the data's
+    dragged: Option<Vec<f64>>,
+    /// of the children in the last layout
+    widths: Vec<f64>,
+    drag: Option<Drag>,
+}
+
+impl<T: Data> Columns<T> {
+    pub fn new(ratios: impl Fn(&T) -> Vec<f64> + 'static) -> Self {
+        Self {
+            children: vec![],
+            ratios: Box::new(ratios),
+            dragged: None,
+            widths: vec![],
+            drag: None,
+        }
+    }
+    pub fn with_child(mut self, child: impl Widget<T> + 'static) -> Self {
+        self.children.push(WidgetPod::new(child).boxed());
+        self
+    }
+
+    /// the width of each child out of `total`
+    fn widths(&self, data: &T, total: f64) -> Vec<f64> {
+        let mut ratios = self.dragged.clone().unwrap_or_else(|| (self.ratios)(data));
+        ratios.resize(self.children.len(), 0.);
+        let sum: f64 = ratios.iter().map(|r| r.max(0.)).sum();
+        if sum <= 0. {
+            return ratios.iter().map(|_| 0.).collect();
+        }
+        ratios.iter().map(|r| (total * r.max(0.) / sum).floor()).collect()
+    }
+
+    /// the edges between shown children, as (x, the child on the left, the one on the right)
+    fn edges(&self) -> Vec<(f64, usize, usize)> {
+        let mut edges = vec![];
+        let (mut x, mut last) = (0., None);
+        for (i, &width) in self.widths.iter().enumerate().filter(|(_, w)| **w > 0.) {
+            if let Some(left) = last {
+                edges.push((x, left, i));
+            }
+            x += width;
+            last = Some(i);
+        }
+        edges
+    }
+
+    /// the edge under `x`, as (the child on the left, the one on the right)
+    fn edge_at(&self, x: f64) -> Option<(usize, usize)> {
+        let mut edges = self.edges().into_iter();
+        edges.find(|(edge, _, _)| (edge - x).abs() <= GRIP).map(|(_, left, right)| (left, right))
+    }
+}
+
+impl<T: Data> Widget<T> for Columns<T> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        match event {
+            Event::MouseDown(mouse) => {
+                if let Some((left, right)) = self.edge_at(mouse.pos.x) {
+                    let widths = self.widths.clone();
+                    self.drag = Some(Drag { left, right, x: mouse.pos.x, widths });
+                    ctx.set_active(true);
+                    ctx.set_handled();
+                    return;
+                }
+            }
+            Event::MouseMove(mouse) => {
+                if let Some(drag) = &self.drag {
+                    let pair = drag.widths[drag.left] + drag.widths[drag.right];
+                    let min = MIN_WIDTH.min(pair / 2.);
+                    let left = drag.widths[drag.left] + mouse.pos.x - drag.x;
+                    let left = left.clamp(min, pair - min);
+                    let mut widths = drag.widths.clone();
+                    widths[drag.left] = left;
+                    widths[drag.right] = pair - left;
+                    self.dragged = Some(widths);
+                    ctx.set_cursor(&Cursor::ResizeLeftRight);
+                    ctx.request_layout();
+                    ctx.set_handled();
+                    return;
+                }
+                if self.edge_at(mouse.pos.x).is_some() {
+                    ctx.set_cursor(&Cursor::ResizeLeftRight);
+                } else {
+                    ctx.clear_cursor();
+                }
+            }
+            Event::MouseUp(_) if self.drag.is_some() => {
+                self.drag = None;
+                ctx.set_active(false);
+                ctx.set_handled();
+                return;
+            }
+            _ => (),
+        }
+        for child in &mut self.children {
+            child.event(ctx, event, data, env);
+        }
+    }
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        for child in &mut self.children {
+            child.lifecycle(ctx, event, data, env);
+        }
+    }
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &T, data: &T, env: &Env) {
+        if (self.ratios)(old_data) != (self.ratios)(data) {
+            // the config changed, it wins over what was dragged
+            self.dragged = None;
+            ctx.request_layout();
+        }
+        for child in &mut self.children {
+            child.update(ctx, data, env);
+        }
+    }
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        bc.debug_check("Columns");
+        let size = bc.max();
+        self.widths = self.widths(data, size.width);
+        let mut x = 0.;
+        for (child, &width) in self.children.iter_mut().zip(&self.widths) {
+            let cbc = BoxConstraints::tight(Size::new(width, size.height));
+            child.layout(ctx, &cbc, data, env);
+            child.set_origin(ctx, data, env, Point::new(x, 0.));
+            x += width;
+        }
+        size
+    }
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        for child in &mut self.children {
+            let rect: Rect = child.layout_rect();
+            if rect.width() <= 0. {
+                continue;
+            }
+            // long lines don't spill into the next column
+            ctx.with_save(|ctx| {
+                ctx.clip(rect);
+                child.paint(ctx, data, env);
+            });
+        }
+        let height = ctx.size().height;
+        let color = env.get(theme::BORDER_DARK);
+        for (x, _, _) in self.edges() {
+            ctx.stroke(Line::new((x - 0.5, 0.), (x - 0.5, height)), &color, 1.);
+        }
+    }
+}
@@ -5,4 +5,8 @@ pub mod splitn;
 pub use splitn::SplitN;
 
 pub mod ratio;
-pub use ratio::Rational;
\ No newline at end of file
+pub use ratio::Rational;
+pub mod columns;
+pub use columns::Columns;
+pub mod table;
+pub use table::{emphasized, CellRow, Cells, Header};
@@ -0,0 +1,267 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use druid::kurbo::Line;
+use druid::text::{Attribute, RichText};
+use druid::{
+    theme, BoxConstraints, Cursor, Data, Env, Event, EventCtx, FontWeight, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, TextLayout, UpdateCtx, Widget,
+};
+
+/// horizontal space kept on each side of a cell's text
+const PADDING: f64 = 4.;
+/// how close to a column's right edge dragging resizes it
+const GRIP: f64 = 4.;
+/// columns can't be resized narrower than this
+pub const MIN_WIDTH: f64 = 20.;
+
+/// A line of a table: one text per column. The widths are shared by all the lines of a table
+/// and the header, so they stay aligned.
+#[derive(Clone, Data, Default)]
+pub struct Cells {
+    pub texts: Arc<Vec<String>>,
+    pub widths: Arc<Vec<f64>>,
+    /// which columns are aligned to the right, numbers usually
+    pub right: Arc<Vec<bool>>,
+    /// `(column, start, end)`: bytes of a column's text to emphasize, what a search matched say
+    pub highlight: Option<(usize, usize, usize)>,
+}
+
+impl Cells {
+    /// left edge of each column
+    fn offsets(&self) -> impl Iterator<Item = f64> + '_ {
+        self.widths.iter().scan(0., |x, width| {
+            let left = *x;
+            *x += width;
+            Some(left)
+        })
+    }
+
+    /// the column under `x`
+    fn column_at(&self, x: f64) -> Option<usize> {
+        let widths = self.offsets().zip(self.widths.iter());
+        widths.position(|(left, width)| (left..left + width).contains(&x))
+    }
+
+    /// the column whose right edge is under `x`
+    fn edge_at(&self, x: f64) -> Option<usize> {
+        let widths = self.offsets().zip(self.widths.iter());
+        widths.position(|(left, width)| (left + width - x).abs() <= GRIP)
+    }
+}
+
+/// `text` with `range` in bold and underlined
+pub fn emphasized(text: &str, ranges: impl IntoIterator<Item = Range<usize>>) -> RichText {
+    let mut rich = RichText::new(text.into());
+    for range in ranges {
+        rich.add_attribute(range.clone(), Attribute::weight(FontWeight::BOLD));
+        rich.add_attribute(range, Attribute::underline(true));
+    }
+    rich
+}
+
+/// the laid out texts of a line
+#[derive(Default)]
+struct Texts {
+    layouts: Vec<TextLayout<RichText>>,
+}
+
+impl Texts {
+    fn set(&mut self, cells: &Cells) {
+        self.layouts.resize_with(cells.texts.len(), TextLayout::new);
+        let texts = self.layouts.iter_mut().zip(cells.texts.iter()).enumerate();
+        for (i, (layout, text)) in texts {
+            let range = match cells.highlight {
+                Some((column, start, end)) if column == i => Some(start..end),
+                _ => None,
+            };
+            layout.set_text(emphasized(text, range));
+        }
+    }
+
+    /// whether the env changed what they look like, the row's color say
+    fn changed(&mut self, ctx: &mut UpdateCtx) -> bool {
+        let mut changed = false;
+        for layout in &mut self.layouts {
+            changed |= layout.needs_rebuild_after_update(ctx);
+        }
+        changed
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        cells: &Cells,
+        env: &Env,
+    ) -> Size {
+        let mut height: f64 = 0.;
+        for layout in &mut self.layouts {
+            layout.rebuild_if_needed(ctx.text(), env);
+            height = height.max(layout.size().height);
+        }
+        bc.constrain(Size::new(cells.widths.iter().sum(), height))
+    }
+
+    fn paint(&self, ctx: &mut PaintCtx, cells: &Cells) {
+        let height = ctx.size().height;
+        let columns = self.layouts.iter().zip(cells.offsets().zip(cells.widths.iter()));
+        for (i, (layout, (left, &width))) in columns.enumerate() {
+            let text_width = layout.size().width;
+            let x = if cells.right.get(i).copied().unwrap_or(false) {
+                (left + width - PADDING - text_width).max(left + PADDING)
+            } else {
+                left + PADDING
+            };
+            // long texts don't spill into the next column
+            ctx.with_save(|ctx| {
+                ctx.clip(Rect::new(left, 0., left + width - PADDING, height));
+                layout.draw(ctx, Point::new(x, 0.));
+            });
+        }
+    }
+}
+
+/// A line of a table, see [`Cells`].
+#[derive(Default)]
+pub struct CellRow {
+    texts: Texts,
+}
+
+impl CellRow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Widget<Cells> for CellRow {
+    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut Cells, _env: &Env) {}
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &Cells, _env: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            self.texts.set(data);
+        }
+    }
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &Cells, data: &Cells, _env: &Env) {
+        if !old_data.same(data) {
+            self.texts.set(data);
+            ctx.request_layout();
+        } else if self.texts.changed(ctx) {
+            ctx.request_layout();
+        }
+    }
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &Cells,
+        env: &Env,
+    ) -> Size {
+        bc.debug_check("CellRow");
+        self.texts.layout(ctx, bc, data, env)
+    }
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &Cells, _env: &Env) {
+        self.texts.paint(ctx, data);
+    }
+}
+
+/// what the mouse is doing to a header
+enum Press {
+    /// resizing a column, from where it was grabbed and its width then
+    Resize { column: usize, x: f64, width: f64 },
+    /// clicking a column, it's a click if released over the same column
+    Click(usize),
+}
+
+/// The titles of a table's columns. Dragging their right edges resizes them, clicking one
+/// calls the header's `on_click` with its index.
+pub struct Header {
+    texts: Texts,
+    press: Option<Press>,
+    on_click: Box<dyn Fn(&mut EventCtx, usize)>,
+}
+
+impl Header {
+    pub fn new() -> Self {
+        Self { texts: Texts::default(), press: None, on_click: Box::new(|_, _| ()) }
+    }
+    pub fn on_click(mut self, f: impl Fn(&mut EventCtx, usize) + 'static) -> Self {
+        self.on_click = Box::new(f);
+        self
+    }
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Widget<Cells> for Header {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut Cells, _env: &Env) {
+        match event {
+            Event::MouseDown(mouse) => {
+                self.press = match (data.edge_at(mouse.pos.x), data.column_at(mouse.pos.x)) {
+                    (Some(column), _) => {
+                        let width = data.widths[column];
+                        Some(Press::Resize { column, x: mouse.pos.x, width })
+                    }
+                    (None, Some(column)) => Some(Press::Click(column)),
+                    (None, None) => None,
+                };
+                ctx.set_active(self.press.is_some());
+            }
+            Event::MouseMove(mouse) => match self.press {
+                Some(Press::Resize { column, x, width }) => {
+                    let width = (width + mouse.pos.x - x).max(MIN_WIDTH);
+                    Arc::make_mut(&mut data.widths)[column] = width;
+                    ctx.set_cursor(&Cursor::ResizeLeftRight);
+                }
+                _ if data.edge_at(mouse.pos.x).is_some() => {
+                    ctx.set_cursor(&Cursor::ResizeLeftRight)
+                }
+                _ => ctx.clear_cursor(),
+            },
+            Event::MouseUp(mouse) if ctx.is_active() => {
+                ctx.set_active(false);
+                if let Some(Press::Click(column)) = self.press.take() {
+                    if ctx.is_hot() && data.column_at(mouse.pos.x) == Some(column) {
+                        (self.on_click)(ctx, column);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &Cells, _env: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            self.texts.set(data);
+        }
+    }
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &Cells, data: &Cells, _env: &Env) {
+        if !old_data.same(data) {
+            self.texts.set(data);
+            ctx.request_layout();
+        } else if self.texts.changed(ctx) {
+            ctx.request_layout();
+        }
+    }
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &Cells,
+        env: &Env,
+    ) -> Size {
+        bc.debug_check("Header");
+        self.texts.layout(ctx, bc, data, env)
+    }
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &Cells, env: &Env) {
+        self.texts.paint(ctx, data);
+        let height = ctx.size().height;
+        let color = env.get(theme::BORDER_DARK);
+        for (left, width) in data.offsets().zip(data.widths.iter()) {
+            let x = left + width - 0.5;
+            ctx.stroke(Line::new((x, 0.), (x, height)), &color, 1.);
+        }
+    }
+}